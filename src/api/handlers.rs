@@ -1,28 +1,90 @@
 //! HTTP endpoint handlers
 
-use std::sync::Arc;
+use std::{convert::Infallible, sync::Arc, time::Duration};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
 };
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use serde::Deserialize;
 use tracing::{error, info, warn};
 
 use crate::{
-    services::{start_systemd_service, stop_systemd_service, force_kill_process, recover_systemd_service, ServiceConfig},
-    state::AppState,
+    services::{
+        admit_service, parse_mac, recover_systemd_service, IdleAction, ServiceConfig, WakeAlarm,
+        WakeSourceKind, DEFAULT_LOG_LINES, MAX_WAKE_MINUTES,
+    },
+    state::{is_server_hold, AppState, TimerCommand, MAX_TIMER_SECONDS, MIN_TIMER_SECONDS},
+    utils::parse_duration,
+};
+use super::responses::{
+    ApiResponse, ConfirmationResponse, HoldResponse, StatusResponse, HistoryResponse, HealthResponse,
+    TimerConfigResponse, WakeAlarmResponse, WakeSourcesResponse, WolResponse,
 };
-use super::responses::{ApiResponse, StatusResponse, HealthResponse};
 
-/// Handle POST /coffee - Enable coffee state
-pub async fn coffee_handler(State(state): State<Arc<AppState>>) -> Result<Json<ApiResponse>, StatusCode> {
-    match state.set_coffee(true) {
-        Ok(system_state) => {
-            info!("Coffee endpoint called - coffee state enabled");
-            Ok(Json(ApiResponse::active(
-                "Coffee state enabled".to_string(),
-                system_state,
-            )))
+/// Query parameters for GET /service/{service_name}/logs
+#[derive(Debug, Deserialize)]
+pub struct LogsQuery {
+    /// Number of history lines to return before following
+    pub lines: Option<usize>,
+    /// Keep the connection open and stream new lines as they are written
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// Query parameters for POST /coffee
+#[derive(Debug, Deserialize)]
+pub struct CoffeeQuery {
+    /// Lease length such as "2h" or "90m"
+    #[serde(rename = "for")]
+    pub lease: Option<String>,
+    /// End of the lease as an RFC 3339 timestamp
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Handle POST /coffee - Enable coffee state, for good or as a lease (`?for=2h`, `?until=`)
+pub async fn coffee_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CoffeeQuery>,
+) -> Result<Response, StatusCode> {
+    let now = state.clock.utc();
+    let until = match (query.lease, query.until) {
+        (None, None) => Ok(None),
+        (Some(lease), None) => parse_duration(&lease)
+            .and_then(|lease| chrono::Duration::from_std(lease).map_err(|e| e.to_string()))
+            .and_then(|lease| {
+                now.checked_add_signed(lease)
+                    .map(Some)
+                    .ok_or_else(|| format!("Lease of {} is too long", lease))
+            }),
+        (None, Some(until)) if until > now => Ok(Some(until)),
+        (None, Some(until)) => Err(format!("Lease end {} is in the past", until)),
+        (Some(_), Some(_)) => Err("Use either for or until, not both".to_string()),
+    };
+
+    let result = match until {
+        Ok(Some(until)) => state.set_coffee_lease(until)
+            .map(|system_state| (format!("Coffee state enabled until {}", until), system_state)),
+        Ok(None) => state.set_coffee(true)
+            .map(|system_state| ("Coffee state enabled".to_string(), system_state)),
+        Err(message) => {
+            return match state.get_system_state() {
+                Ok(system_state) => Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(message, system_state))).into_response()),
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            };
+        }
+    };
+
+    match result {
+        Ok((message, system_state)) => {
+            info!("Coffee endpoint called - {}", message);
+            Ok(Json(ApiResponse::active(message, system_state)).into_response())
         }
         Err(e) => {
             error!("Failed to enable coffee state: {}", e);
@@ -48,13 +110,130 @@ pub async fn chill_handler(State(state): State<Arc<AppState>>) -> Result<Json<Ap
     }
 }
 
+/// Handle POST /holds - Take a named hold; the machine stays awake while any hold exists
+pub async fn hold_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<HoldRequest>,
+) -> Result<Response, StatusCode> {
+    let ttl = if request.owner.trim().is_empty() {
+        Err("owner is required".to_string())
+    } else {
+        // At most a year, so the end of the hold and its renewals can't overflow
+        request.ttl.as_deref().map(parse_duration).transpose()
+    };
+
+    let ttl = match ttl {
+        Ok(ttl) => ttl,
+        Err(message) => {
+            return match state.get_system_state() {
+                Ok(system_state) => Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(message, system_state))).into_response()),
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            };
+        }
+    };
+
+    match state.take_hold(&request.owner, &request.reason, ttl) {
+        Ok((id, system_state)) => {
+            let Some(hold) = system_state.holds.get(&id).cloned() else {
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            };
+            Ok(Json(HoldResponse {
+                status: "active".to_string(),
+                message: format!("Hold {} taken by {}", id, hold.owner),
+                id,
+                hold,
+                states: system_state,
+            }).into_response())
+        }
+        Err(e) => {
+            error!("Failed to take hold: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Handle DELETE /holds/{id} - Release a hold; the other holds stay
+pub async fn hold_release_handler(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, StatusCode> {
+    let system_state = state.get_system_state().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let refusal = if !system_state.holds.contains_key(&id) {
+        Some((StatusCode::NOT_FOUND, format!("No hold {}", id)))
+    } else if is_server_hold(&id) {
+        Some((StatusCode::FORBIDDEN, format!("Hold {} is released by order-coffee itself", id)))
+    } else {
+        None
+    };
+    if let Some((code, message)) = refusal {
+        return Ok((code, Json(ApiResponse::error(message, system_state))).into_response());
+    }
+
+    match state.release_hold(&id) {
+        Ok(system_state) => {
+            let message = format!("Hold {} released", id);
+            let response = if system_state.any_active() {
+                ApiResponse::active(message, system_state)
+            } else {
+                ApiResponse::inactive(message, system_state)
+            };
+            Ok(Json(response).into_response())
+        }
+        Err(e) => {
+            error!("Failed to release hold {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Handle POST /holds/{id}/heartbeat - Keep a hold taken with a `ttl` for another `ttl`
+pub async fn hold_heartbeat_handler(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, StatusCode> {
+    let system_state = state.get_system_state().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let refusal = match system_state.holds.get(&id) {
+        None => Some((StatusCode::NOT_FOUND, format!("No hold {}", id))),
+        Some(hold) if hold.ttl_seconds.is_none() => {
+            Some((StatusCode::CONFLICT, format!("Hold {} has no ttl to renew", id)))
+        }
+        Some(_) => None,
+    };
+    if let Some((code, message)) = refusal {
+        return Ok((code, Json(ApiResponse::error(message, system_state))).into_response());
+    }
+
+    match state.renew_hold(&id) {
+        Ok(system_state) => {
+            // Released by the expiry task in the meantime
+            let Some(hold) = system_state.holds.get(&id).cloned() else {
+                return Ok((
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse::error(format!("No hold {}", id), system_state)),
+                ).into_response());
+            };
+            Ok(Json(HoldResponse {
+                status: "active".to_string(),
+                message: format!("Hold {} renewed", id),
+                id,
+                hold,
+                states: system_state,
+            }).into_response())
+        }
+        Err(e) => {
+            error!("Failed to renew hold {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 /// Handle POST /service/{service_name}/start - Start a systemd service
 pub async fn service_start_handler(
     Path(service_name): Path<String>,
     State(state): State<Arc<AppState>>
-) -> Result<Json<ApiResponse>, StatusCode> {
+) -> Result<Response, StatusCode> {
     // Get service configuration
-    let service_config = match ServiceConfig::from_name(&service_name) {
+    let service_config = match state.service_config(&service_name) {
         Some(config) => config,
         None => {
             warn!("Unknown service requested: {}", service_name);
@@ -67,15 +246,30 @@ pub async fn service_start_handler(
         warn!("Failed to clear {} errors: {}", service_name, e);
     }
 
+    // Make sure there is enough memory before starting heavy services
+    let admission = match admit_service(&state, &service_name, &service_config, state.admission_policy).await {
+        Ok(admission) => admission,
+        Err(reason) => {
+            warn!("Refusing to start {}: {}", service_name, reason);
+            return match state.get_system_state() {
+                Ok(system_state) => Ok((
+                    StatusCode::CONFLICT,
+                    Json(ApiResponse::error(reason, system_state)),
+                ).into_response()),
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            };
+        }
+    };
+
     // Try to start the service
-    match start_systemd_service(&service_config.service_name).await {
+    let result = match state.backend.start_service(&service_config.service_name).await {
         Ok(()) => {
             // Service started successfully, update state
             match state.set_service(&service_name, true) {
                 Ok(system_state) => {
                     info!("{} service started successfully", service_name);
                     Ok(Json(ApiResponse::active(
-                        format!("{} service started", service_name),
+                        admission.describe(format!("{} service started", service_name)),
                         system_state,
                     )))
                 }
@@ -90,13 +284,13 @@ pub async fn service_start_handler(
             if service_config.recovery_enabled {
                 warn!("Failed to start {} service: {}, attempting recovery", service_name, e);
                 
-                match recover_systemd_service(&service_config).await {
+                match recover_systemd_service(&state.backend, &service_config).await {
                     Ok(()) => {
                         match state.set_service(&service_name, true) {
                             Ok(system_state) => {
                                 info!("{} service recovered and started successfully", service_name);
                                 Ok(Json(ApiResponse::active(
-                                    format!("{} service started after recovery", service_name),
+                                    admission.describe(format!("{} service started after recovery", service_name)),
                                     system_state,
                                 )))
                             }
@@ -137,7 +331,9 @@ pub async fn service_start_handler(
                 }
             }
         }
-    }
+    };
+
+    result.map(IntoResponse::into_response)
 }
 
 /// Handle POST /service/{service_name}/stop - Stop a systemd service
@@ -160,7 +356,7 @@ pub async fn service_stop_handler(
     }
 
     // Try to stop the service
-    match stop_systemd_service(&service_config.service_name).await {
+    match state.backend.stop_service(&service_config.service_name).await {
         Ok(()) => {
             match state.set_service(&service_name, false) {
                 Ok(system_state) => {
@@ -181,7 +377,7 @@ pub async fn service_stop_handler(
             if let Some(process_name) = &service_config.process_name {
                 warn!("Failed to stop {} service: {}, attempting force kill", service_name, e);
                 
-                if let Err(kill_error) = force_kill_process(process_name).await {
+                if let Err(kill_error) = state.backend.force_kill(process_name).await {
                     warn!("Force kill also failed: {}", kill_error);
                     
                     let error_msg = format!("{} service stop failed: {}", service_name, e);
@@ -209,6 +405,499 @@ pub async fn service_stop_handler(
     }
 }
 
+/// Query parameters for POST /suspend
+#[derive(Debug, Deserialize)]
+pub struct SuspendQuery {
+    /// Override the configured idle action for this request
+    pub action: Option<IdleAction>,
+    /// Seconds to wait before running the idle action
+    pub delay: Option<u64>,
+    /// Token from a previous response, required while states are active
+    pub confirm: Option<String>,
+}
+
+/// Query parameters for POST /timer/extend and /timer/snooze
+#[derive(Debug, Deserialize)]
+pub struct TimerQuery {
+    pub minutes: Option<u64>,
+}
+
+/// Request body for PUT /config/timer
+#[derive(Debug, Deserialize)]
+pub struct TimerConfigRequest {
+    pub seconds: u64,
+}
+
+/// Request body for POST /wake-alarm; either `at` or `in_minutes` is required
+#[derive(Debug, Deserialize)]
+pub struct WakeAlarmRequest {
+    pub at: Option<DateTime<Utc>>,
+    pub in_minutes: Option<u64>,
+    pub name: Option<String>,
+    /// Managed services to start after the wake-up
+    #[serde(default)]
+    pub start_services: Vec<String>,
+    /// Minutes the machine is held awake after the wake-up
+    #[serde(default)]
+    pub hold_minutes: u64,
+}
+
+/// Request body for POST /holds
+#[derive(Debug, Deserialize)]
+pub struct HoldRequest {
+    /// Who takes the hold, e.g. a user or host name
+    pub owner: String,
+    #[serde(default)]
+    pub reason: String,
+    /// Release the hold by itself after this long, such as "2h" or "90m",
+    /// unless it is renewed with POST /holds/{id}/heartbeat
+    pub ttl: Option<String>,
+}
+
+/// Request body for PUT /wake-sources/{id}
+#[derive(Debug, Deserialize)]
+pub struct WakeSourceRequest {
+    pub enabled: bool,
+}
+
+/// Delay before sleeping so the response can reach the client first
+const SUSPEND_NOW_DELAY: Duration = Duration::from_secs(1);
+
+/// Minutes added by POST /timer/extend without `minutes`
+const DEFAULT_EXTEND_MINUTES: u64 = 15;
+
+/// Minutes a POST /timer/snooze without `minutes` postpones the suspension by
+const DEFAULT_SNOOZE_MINUTES: u64 = 10;
+
+/// Handle GET /service/{service_name}/logs - Stream the journal of a known service
+///
+/// With `--simulate` the simulated backend's in-memory log is streamed instead.
+///
+/// Responds with Server-Sent Events when the client accepts `text/event-stream`,
+/// otherwise with a chunked plain-text body (one journal line per line).
+pub async fn service_logs_handler(
+    Path(service_name): Path<String>,
+    Query(query): Query<LogsQuery>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    // Only units we manage may be read, never arbitrary journal units
+    let service_config = match ServiceConfig::from_name(&service_name) {
+        Some(config) => config,
+        None => {
+            warn!("Logs requested for unknown service: {}", service_name);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let lines = query.lines.unwrap_or(DEFAULT_LOG_LINES);
+    let log_stream = match state.backend.stream_logs(&service_config.service_name, lines, query.follow) {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to read {} logs: {}", service_name, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    info!("Streaming {} logs (lines={}, follow={})", service_name, lines, query.follow);
+
+    let wants_sse = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map(|accept| accept.contains("text/event-stream"))
+        .unwrap_or(false);
+
+    if wants_sse {
+        // Read errors end the stream; the client sees the connection close
+        let events = log_stream
+            .take_while(|line| futures::future::ready(line.is_ok()))
+            .map(|line| Ok::<_, Infallible>(Event::default().data(line.unwrap_or_default())));
+        Ok(Sse::new(events).keep_alive(KeepAlive::default()).into_response())
+    } else {
+        let body = Body::from_stream(log_stream.map(|line| line.map(|line| format!("{}\n", line))));
+        Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response())
+    }
+}
+
+/// Handle POST /suspend - Run the idle action (or the requested one) right away
+///
+/// While any state is active the first request only returns a confirmation
+/// token; repeating the request with `?confirm=<token>` runs the idle action.
+pub async fn suspend_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SuspendQuery>,
+) -> Result<Response, StatusCode> {
+    let system_state = match state.get_system_state() {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get system state: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let action = query.action.unwrap_or(state.idle_action);
+    let capabilities = state.backend.sleep_capabilities(&state.sysfs_root);
+    if let Err(e) = capabilities.check(action, state.idle_command.as_deref()) {
+        warn!("Rejected suspend request: {}", e);
+        return Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(e, system_state))).into_response());
+    }
+
+    if query.delay.is_some_and(|delay| delay > MAX_TIMER_SECONDS) {
+        let message = format!("Delay must be at most {} seconds", MAX_TIMER_SECONDS);
+        return Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(message, system_state))).into_response());
+    }
+    let delay = query.delay.map(Duration::from_secs).unwrap_or(SUSPEND_NOW_DELAY).max(SUSPEND_NOW_DELAY);
+
+    // Sleeping while something is in use needs a second, confirmed request
+    let confirmed = query.confirm
+        .as_deref()
+        .is_some_and(|token| state.redeem_suspend_token(token, action, delay));
+    if system_state.any_active() && !confirmed {
+        let (token, expires_at) = match state.issue_suspend_token(action, delay) {
+            Ok(issued) => issued,
+            Err(e) => {
+                error!("Failed to issue confirmation token: {}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+        let message = format!(
+            "Active: {}. Repeat with ?confirm={} to run {} anyway",
+            system_state.active_holds().join(", "), token, action
+        );
+        info!("Suspend request needs confirmation: {}", message);
+        return Ok((StatusCode::ACCEPTED, Json(ConfirmationResponse {
+            status: "confirm".to_string(),
+            message,
+            token,
+            expires_at,
+            states: system_state,
+        })).into_response());
+    }
+
+    info!("Suspend endpoint called - running {} in {}s", action, delay.as_secs());
+    timer_command_response(&state, TimerCommand::SuspendNow { action, delay }, &format!("suspend-{}", action)).await
+}
+
+/// Handle POST /timer/extend - Push the running countdown back by `minutes` (default 15)
+pub async fn timer_extend_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TimerQuery>,
+) -> Result<Response, StatusCode> {
+    let extra = match timer_minutes(query.minutes.unwrap_or(DEFAULT_EXTEND_MINUTES)) {
+        Ok(extra) => extra,
+        Err(message) => return bad_request(&state, message),
+    };
+    timer_command_response(&state, TimerCommand::Extend(extra), "timer-extend").await
+}
+
+/// Handle POST /timer/snooze - Restart the countdown with `minutes` (default 10)
+pub async fn timer_snooze_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TimerQuery>,
+) -> Result<Response, StatusCode> {
+    let duration = match timer_minutes(query.minutes.unwrap_or(DEFAULT_SNOOZE_MINUTES)) {
+        Ok(duration) => duration,
+        Err(message) => return bad_request(&state, message),
+    };
+    timer_command_response(&state, TimerCommand::Snooze(duration), "timer-snooze").await
+}
+
+/// `minutes` of a timer command, at most as long as the longest timer
+fn timer_minutes(minutes: u64) -> Result<Duration, String> {
+    match minutes.checked_mul(60) {
+        Some(seconds) if seconds <= MAX_TIMER_SECONDS => Ok(Duration::from_secs(seconds)),
+        _ => Err(format!("minutes must be at most {}", MAX_TIMER_SECONDS / 60)),
+    }
+}
+
+/// Answer `400 Bad Request` with `message` and the current states
+fn bad_request(state: &AppState, message: String) -> Result<Response, StatusCode> {
+    match state.get_system_state() {
+        Ok(system_state) => Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(message, system_state))).into_response()),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Handle POST /timer/reset - Restart the countdown with the full timer duration
+pub async fn timer_reset_handler(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    timer_command_response(&state, TimerCommand::Reset, "timer-reset").await
+}
+
+/// Handle POST /timer/cancel - Stop the countdown until the next state change
+pub async fn timer_cancel_handler(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    timer_command_response(&state, TimerCommand::Cancel, "timer-cancel").await
+}
+
+/// Handle POST /timer/grace/cancel - End the post-resume grace period early
+pub async fn timer_grace_cancel_handler(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    timer_command_response(&state, TimerCommand::EndGrace, "grace-cancel").await
+}
+
+/// Handle POST /wake-alarm - Wake the machine up at a given time if it is asleep by then
+pub async fn wake_alarm_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<WakeAlarmRequest>,
+) -> Result<Response, StatusCode> {
+    let now = state.clock.utc();
+    let at = match (request.at, request.in_minutes) {
+        (Some(at), None) => Ok(at),
+        (None, Some(minutes)) => i64::try_from(minutes)
+            .ok()
+            .filter(|_| minutes <= MAX_WAKE_MINUTES)
+            .and_then(chrono::Duration::try_minutes)
+            .and_then(|delay| now.checked_add_signed(delay))
+            .ok_or_else(|| format!("in_minutes must be at most {}", MAX_WAKE_MINUTES)),
+        _ => Err("Exactly one of at or in_minutes is required".to_string()),
+    }
+    .and_then(|at| if request.hold_minutes <= MAX_WAKE_MINUTES {
+        Ok(at)
+    } else {
+        Err(format!("hold_minutes must be at most {}", MAX_WAKE_MINUTES))
+    })
+    .and_then(|at| if at > now { Ok(at) } else { Err(format!("Wake alarm time {} is in the past", at)) })
+    .and_then(|at| if at.signed_duration_since(now) <= chrono::Duration::minutes(MAX_WAKE_MINUTES as i64) {
+        Ok(at)
+    } else {
+        Err(format!("Wake alarm time {} is more than {} minutes ahead", at, MAX_WAKE_MINUTES))
+    })
+    .and_then(|at| match request.start_services.iter().find(|name| ServiceConfig::from_name(name).is_none()) {
+        Some(name) => Err(format!("Unknown service: {}", name)),
+        None => Ok(at),
+    });
+
+    let at = match at {
+        Ok(at) => at,
+        Err(message) => {
+            return match state.get_system_state() {
+                Ok(system_state) => Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(message, system_state))).into_response()),
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            };
+        }
+    };
+
+    let alarm = WakeAlarm {
+        name: request.name.unwrap_or_else(|| "wake-alarm".to_string()),
+        at,
+        start_services: request.start_services,
+        hold_minutes: request.hold_minutes,
+    };
+    if let Err(e) = state.request_wake_alarm(alarm.clone()) {
+        error!("Failed to request wake alarm: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    state.record_action("wake-alarm");
+
+    Ok(Json(WakeAlarmResponse {
+        message: format!("Wake alarm {} set for {}", alarm.name, alarm.at),
+        requested: Some(alarm),
+        next_wake_alarm: state.next_wake_alarm(),
+    }).into_response())
+}
+
+/// Handle DELETE /wake-alarm - Drop the requested wake-up; scheduled ones stay
+pub async fn wake_alarm_cancel_handler(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    let cancelled = match state.cancel_wake_alarm() {
+        Ok(cancelled) => cancelled,
+        Err(e) => {
+            error!("Failed to cancel wake alarm: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let Some(alarm) = cancelled else {
+        return match state.get_system_state() {
+            Ok(system_state) => Ok((
+                StatusCode::CONFLICT,
+                Json(ApiResponse::error("No wake alarm is requested".to_string(), system_state)),
+            ).into_response()),
+            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+    };
+    state.record_action("wake-alarm-cancel");
+
+    Ok(Json(WakeAlarmResponse {
+        message: format!("Wake alarm {} for {} cancelled", alarm.name, alarm.at),
+        requested: None,
+        next_wake_alarm: state.next_wake_alarm(),
+    }).into_response())
+}
+
+/// Handle POST /wol/{host} - Send a Wake-on-LAN magic packet to a configured peer
+pub async fn wol_handler(
+    Path(host): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, StatusCode> {
+    let Some(peer) = state.wol.peer(&host) else {
+        warn!("Unknown Wake-on-LAN peer requested: {}", host);
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let result = match parse_mac(&peer.mac) {
+        Ok(mac) => state.backend.send_magic_packet(mac, &peer.broadcast).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        error!("Failed to wake {}: {}", host, e);
+        return match state.get_system_state() {
+            Ok(system_state) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e, system_state))).into_response()),
+            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+    }
+
+    info!("Magic packet for {} ({}) sent to {}", host, peer.mac, peer.broadcast);
+    state.record_action(&format!("wol-{}", host));
+    Ok(Json(WolResponse {
+        message: format!("Magic packet sent to {}", host),
+        host,
+        mac: peer.mac.clone(),
+        broadcast: peer.broadcast.clone(),
+    }).into_response())
+}
+
+/// Handle GET /wake-sources - List the devices allowed to wake this machine
+pub async fn wake_sources_handler(State(state): State<Arc<AppState>>) -> Result<Response, StatusCode> {
+    wake_sources_response(&state)
+}
+
+/// Handle PUT /wake-sources/{id} - Allow or forbid a device to wake this machine
+pub async fn wake_source_update_handler(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<WakeSourceRequest>,
+) -> Result<Response, StatusCode> {
+    let source = state.backend
+        .wake_sources(&state.sysfs_root, &state.procfs_root)
+        .map(|sources| sources.into_iter().find(|source| source.id == id));
+    let source = match source {
+        Ok(Some(source)) => source,
+        Ok(None) => {
+            warn!("Unknown wake source requested: {}", id);
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Err(e) => {
+            error!("Failed to read wake sources: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let Err(e) = state.backend.set_wake_source(&state.sysfs_root, &state.procfs_root, &source, request.enabled) {
+        error!("Failed to update wake source {}: {}", id, e);
+        return match state.get_system_state() {
+            Ok(system_state) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(e, system_state))).into_response()),
+            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+    }
+
+    info!("Wake source {} {}", id, if request.enabled { "enabled" } else { "disabled" });
+    state.record_action(&format!("wake-source-{}-{}", id, if request.enabled { "on" } else { "off" }));
+    wake_sources_response(&state)
+}
+
+/// Build the GET /wake-sources response
+fn wake_sources_response(state: &AppState) -> Result<Response, StatusCode> {
+    let sources = match state.backend.wake_sources(&state.sysfs_root, &state.procfs_root) {
+        Ok(sources) => sources,
+        Err(e) => {
+            error!("Failed to read wake sources: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let wake_on_lan = sources.iter().any(|source| source.kind == WakeSourceKind::Net && source.enabled);
+    Ok(Json(WakeSourcesResponse { sources, wake_on_lan }).into_response())
+}
+
+/// Handle GET /config/timer - Return the idle timer duration
+pub async fn timer_config_handler(State(state): State<Arc<AppState>>) -> Result<Json<TimerConfigResponse>, StatusCode> {
+    timer_config_response(&state).map(Json)
+}
+
+/// Handle PUT /config/timer - Change the idle timer duration, re-computing a running countdown
+pub async fn timer_config_update_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<TimerConfigRequest>,
+) -> Result<Response, StatusCode> {
+    if !(MIN_TIMER_SECONDS..=MAX_TIMER_SECONDS).contains(&request.seconds) {
+        let message = format!(
+            "Timer must be between {} and {} seconds", MIN_TIMER_SECONDS, MAX_TIMER_SECONDS
+        );
+        return match state.get_system_state() {
+            Ok(system_state) => Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(message, system_state))).into_response()),
+            Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+    }
+
+    let previous = match state.set_timer_duration(Duration::from_secs(request.seconds)) {
+        Ok(previous) => previous,
+        Err(e) => {
+            error!("Failed to set timer duration: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    state.record_action("timer-config");
+
+    // The new value applies even if it can't be saved, the error shows up in /status
+    let _ = state.clear_errors_for("timer setting");
+    if let Err(e) = state.update_persisted(|persisted| persisted.timer_seconds = Some(request.seconds)) {
+        if let Err(e) = state.add_error(format!("Failed to save timer setting: {}", e)) {
+            error!("Failed to add error to state: {}", e);
+        }
+    }
+
+    match state.send_timer_command(TimerCommand::DurationChanged { previous }).await {
+        Ok(message) => info!("Timer duration set to {}s: {}", request.seconds, message),
+        Err(e) => warn!("Running countdown not re-computed: {}", e),
+    }
+
+    timer_config_response(&state).map(|response| Json(response).into_response())
+}
+
+/// Build the GET /config/timer response
+fn timer_config_response(state: &AppState) -> Result<TimerConfigResponse, StatusCode> {
+    let timer_state = match state.get_timer_state() {
+        Ok(t) => t,
+        Err(e) => {
+            error!("Failed to get timer state: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok(TimerConfigResponse {
+        seconds: state.get_timer_duration().as_secs(),
+        timer_active: timer_state.active,
+        timer_remaining_seconds: timer_state.remaining_seconds(state.clock.now()),
+        suspend_at: timer_state.suspend_at,
+    })
+}
+
+/// Send a command to the timer task; success is recorded as `action`, refusals are 409s
+async fn timer_command_response(
+    state: &AppState,
+    command: TimerCommand,
+    action: &str,
+) -> Result<Response, StatusCode> {
+    let outcome = state.send_timer_command(command).await;
+    if outcome.is_ok() {
+        state.record_action(action);
+    }
+
+    let system_state = match state.get_system_state() {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to get system state: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match outcome {
+        Ok(message) => Ok(Json(ApiResponse::new("timer".to_string(), message, system_state)).into_response()),
+        Err(e) => {
+            warn!("Timer command {:?} refused: {}", command, e);
+            Ok((StatusCode::CONFLICT, Json(ApiResponse::error(e, system_state))).into_response())
+        }
+    }
+}
+
 /// Handle GET /status - Return current system status
 pub async fn status_handler(State(state): State<Arc<AppState>>) -> Result<Json<StatusResponse>, StatusCode> {
     let system_state = match state.get_system_state() {
@@ -227,12 +916,38 @@ pub async fn status_handler(State(state): State<Arc<AppState>>) -> Result<Json<S
         }
     };
 
+    let phase = match state.get_phase() {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to get suspend phase: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
     let (last_action, last_action_time) = state.get_last_action();
+    let (inhibitor_active, inhibitor_reason) = state.get_inhibitor();
+    let last_hook_run = state.get_last_hook_run();
+    let coffee_remaining_seconds = system_state.coffee_until
+        .filter(|_| system_state.coffee)
+        .map(|until| (until - state.clock.utc()).num_seconds().max(0) as u64);
     
     Ok(Json(StatusResponse {
         states: system_state,
+        coffee_remaining_seconds,
+        phase: phase.phase,
+        phase_entered_at: phase.entered_at,
         timer_active: timer_state.active,
-        timer_remaining_seconds: timer_state.remaining_seconds,
+        timer_remaining_seconds: timer_state.remaining_seconds(state.clock.now()),
+        suspend_at: timer_state.suspend_at,
+        quiet_timer_seconds: state.get_quiet_timer().map(|quiet| quiet.as_secs()),
+        grace_remaining_seconds: timer_state.grace_remaining_seconds(state.clock.now()),
+        grace_ends_at: timer_state.grace_ends_at,
+        idle_stages: timer_state.stages,
+        blocking_inhibitors: timer_state.blocking_inhibitors,
+        next_wake_alarm: state.next_wake_alarm(),
+        inhibitor_active,
+        inhibitor_reason,
+        last_hook_run,
         uptime: state.get_uptime(),
         port: state.port,
         host: state.host.clone(),
@@ -241,6 +956,17 @@ pub async fn status_handler(State(state): State<Arc<AppState>>) -> Result<Json<S
     }))
 }
 
+/// Handle GET /history - Return recent events such as sleeps
+pub async fn history_handler(State(state): State<Arc<AppState>>) -> Result<Json<HistoryResponse>, StatusCode> {
+    match state.get_history() {
+        Ok(entries) => Ok(Json(HistoryResponse { entries })),
+        Err(e) => {
+            error!("Failed to get history: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 /// Handle GET /health - Health check endpoint
 pub async fn health_handler() -> Json<HealthResponse> {
     Json(HealthResponse::ok())
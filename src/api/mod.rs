@@ -7,7 +7,7 @@ pub mod responses;
 
 use std::sync::Arc;
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
@@ -20,10 +20,26 @@ pub fn create_router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/coffee", post(coffee_handler))
         .route("/chill", post(chill_handler))
+        .route("/holds", post(hold_handler))
+        .route("/holds/:id", delete(hold_release_handler))
+        .route("/holds/:id/heartbeat", post(hold_heartbeat_handler))
         // New generic service endpoints
         .route("/service/:service_name/start", post(service_start_handler))
         .route("/service/:service_name/stop", post(service_stop_handler))
+        .route("/service/:service_name/logs", get(service_logs_handler))
+        .route("/suspend", post(suspend_handler))
+        .route("/timer/extend", post(timer_extend_handler))
+        .route("/timer/snooze", post(timer_snooze_handler))
+        .route("/timer/reset", post(timer_reset_handler))
+        .route("/timer/cancel", post(timer_cancel_handler))
+        .route("/timer/grace/cancel", post(timer_grace_cancel_handler))
+        .route("/wake-alarm", post(wake_alarm_handler).delete(wake_alarm_cancel_handler))
+        .route("/wol/:host", post(wol_handler))
+        .route("/wake-sources", get(wake_sources_handler))
+        .route("/wake-sources/:id", put(wake_source_update_handler))
+        .route("/config/timer", get(timer_config_handler).put(timer_config_update_handler))
         .route("/status", get(status_handler))
+        .route("/history", get(history_handler))
         .route("/health", get(health_handler))
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http())
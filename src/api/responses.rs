@@ -3,7 +3,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::state::SystemState;
+use crate::{
+    services::{HookRun, IdleStageStatus, Inhibitor, WakeAlarm, WakeSource},
+    state::{HistoryEntry, Hold, SuspendPhase, SystemState},
+};
 
 /// API response structure for state change endpoints
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,12 +44,52 @@ impl ApiResponse {
     }
 }
 
+/// Response asking the client to confirm a request with a token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfirmationResponse {
+    pub status: String,
+    pub message: String,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub states: SystemState,
+}
+
+/// Response to POST /holds with the id to release the hold with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoldResponse {
+    pub status: String,
+    pub message: String,
+    pub id: String,
+    pub hold: Hold,
+    pub states: SystemState,
+}
+
 /// Enhanced status response with timer information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusResponse {
     pub states: SystemState,
+    /// Time left on the coffee lease (`/coffee?for=`)
+    pub coffee_remaining_seconds: Option<u64>,
+    pub phase: SuspendPhase,
+    pub phase_entered_at: DateTime<Utc>,
     pub timer_active: bool,
     pub timer_remaining_seconds: Option<u64>,
+    /// When the idle action will run, for rendering countdowns locally
+    pub suspend_at: Option<DateTime<Utc>>,
+    /// Shorter idle timer of the quiet hours in effect
+    pub quiet_timer_seconds: Option<u64>,
+    /// Post-resume grace period holding the timer
+    pub grace_remaining_seconds: Option<u64>,
+    pub grace_ends_at: Option<DateTime<Utc>>,
+    /// Idle stages of the running countdown
+    pub idle_stages: Vec<IdleStageStatus>,
+    /// Inhibitors of other applications holding off the suspension
+    pub blocking_inhibitors: Vec<Inhibitor>,
+    pub inhibitor_active: bool,
+    pub inhibitor_reason: Option<String>,
+    /// Wake-up programmed into the RTC before the next sleep
+    pub next_wake_alarm: Option<WakeAlarm>,
+    pub last_hook_run: Option<HookRun>,
     pub uptime: String,
     pub port: u16,
     pub host: String,
@@ -54,6 +97,46 @@ pub struct StatusResponse {
     pub last_action_time: Option<DateTime<Utc>>,
 }
 
+/// Idle timer configuration and the state of the running countdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimerConfigResponse {
+    pub seconds: u64,
+    pub timer_active: bool,
+    pub timer_remaining_seconds: Option<u64>,
+    pub suspend_at: Option<DateTime<Utc>>,
+}
+
+/// The requested one-off wake-up and the next wake-up overall
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WakeAlarmResponse {
+    pub message: String,
+    pub requested: Option<WakeAlarm>,
+    pub next_wake_alarm: Option<WakeAlarm>,
+}
+
+/// A magic packet sent to a Wake-on-LAN peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WolResponse {
+    pub message: String,
+    pub host: String,
+    pub mac: String,
+    pub broadcast: String,
+}
+
+/// Devices allowed to wake this machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WakeSourcesResponse {
+    pub sources: Vec<WakeSource>,
+    /// Whether a network interface may wake the machine, so it answers magic packets
+    pub wake_on_lan: bool,
+}
+
+/// History response with recent events, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryResponse {
+    pub entries: Vec<HistoryEntry>,
+}
+
 /// Health check response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthResponse {
@@ -1,6 +1,41 @@
 //! Configuration and CLI argument handling
 
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 use clap::Parser;
+use serde::Deserialize;
+
+use crate::{
+    services::{
+        validate_service_settings, AdmissionPolicy, HooksConfig, IdleAction, IdlePolicyConfig,
+        InhibitorPolicyConfig, CalendarConfig, ScheduleConfig, ServiceSettings, SimulationConfig,
+        WakeOnLanConfig, WakeScheduleConfig,
+    },
+    state::{BlipPolicy, TimerHysteresis, DEFAULT_STATE_FILE, MAX_TIMER_SECONDS, MIN_TIMER_SECONDS},
+};
+
+/// Settings read from the TOML file given with `--config`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    /// Memory requirement and priority of the managed services, keyed by name
+    pub services: HashMap<String, ServiceSettings>,
+    /// Pre-suspend and post-resume hooks
+    pub hooks: HooksConfig,
+    /// Stages run while the suspension countdown is running
+    pub idle: IdlePolicyConfig,
+    /// Other applications' inhibitors to respect before suspending
+    pub inhibitors: InhibitorPolicyConfig,
+    /// Daily wake-ups programmed into the RTC before sleeping
+    pub wake: WakeScheduleConfig,
+    /// Machines woken through POST /wol/:host
+    pub wol: WakeOnLanConfig,
+    /// Work hours, quiet hours and timed service starts/stops
+    pub schedule: ScheduleConfig,
+    /// Keep-awake holds from a local .ics file
+    pub calendar: CalendarConfig,
+    /// Simulated host settings used with `--simulate`
+    pub simulation: SimulationConfig,
+}
 
 /// CLI argument parsing structure
 #[derive(Parser)]
@@ -16,10 +51,70 @@ pub struct Config {
     #[arg(long, default_value = "0.0.0.0")]
     pub host: String,
 
-    /// Suspension timer duration in minutes
+    /// Path to a TOML configuration file (hooks, ...)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Suspension timer duration in minutes (a value set with PUT /config/timer takes precedence)
     #[arg(short, long, default_value = "10")]
     pub timer: u64,
 
+    /// Seconds everything must stay inactive before the suspension timer starts
+    #[arg(long, default_value = "0")]
+    pub debounce_seconds: u64,
+
+    /// What a brief activity does to a running countdown
+    #[arg(long, value_enum, default_value = "reset")]
+    pub blip_policy: BlipPolicy,
+
+    /// Longest activity (in seconds) that `--blip-policy pause` resumes the countdown after
+    #[arg(long, default_value = "60")]
+    pub blip_seconds: u64,
+
+    /// Seconds the suspension timer stays held after a wake-up
+    #[arg(long, default_value = "120")]
+    pub resume_grace_seconds: u64,
+
+    /// File keeping settings changed at runtime [default: /var/lib/order-coffee/state.json, none with --simulate]
+    #[arg(long)]
+    pub state_file: Option<PathBuf>,
+
+    /// What to do when the suspension timer expires
+    #[arg(long, value_enum, default_value = "suspend")]
+    pub idle_action: IdleAction,
+
+    /// Shell command run by the "custom" idle action
+    #[arg(long)]
+    pub idle_command: Option<String>,
+
+    /// Seconds between service watchdog checks (0 disables the watchdog)
+    #[arg(long, default_value = "30")]
+    pub watchdog_interval: u64,
+
+    /// Restart attempts for a crashed service before it is marked failed
+    #[arg(long, default_value = "3")]
+    pub watchdog_max_restarts: u32,
+
+    /// What to do when a service's memory requirement is not met
+    #[arg(long, value_enum, default_value = "reject")]
+    pub admission_policy: AdmissionPolicy,
+
+    /// Where sysfs is mounted (power states, CPU governors, RTC wake alarm)
+    #[arg(long, default_value = "/sys")]
+    pub sysfs_root: PathBuf,
+
+    /// Where procfs is mounted (ACPI wake devices)
+    #[arg(long, default_value = "/proc")]
+    pub procfs_root: PathBuf,
+
+    /// Don't hold a logind sleep inhibitor lock while states are active
+    #[arg(long)]
+    pub no_inhibitor: bool,
+
+    /// Replace systemctl, pkill and suspension with a simulated host
+    #[arg(long)]
+    pub simulate: bool,
+
     /// Enable verbose logging
     #[arg(short, long)]
     pub verbose: bool,
@@ -31,6 +126,66 @@ impl Config {
         Parser::parse()
     }
 
+    /// Check the arguments that are turned into durations, so adding them to
+    /// an `Instant` can't overflow; `--timer` gets the bounds of PUT /config/timer
+    pub fn validate(&self) -> Result<(), String> {
+        let timer_range = MIN_TIMER_SECONDS..=MAX_TIMER_SECONDS;
+        if !self.timer.checked_mul(60).is_some_and(|seconds| timer_range.contains(&seconds)) {
+            return Err(format!(
+                "--timer of {} minutes must be between {} and {} seconds",
+                self.timer, MIN_TIMER_SECONDS, MAX_TIMER_SECONDS
+            ));
+        }
+        let seconds = [
+            ("--debounce-seconds", self.debounce_seconds),
+            ("--blip-seconds", self.blip_seconds),
+            ("--resume-grace-seconds", self.resume_grace_seconds),
+        ];
+        match seconds.iter().find(|(_, value)| *value > MAX_TIMER_SECONDS) {
+            Some((name, _)) => Err(format!("{} must be at most {}", name, MAX_TIMER_SECONDS)),
+            None => Ok(()),
+        }
+    }
+
+    /// Load the configuration file, or defaults when none was given
+    pub fn load_file(&self) -> Result<FileConfig, String> {
+        let Some(path) = &self.config else {
+            return Ok(FileConfig::default());
+        };
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        let file_config: FileConfig = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse config file {}: {}", path.display(), e))?;
+        validate_service_settings(&file_config.services)
+            .and_then(|_| file_config.idle.validate())
+            .and_then(|_| file_config.inhibitors.validate())
+            .and_then(|_| file_config.wake.validate())
+            .and_then(|_| file_config.schedule.validate())
+            .and_then(|_| file_config.calendar.validate())
+            .and_then(|_| file_config.simulation.validate())
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+        Ok(file_config)
+    }
+
+    /// Get the state file to use; simulations only persist when asked to
+    pub fn state_file(&self) -> Option<PathBuf> {
+        match &self.state_file {
+            Some(path) => Some(path.clone()),
+            None if self.simulate => None,
+            None => Some(PathBuf::from(DEFAULT_STATE_FILE)),
+        }
+    }
+
+    /// Debounce and blip handling of the suspension timer
+    pub fn hysteresis(&self) -> TimerHysteresis {
+        TimerHysteresis {
+            debounce: Duration::from_secs(self.debounce_seconds),
+            blip_policy: self.blip_policy,
+            blip_window: Duration::from_secs(self.blip_seconds),
+        }
+    }
+
     /// Get the server address as a formatted string
     pub fn address(&self) -> String {
         format!("{}:{}", self.host, self.port)
@@ -10,8 +10,13 @@ use order_coffee::{
     config::Config,
     state::AppState,
     api::create_router,
-    services::{check_systemctl_available, initialize_service_state, ServiceConfig},
-    tasks::{suspension_timer_task, wake_up_recovery_task},
+    services::{
+        initialize_service_state, Backend, HookPipeline, ServiceConfig, SimulatedHost,
+    },
+    tasks::{
+        calendar_task, hold_expiry_task, schedule_task, service_watchdog_task, suspension_timer_task,
+        wake_up_recovery_task,
+    },
     utils::shutdown_signal,
 };
 
@@ -25,17 +30,64 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     info!("Starting order-coffee server v2.0.0");
-    info!("Configuration: host={}, port={}, timer={}min", 
-          config.host, config.port, config.timer);
+    info!("Configuration: host={}, port={}, timer={}min, idle_action={}", 
+          config.host, config.port, config.timer, config.idle_action);
+
+    if let Err(e) = config.validate() {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    }
+
+    let file_config = match config.load_file() {
+        Ok(file_config) => file_config,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Pick the backend: real systemd, or a simulated host that never touches the machine
+    let backend = if config.simulate {
+        info!("Simulation mode: no services are touched and the machine never really sleeps");
+        Backend::Simulated(Box::new(SimulatedHost::new(file_config.simulation.clone())))
+    } else {
+        Backend::Systemd
+    };
 
     // Check if systemctl is available (required for ollama service management and suspension)
-    if let Err(e) = check_systemctl_available().await {
+    if let Err(e) = backend.check_available().await {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    }
+
+    // Make sure the machine can actually perform the configured idle action
+    let capabilities = backend.sleep_capabilities(&config.sysfs_root);
+    if let Err(e) = capabilities.check(config.idle_action, config.idle_command.as_deref()) {
         tracing::error!("{}", e);
         std::process::exit(1);
     }
 
     // Create application state
-    let state = Arc::new(AppState::new(config.port, config.host.clone(), config.timer));
+    let state = Arc::new(
+        AppState::new(config.port, config.host.clone(), config.timer)
+            .with_admission_policy(config.admission_policy)
+            .with_service_settings(file_config.services)
+            .with_inhibitor(!config.no_inhibitor)
+            .with_idle_action(config.idle_action, config.idle_command.clone())
+            .with_hooks(HookPipeline::new(file_config.hooks))
+            .with_idle_policy(file_config.idle)
+            .with_hysteresis(config.hysteresis())
+            .with_inhibitor_policy(file_config.inhibitors)
+            .with_wake_schedule(file_config.wake)
+            .with_wake_on_lan(file_config.wol)
+            .with_schedule(file_config.schedule)
+            .with_calendar(file_config.calendar)
+            .with_sysfs_root(config.sysfs_root.clone())
+            .with_procfs_root(config.procfs_root.clone())
+            .with_resume_grace(Duration::from_secs(config.resume_grace_seconds))
+            .with_backend(backend)
+            .with_state_file(config.state_file()),
+    );
 
     // Start the suspension timer background task
     let timer_state = Arc::clone(&state);
@@ -49,6 +101,36 @@ async fn main() -> anyhow::Result<()> {
         wake_up_recovery_task(recovery_state).await;
     });
 
+    // Start the hold expiry background task
+    let hold_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        hold_expiry_task(hold_state).await;
+    });
+
+    // Start the schedule background task (returns right away without schedules)
+    let schedule_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        schedule_task(schedule_state).await;
+    });
+
+    // Start the calendar background task (returns right away without a calendar)
+    let calendar_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        calendar_task(calendar_state).await;
+    });
+
+    // Start the service watchdog background task
+    if config.watchdog_interval > 0 {
+        let watchdog_state = Arc::clone(&state);
+        let watchdog_interval = Duration::from_secs(config.watchdog_interval);
+        let watchdog_max_restarts = config.watchdog_max_restarts;
+        tokio::spawn(async move {
+            service_watchdog_task(watchdog_state, watchdog_interval, watchdog_max_restarts).await;
+        });
+    } else {
+        info!("Service watchdog disabled");
+    }
+
     // INITIAL STATE MANAGEMENT =============================
     
     
@@ -65,24 +147,24 @@ async fn main() -> anyhow::Result<()> {
     // Initialize service states to match server's initial state
     sleep(Duration::from_millis(6000)).await;
     let ollama_config = ServiceConfig::ollama();
-    if let Err(e) = initialize_service_state(&ollama_config, false).await {
+    if let Err(e) = initialize_service_state(&state.backend, &ollama_config, false).await {
         tracing::warn!("Failed to initialize ollama service state: {}", e);
     }
     sleep(Duration::from_millis(6000)).await;
     let comfy_unsafe_config = ServiceConfig::comfy_unsafe();
-    if let Err(e) = initialize_service_state(&comfy_unsafe_config, false).await {
+    if let Err(e) = initialize_service_state(&state.backend, &comfy_unsafe_config, false).await {
         tracing::warn!("Failed to initialize comfy-unsafe service state: {}", e);
     }
     sleep(Duration::from_millis(6000)).await;
     let comfy_safe_config = ServiceConfig::comfy_safe();
-    if let Err(e) = initialize_service_state(&comfy_safe_config, false).await {
+    if let Err(e) = initialize_service_state(&state.backend, &comfy_safe_config, false).await {
         tracing::warn!("Failed to initialize comfy-safe service state: {}", e);
     }
 
     // INITIATE HTTP ROUTER SERVER =============================
 
     // Create HTTP router with all endpoints
-    let app = create_router(state);
+    let app = create_router(Arc::clone(&state));
 
     // Bind to the specified address
     let addr = config.address();
@@ -92,9 +174,27 @@ async fn main() -> anyhow::Result<()> {
     info!("Endpoints:");
     info!("  POST /coffee                    - Enable coffee state");
     info!("  POST /chill                     - Disable coffee state");
+    info!("  POST /holds                     - Take a named hold (owner, reason, ttl)");
+    info!("  DELETE /holds/_id_              - Release a named hold");
+    info!("  POST /holds/_id_/heartbeat      - Renew a hold taken with a ttl");
     info!("  POST /service/_service_name_/start      - Start a systemd service");
     info!("  POST /service/_service_name_/stop        - Stop a systemd service");
+    info!("  GET  /service/_service_name_/logs        - Stream a service journal (?lines=&follow=)");
+    info!("  POST /suspend                   - Run the idle action now (?action=&delay=&confirm=)");
+    info!("  POST /timer/extend              - Push the countdown back (?minutes=)");
+    info!("  POST /timer/snooze              - Restart the countdown with ?minutes=");
+    info!("  POST /timer/reset               - Restart the countdown with the full duration");
+    info!("  POST /timer/cancel              - Stop the countdown until the next state change");
+    info!("  POST /timer/grace/cancel        - End the post-resume grace period");
+    info!("  GET  /config/timer              - Get the idle timer duration");
+    info!("  PUT  /config/timer              - Change the idle timer duration ({{\"seconds\": N}})");
+    info!("  POST /wake-alarm                - Wake the machine up at a given time ({{\"at\": ...}})");
+    info!("  DELETE /wake-alarm              - Cancel the requested wake-up");
+    info!("  POST /wol/_host_                - Send a Wake-on-LAN packet to a configured peer");
+    info!("  GET  /wake-sources              - Devices allowed to wake this machine");
+    info!("  PUT  /wake-sources/_id_         - Allow or forbid a device to wake this machine ({{\"enabled\": true}})");
     info!("  GET  /status                    - Check current status and timer");
+    info!("  GET  /history                   - Recent events (sleeps, ...)");
     info!("  GET  /health                    - Health check");
 
     // Setup graceful shutdown
@@ -111,6 +211,9 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    // Let the machine sleep normally once we're gone
+    state.release_inhibitor();
+
     info!("Server shutdown complete");
     Ok(())
 }
@@ -0,0 +1,250 @@
+//! Service backend abstraction over systemd and the simulated host
+
+use std::{io, path::Path, time::Duration};
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use tokio::process::Command;
+
+use super::{
+    idle_policy::{restore_cpu_governors, write_cpu_governor, GovernorSetting},
+    inhibitor_policy::{list_logind_inhibitors, Inhibitor},
+    logs::{stream_journal_lines, MAX_LOG_LINES},
+    memory::read_available_memory,
+    services::{
+        check_systemd_service_status, force_kill_process, reload_systemd_daemon,
+        restart_systemd_service, start_systemd_service, stop_systemd_service,
+    },
+    simulation::SimulatedHost,
+    system::{check_systemctl_available, read_suspend_successes, IdleAction, SleepCapabilities},
+    wake_alarm::{clear_rtc_wake_alarm, write_rtc_wake_alarm},
+    wake_on_lan::{read_wake_sources, send_magic_packet, write_wake_source, WakeSource},
+};
+
+/// The backend controlling services and the machine's sleep state.
+///
+/// Every systemctl, pkill and idle action call goes through the backend so that
+/// `--simulate` can replace them all at once.
+#[derive(Debug, Default)]
+pub enum Backend {
+    /// The real machine, controlled through systemctl
+    #[default]
+    Systemd,
+    /// An in-memory machine for demos and tests
+    Simulated(Box<SimulatedHost>),
+}
+
+impl Backend {
+    /// Check whether this backend is simulated
+    pub fn is_simulated(&self) -> bool {
+        matches!(self, Backend::Simulated(_))
+    }
+
+    /// Make sure the backend can be used at all
+    pub async fn check_available(&self) -> Result<(), String> {
+        match self {
+            Backend::Systemd => check_systemctl_available().await,
+            Backend::Simulated(_) => Ok(()),
+        }
+    }
+
+    /// Start a unit
+    pub async fn start_service(&self, service_name: &str) -> Result<(), String> {
+        match self {
+            Backend::Systemd => start_systemd_service(service_name).await,
+            Backend::Simulated(sim) => sim.start_service(service_name).await,
+        }
+    }
+
+    /// Stop a unit
+    pub async fn stop_service(&self, service_name: &str) -> Result<(), String> {
+        match self {
+            Backend::Systemd => stop_systemd_service(service_name).await,
+            Backend::Simulated(sim) => sim.stop_service(service_name).await,
+        }
+    }
+
+    /// Restart a unit
+    pub async fn restart_service(&self, service_name: &str) -> Result<(), String> {
+        match self {
+            Backend::Systemd => restart_systemd_service(service_name).await,
+            Backend::Simulated(sim) => sim.restart_service(service_name).await,
+        }
+    }
+
+    /// Check whether a unit is active
+    pub async fn is_service_active(&self, service_name: &str) -> Result<bool, String> {
+        match self {
+            Backend::Systemd => check_systemd_service_status(service_name).await,
+            Backend::Simulated(sim) => sim.is_service_active(service_name).await,
+        }
+    }
+
+    /// Force kill processes by name
+    pub async fn force_kill(&self, process_name: &str) -> Result<(), String> {
+        match self {
+            Backend::Systemd => force_kill_process(process_name).await,
+            Backend::Simulated(sim) => sim.force_kill(process_name).await,
+        }
+    }
+
+    /// Reload the service manager configuration
+    pub async fn reload_daemon(&self) -> Result<(), String> {
+        match self {
+            Backend::Systemd => reload_systemd_daemon().await,
+            Backend::Simulated(_) => Ok(()),
+        }
+    }
+
+    /// Run an idle action; `custom_command` is used for `IdleAction::Custom`
+    pub async fn run_idle_action(&self, action: IdleAction, custom_command: Option<&str>) -> Result<(), String> {
+        match self {
+            Backend::Systemd => run_systemd_idle_action(action, custom_command).await,
+            Backend::Simulated(sim) => sim.run_idle_action(action).await,
+        }
+    }
+
+    /// Number of successful suspends so far, if the kernel reports it
+    pub fn suspend_successes(&self, sysfs_root: &Path) -> Option<u64> {
+        match self {
+            Backend::Systemd => read_suspend_successes(sysfs_root),
+            Backend::Simulated(sim) => Some(sim.suspend_successes()),
+        }
+    }
+
+    /// Inhibitor locks currently held on the machine
+    pub async fn list_inhibitors(&self) -> Result<Vec<Inhibitor>, String> {
+        match self {
+            Backend::Systemd => list_logind_inhibitors().await,
+            Backend::Simulated(sim) => Ok(sim.inhibitors()),
+        }
+    }
+
+    /// Sleep states the machine supports
+    pub fn sleep_capabilities(&self, sysfs_root: &Path) -> SleepCapabilities {
+        match self {
+            Backend::Systemd => SleepCapabilities::read(sysfs_root),
+            Backend::Simulated(sim) => sim.sleep_capabilities(),
+        }
+    }
+
+    /// Switch every CPU to `governor`, returning what to restore later
+    pub fn set_cpu_governor(&self, sysfs_root: &Path, governor: &str) -> Result<Vec<GovernorSetting>, String> {
+        match self {
+            Backend::Systemd => write_cpu_governor(sysfs_root, governor),
+            Backend::Simulated(sim) => Ok(sim.set_cpu_governor(governor)),
+        }
+    }
+
+    /// Restore CPU governors saved by `set_cpu_governor`
+    pub fn restore_cpu_governors(&self, sysfs_root: &Path, settings: &[GovernorSetting]) -> Result<(), String> {
+        match self {
+            Backend::Systemd => restore_cpu_governors(sysfs_root, settings),
+            Backend::Simulated(sim) => {
+                sim.restore_cpu_governors(settings);
+                Ok(())
+            }
+        }
+    }
+
+    /// Program the RTC to wake the machine at `at` (`from_now` for the simulated
+    /// host, which has no wall clock of its own)
+    pub fn set_wake_alarm(&self, sysfs_root: &Path, at: DateTime<Utc>, from_now: Duration) -> Result<(), String> {
+        match self {
+            Backend::Systemd => write_rtc_wake_alarm(sysfs_root, at),
+            Backend::Simulated(sim) => {
+                sim.set_wake_alarm(from_now);
+                Ok(())
+            }
+        }
+    }
+
+    /// Clear the RTC wake alarm
+    pub fn clear_wake_alarm(&self, sysfs_root: &Path) -> Result<(), String> {
+        match self {
+            Backend::Systemd => clear_rtc_wake_alarm(sysfs_root),
+            Backend::Simulated(sim) => {
+                sim.clear_wake_alarm();
+                Ok(())
+            }
+        }
+    }
+
+    /// Send a Wake-on-LAN magic packet for `mac` to `target`
+    pub async fn send_magic_packet(&self, mac: [u8; 6], target: &str) -> Result<(), String> {
+        match self {
+            Backend::Systemd => send_magic_packet(mac, target).await,
+            Backend::Simulated(sim) => {
+                sim.send_magic_packet(mac, target);
+                Ok(())
+            }
+        }
+    }
+
+    /// Devices that may be allowed to wake the machine
+    pub fn wake_sources(&self, sysfs_root: &Path, procfs_root: &Path) -> Result<Vec<WakeSource>, String> {
+        match self {
+            Backend::Systemd => read_wake_sources(sysfs_root, procfs_root),
+            Backend::Simulated(sim) => Ok(sim.wake_sources()),
+        }
+    }
+
+    /// Allow or forbid a wake source to wake the machine
+    pub fn set_wake_source(&self, sysfs_root: &Path, procfs_root: &Path, source: &WakeSource, enabled: bool) -> Result<(), String> {
+        match self {
+            Backend::Systemd => write_wake_source(sysfs_root, procfs_root, source, enabled),
+            Backend::Simulated(sim) => {
+                sim.set_wake_source(&source.id, enabled);
+                Ok(())
+            }
+        }
+    }
+
+    /// Available memory in bytes
+    pub async fn available_memory(&self) -> Result<u64, String> {
+        match self {
+            Backend::Systemd => read_available_memory().await,
+            Backend::Simulated(sim) => sim.available_memory().await,
+        }
+    }
+
+    /// Stream the log of a unit (journal or in-memory ring buffer), with at most
+    /// `MAX_LOG_LINES` lines of history
+    pub fn stream_logs(
+        &self,
+        service_name: &str,
+        lines: usize,
+        follow: bool,
+    ) -> Result<BoxStream<'static, Result<String, io::Error>>, String> {
+        let lines = lines.min(MAX_LOG_LINES);
+        match self {
+            Backend::Systemd => stream_journal_lines(service_name, lines, follow).map(StreamExt::boxed),
+            Backend::Simulated(sim) => Ok(sim.stream_logs(service_name, lines, follow)),
+        }
+    }
+}
+
+/// Run an idle action through systemctl or the custom command
+async fn run_systemd_idle_action(action: IdleAction, custom_command: Option<&str>) -> Result<(), String> {
+    let output = match action.systemctl_verb() {
+        Some(verb) => Command::new("systemctl")
+            .arg(verb)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to execute systemctl {}: {}", verb, e))?,
+        None => {
+            let command = custom_command.ok_or_else(|| "No idle command configured".to_string())?;
+            Command::new("sh")
+                .args(["-c", command])
+                .output()
+                .await
+                .map_err(|e| format!("Failed to execute idle command: {}", e))?
+        }
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} failed: {}", action, stderr));
+    }
+
+    Ok(())
+}
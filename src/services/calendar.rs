@@ -0,0 +1,393 @@
+//! Keep-awake holds from a local iCalendar (.ics) file
+//!
+//! Only what booking calendars need is understood: `VEVENT`s with `DTSTART`,
+//! `DTEND` or `DURATION`, `SUMMARY`, `UID` and `STATUS`, and recurrences with a
+//! daily or weekly `RRULE` (`INTERVAL`, `COUNT` and `UNTIL`), `EXDATE` and
+//! `RECURRENCE-ID` overrides. Times with a `TZID` are taken as local time.
+
+use std::{collections::HashSet, fs, path::{Path, PathBuf}, time::SystemTime};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+use tracing::warn;
+
+use super::services::ServiceConfig;
+
+fn default_poll_seconds() -> u64 {
+    60
+}
+
+/// Calendar holds from the `[calendar]` config table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// The .ics file to read; no calendar holds without one
+    pub path: Option<PathBuf>,
+    /// Only events whose summary contains this text (case-insensitive) hold the machine
+    pub summary_contains: Option<String>,
+    /// Managed service to start when a matching event begins
+    pub start_service: Option<String>,
+    /// How often the file is checked for changes and events for their start and end
+    pub poll_seconds: u64,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            summary_contains: None,
+            start_service: None,
+            poll_seconds: default_poll_seconds(),
+        }
+    }
+}
+
+impl CalendarConfig {
+    /// Check whether an event should hold the machine awake
+    pub fn matches(&self, event: &CalendarEvent) -> bool {
+        self.summary_contains
+            .as_ref()
+            .is_none_or(|text| event.summary.to_lowercase().contains(&text.to_lowercase()))
+    }
+
+    /// Check that the service to start is a managed one
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.start_service {
+            Some(name) if ServiceConfig::from_name(name).is_none() => {
+                Err(format!("Unknown service in calendar config: {}", name))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A daily or weekly `RRULE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    /// Days between two occurrences (`INTERVAL`, in weeks for weekly rules)
+    pub every_days: i64,
+    /// Number of occurrences, the first one included (`COUNT`)
+    pub count: Option<i64>,
+    /// Latest start of an occurrence (`UNTIL`)
+    pub until: Option<DateTime<Utc>>,
+    /// Occurrences keep their local time of day across DST changes (the start
+    /// was not given in UTC)
+    pub local: bool,
+}
+
+/// A calendar event; a recurring one stands for all of its occurrences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarEvent {
+    pub uid: Option<String>,
+    pub summary: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub recurrence: Option<Recurrence>,
+    /// Starts of occurrences that were removed (`EXDATE`, cancelled overrides)
+    pub exdates: Vec<DateTime<Utc>>,
+    /// Start of the occurrence this event replaces (`RECURRENCE-ID`)
+    pub recurrence_id: Option<DateTime<Utc>>,
+}
+
+impl CalendarEvent {
+    /// Name of the hold kept while this occurrence is running
+    pub fn hold_name(&self) -> String {
+        let id = self.uid.as_deref().unwrap_or(&self.summary);
+        format!("calendar:{}@{}", id, self.start.format("%Y%m%dT%H%M%SZ"))
+    }
+
+    /// Check whether the event is running at `now`, ignoring its recurrence
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.start <= now && now < self.end
+    }
+
+    /// The occurrences of this event running at `now`
+    pub fn occurrences_at(&self, now: DateTime<Utc>) -> Vec<CalendarEvent> {
+        let Some(recurrence) = &self.recurrence else {
+            return if self.is_active(now) { vec![self.clone()] } else { Vec::new() };
+        };
+        if now < self.start {
+            return Vec::new();
+        }
+
+        // Occurrences longer than the interval overlap, so look back far enough
+        let length = self.end - self.start;
+        let latest = (now - self.start).num_days() / recurrence.every_days;
+        let overlapping = length.num_days() / recurrence.every_days + 1;
+        ((latest - overlapping).max(0)..=latest + 1)
+            .filter(|n| recurrence.count.is_none_or(|count| *n < count))
+            .filter_map(|n| self.nth_start(recurrence, n))
+            .filter(|start| recurrence.until.is_none_or(|until| *start <= until))
+            .filter(|start| !self.exdates.contains(start))
+            .filter_map(|start| Some(CalendarEvent {
+                end: start.checked_add_signed(length)?,
+                start,
+                recurrence: None,
+                exdates: Vec::new(),
+                recurrence_id: Some(start),
+                ..self.clone()
+            }))
+            .filter(|occurrence| occurrence.is_active(now))
+            .collect()
+    }
+
+    /// Start of the `n`th occurrence (the event itself is the 0th)
+    fn nth_start(&self, recurrence: &Recurrence, n: i64) -> Option<DateTime<Utc>> {
+        let offset = Duration::try_days(n.checked_mul(recurrence.every_days)?)?;
+        if recurrence.local {
+            local_to_utc(self.start.with_timezone(&Local).naive_local().checked_add_signed(offset)?)
+        } else {
+            self.start.checked_add_signed(offset)
+        }
+    }
+}
+
+/// The occurrences of `events` running at `now`; occurrences replaced by a
+/// `RECURRENCE-ID` override only run as the override
+pub fn running_events(events: &[CalendarEvent], now: DateTime<Utc>) -> Vec<CalendarEvent> {
+    let overridden: HashSet<(Option<&str>, DateTime<Utc>)> = events
+        .iter()
+        .filter_map(|event| Some((event.uid.as_deref(), event.recurrence_id?)))
+        .collect();
+    let overridden = &overridden;
+
+    events
+        .iter()
+        .flat_map(|event| {
+            let is_override = event.recurrence_id.is_some();
+            event.occurrences_at(now)
+                .into_iter()
+                .filter(move |occurrence| {
+                    is_override || !overridden.contains(&(occurrence.uid.as_deref(), occurrence.start))
+                })
+        })
+        .collect()
+}
+
+/// Modification time and size, to notice when the file has to be read again
+pub type CalendarVersion = (Option<SystemTime>, u64);
+
+/// The current version of the calendar file
+pub fn calendar_version(path: &Path) -> Result<CalendarVersion, String> {
+    let metadata = fs::metadata(path)
+        .map_err(|e| format!("Failed to read calendar {}: {}", path.display(), e))?;
+    Ok((metadata.modified().ok(), metadata.len()))
+}
+
+/// Read and parse the calendar file
+pub fn read_calendar(path: &Path) -> Result<Vec<CalendarEvent>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read calendar {}: {}", path.display(), e))?;
+    Ok(parse_ics(&content))
+}
+
+/// Parse the events of an iCalendar document, skipping cancelled and unreadable
+/// ones as well as those with a recurrence rule that can't be expanded
+pub fn parse_ics(content: &str) -> Vec<CalendarEvent> {
+    let mut events = Vec::new();
+    let mut cancelled = Vec::new();
+    let mut current: Option<Vec<(String, String)>> = None;
+
+    for line in unfold(content) {
+        let Some((name_and_params, value)) = line.split_once(':') else {
+            continue;
+        };
+        match (name_and_params, value) {
+            ("BEGIN", "VEVENT") => current = Some(Vec::new()),
+            ("END", "VEVENT") => {
+                let Some(properties) = current.take() else {
+                    continue;
+                };
+                if is_cancelled(&properties) {
+                    // A cancelled override removes its occurrence
+                    let uid = find_property(&properties, "UID").map(|(_, uid)| uid.clone());
+                    if let Some(at) = find_property(&properties, "RECURRENCE-ID")
+                        .and_then(|(params, value)| parse_date_time(params, value))
+                    {
+                        cancelled.push((uid, at.0));
+                    }
+                } else {
+                    match event_from(&properties) {
+                        Ok(Some(event)) => events.push(event),
+                        Ok(None) => {}
+                        Err(e) => warn!("Skipping calendar {}", e),
+                    }
+                }
+            }
+            _ => {
+                if let Some(properties) = current.as_mut() {
+                    properties.push((name_and_params.to_string(), value.to_string()));
+                }
+            }
+        }
+    }
+
+    for (uid, at) in cancelled {
+        events
+            .iter_mut()
+            .filter(|event| event.recurrence.is_some() && event.uid == uid)
+            .for_each(|event| event.exdates.push(at));
+    }
+    events
+}
+
+/// Join continuation lines (starting with a space or tab) to the line before
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// The first property called `name` among `NAME;PARAMS` / value pairs
+fn find_property<'a>(properties: &'a [(String, String)], name: &str) -> Option<&'a (String, String)> {
+    properties
+        .iter()
+        .find(|(name_and_params, _)| name_and_params.split(';').next() == Some(name))
+}
+
+fn is_cancelled(properties: &[(String, String)]) -> bool {
+    find_property(properties, "STATUS").is_some_and(|(_, status)| status == "CANCELLED")
+}
+
+/// Build an event from its `NAME;PARAMS` / value pairs; `None` if it can't be read
+fn event_from(properties: &[(String, String)]) -> Result<Option<CalendarEvent>, String> {
+    let property = |name: &str| find_property(properties, name);
+
+    let Some((start_params, start_value)) = property("DTSTART") else {
+        return Ok(None);
+    };
+    let Some((start, all_day)) = parse_date_time(start_params, start_value) else {
+        return Ok(None);
+    };
+    let end = match (property("DTEND"), property("DURATION")) {
+        (Some((params, value)), _) => parse_date_time(params, value).map(|(end, _)| end),
+        (None, Some((_, value))) => parse_duration(value).and_then(|length| start.checked_add_signed(length)),
+        // An all-day event without an end lasts the day
+        (None, None) if all_day => start.checked_add_signed(Duration::days(1)),
+        (None, None) => Some(start),
+    };
+    let Some(end) = end else {
+        return Ok(None);
+    };
+
+    let uid = property("UID").map(|(_, uid)| uid.clone());
+    let recurrence = match property("RRULE") {
+        Some((_, rule)) => Some(parse_rrule(rule, !start_value.ends_with('Z')).map_err(|e| {
+            format!("event {}: {}", uid.as_deref().unwrap_or("without UID"), e)
+        })?),
+        None => None,
+    };
+    let exdates = properties
+        .iter()
+        .filter(|(name_and_params, _)| name_and_params.split(';').next() == Some("EXDATE"))
+        .flat_map(|(params, values)| values.split(',').filter_map(|value| parse_date_time(params, value)))
+        .map(|(at, _)| at)
+        .collect();
+
+    Ok(Some(CalendarEvent {
+        uid,
+        summary: property("SUMMARY").map(|(_, summary)| unescape(summary)).unwrap_or_default(),
+        start,
+        end,
+        recurrence,
+        exdates,
+        recurrence_id: property("RECURRENCE-ID")
+            .and_then(|(params, value)| parse_date_time(params, value))
+            .map(|(at, _)| at),
+    }))
+}
+
+/// Parse an `RRULE` such as "FREQ=WEEKLY;INTERVAL=2;COUNT=10"
+fn parse_rrule(rule: &str, local: bool) -> Result<Recurrence, String> {
+    let unsupported = || format!(
+        "unsupported recurrence rule {:?} (only FREQ=DAILY or WEEKLY with INTERVAL, COUNT and UNTIL)",
+        rule
+    );
+    let mut days = None;
+    let mut interval = 1;
+    let mut count = None;
+    let mut until = None;
+
+    for part in rule.split(';') {
+        let (name, value) = part.split_once('=').ok_or_else(unsupported)?;
+        match (name, value) {
+            ("FREQ", "DAILY") => days = Some(1),
+            ("FREQ", "WEEKLY") => days = Some(7),
+            ("INTERVAL", value) => interval = value.parse::<i64>().ok().filter(|n| *n > 0).ok_or_else(unsupported)?,
+            ("COUNT", value) => count = Some(value.parse::<i64>().map_err(|_| unsupported())?),
+            ("UNTIL", value) => {
+                let (at, all_day) = parse_date_time("", value).ok_or_else(unsupported)?;
+                // A date includes occurrences starting later that day
+                until = Some(if all_day { at + Duration::days(1) - Duration::seconds(1) } else { at });
+            }
+            ("WKST", _) => {}
+            _ => return Err(unsupported()),
+        }
+    }
+
+    let every_days = days
+        .and_then(|days: i64| days.checked_mul(interval))
+        .ok_or_else(unsupported)?;
+    Ok(Recurrence { every_days, count, until, local })
+}
+
+/// Parse a `DATE` or `DATE-TIME` value; returns whether it was a date only
+fn parse_date_time(params: &str, value: &str) -> Option<(DateTime<Utc>, bool)> {
+    if (params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME")) || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((local_to_utc(date.and_hms_opt(0, 0, 0)?)?, true));
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&naive), false));
+    }
+
+    // Floating time, or a TZID we can't resolve without a time zone database
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((local_to_utc(naive)?, false))
+}
+
+fn local_to_utc(naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local.from_local_datetime(&naive).earliest().map(|local| local.with_timezone(&Utc))
+}
+
+/// Parse a `DURATION` value such as "PT1H30M" or "P1D"
+fn parse_duration(value: &str) -> Option<Duration> {
+    let rest = value.strip_prefix('+').unwrap_or(value).strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            'T' => continue,
+            '0'..='9' => number.push(c),
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                let part = match unit {
+                    'W' => Duration::try_weeks(n),
+                    'D' => Duration::try_days(n),
+                    'H' => Duration::try_hours(n),
+                    'M' => Duration::try_minutes(n),
+                    'S' => Duration::try_seconds(n),
+                    _ => return None,
+                };
+                total = total.checked_add(&part?)?;
+            }
+        }
+    }
+    Some(total)
+}
+
+/// Undo iCalendar text escaping
+fn unescape(text: &str) -> String {
+    text.replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
@@ -0,0 +1,237 @@
+//! Pre-suspend and post-resume hook pipeline
+
+use std::{sync::Mutex, time::Duration};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{process::Command, time::timeout};
+use tracing::{info, warn};
+
+use crate::utils::Clock;
+use super::{backend::Backend, services::ServiceConfig};
+
+fn default_hook_timeout() -> u64 {
+    30
+}
+
+/// A single hook from the `[[hooks.pre_suspend]]` / `[[hooks.post_resume]]` config tables.
+///
+/// A hook may stop services, run a shell command, and/or restore previously
+/// stopped services; the steps run in that order.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    pub name: String,
+    /// Shell command run through `sh -c`
+    pub command: Option<String>,
+    /// Managed services to stop if their unit is running (pre-suspend)
+    #[serde(default)]
+    pub stop_services: Vec<String>,
+    /// Start again the services stopped by pre-suspend hooks (post-resume)
+    #[serde(default)]
+    pub restore_services: bool,
+    /// Seconds the whole hook may take before it is counted as failed
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_seconds: u64,
+    /// A failing pre-suspend hook vetoes the suspension
+    #[serde(default)]
+    pub can_veto: bool,
+}
+
+/// Hook configuration by stage
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    pub pre_suspend: Vec<HookConfig>,
+    pub post_resume: Vec<HookConfig>,
+}
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    PreSuspend,
+    PostResume,
+}
+
+/// Result of running a single hook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookResult {
+    pub name: String,
+    pub success: bool,
+    pub message: String,
+    pub duration_ms: u64,
+}
+
+/// Results of running all hooks of one stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookRun {
+    pub stage: HookStage,
+    pub finished_at: DateTime<Utc>,
+    pub results: Vec<HookResult>,
+    /// Reason given by the pre-suspend hook that vetoed the suspension
+    pub vetoed: Option<String>,
+}
+
+/// Ordered hook pipeline, remembering which services were stopped before suspending
+#[derive(Debug, Default)]
+pub struct HookPipeline {
+    config: HooksConfig,
+    stopped_services: Mutex<Vec<String>>,
+}
+
+impl HookPipeline {
+    /// Create a pipeline from configuration
+    pub fn new(config: HooksConfig) -> Self {
+        Self {
+            config,
+            stopped_services: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Check whether any hook is configured for a stage
+    pub fn has_hooks(&self, stage: HookStage) -> bool {
+        !self.hooks_for(stage).is_empty()
+    }
+
+    fn hooks_for(&self, stage: HookStage) -> &[HookConfig] {
+        match stage {
+            HookStage::PreSuspend => &self.config.pre_suspend,
+            HookStage::PostResume => &self.config.post_resume,
+        }
+    }
+
+    /// Run all hooks of a stage in order.
+    ///
+    /// In the pre-suspend stage the first failing hook with `can_veto` stops the
+    /// pipeline; services stopped so far are started again since the machine
+    /// stays awake.
+    pub async fn run(&self, backend: &Backend, clock: &dyn Clock, stage: HookStage) -> HookRun {
+        let mut results = Vec::new();
+        let mut vetoed = None;
+
+        for hook in self.hooks_for(stage) {
+            info!("Running {:?} hook '{}'", stage, hook.name);
+            let started = clock.now();
+
+            let outcome = match timeout(Duration::from_secs(hook.timeout_seconds), self.run_hook(backend, hook)).await {
+                Ok(outcome) => outcome,
+                Err(_) => Err(format!("timed out after {}s", hook.timeout_seconds)),
+            };
+
+            let result = HookResult {
+                name: hook.name.clone(),
+                success: outcome.is_ok(),
+                message: match &outcome {
+                    Ok(message) => message.clone(),
+                    Err(e) => e.clone(),
+                },
+                duration_ms: clock.now().saturating_duration_since(started).as_millis() as u64,
+            };
+
+            if !result.success {
+                warn!("Hook '{}' failed: {}", hook.name, result.message);
+            }
+
+            let veto = stage == HookStage::PreSuspend && hook.can_veto && !result.success;
+            if veto {
+                vetoed = Some(format!("{}: {}", hook.name, result.message));
+            }
+            results.push(result);
+            if veto {
+                break;
+            }
+        }
+
+        if vetoed.is_some() {
+            // The machine stays awake, so bring back what we stopped
+            for message in self.restore_stopped_services(backend).await {
+                info!("{}", message);
+            }
+        }
+
+        HookRun {
+            stage,
+            finished_at: clock.utc(),
+            results,
+            vetoed,
+        }
+    }
+
+    /// Run the steps of a single hook, returning a short description of what was done
+    async fn run_hook(&self, backend: &Backend, hook: &HookConfig) -> Result<String, String> {
+        let mut messages = Vec::new();
+
+        for service_name in &hook.stop_services {
+            messages.push(self.stop_service(backend, service_name).await?);
+        }
+
+        if let Some(command) = &hook.command {
+            messages.push(run_hook_command(command).await?);
+        }
+
+        if hook.restore_services {
+            messages.extend(self.restore_stopped_services(backend).await);
+        }
+
+        Ok(messages.join("; "))
+    }
+
+    /// Stop a managed service if its unit is running and remember it for restoring
+    async fn stop_service(&self, backend: &Backend, service_name: &str) -> Result<String, String> {
+        let config = ServiceConfig::from_name(service_name)
+            .ok_or_else(|| format!("unknown service {}", service_name))?;
+
+        if !backend.is_service_active(&config.service_name).await? {
+            return Ok(format!("{} not running", service_name));
+        }
+
+        backend.stop_service(&config.service_name).await?;
+        if let Ok(mut stopped) = self.stopped_services.lock() {
+            stopped.push(service_name.to_string());
+        }
+        Ok(format!("stopped {}", service_name))
+    }
+
+    /// Start again every service stopped by pre-suspend hooks
+    pub async fn restore_stopped_services(&self, backend: &Backend) -> Vec<String> {
+        let stopped: Vec<String> = match self.stopped_services.lock() {
+            Ok(mut stopped) => stopped.drain(..).collect(),
+            Err(_) => Vec::new(),
+        };
+
+        let mut messages = Vec::new();
+        for service_name in stopped {
+            let Some(config) = ServiceConfig::from_name(&service_name) else {
+                continue;
+            };
+            match backend.start_service(&config.service_name).await {
+                Ok(()) => messages.push(format!("restarted {}", service_name)),
+                Err(e) => messages.push(format!("failed to restart {}: {}", service_name, e)),
+            }
+        }
+        messages
+    }
+}
+
+/// Run a hook command; a non-zero exit is a failure whose reason is the command's output
+async fn run_hook_command(command: &str) -> Result<String, String> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("failed to execute hook command: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+    if output.status.success() {
+        Ok(if stdout.is_empty() { "ok".to_string() } else { stdout })
+    } else {
+        let reason = [stdout, stderr]
+            .into_iter()
+            .find(|text| !text.is_empty())
+            .unwrap_or_else(|| format!("exited with {}", output.status));
+        Err(reason)
+    }
+}
@@ -0,0 +1,174 @@
+//! Staged idle policy: things to do while the suspension countdown runs
+
+use std::{fs, path::Path, time::Duration};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::state::{SystemState, MAX_TIMER_SECONDS};
+use super::{backend::Backend, services::ServiceConfig};
+
+/// A stage from the `[[idle.stages]]` config tables.
+///
+/// Stages run once the machine has been idle for `after_minutes`; the idle
+/// action itself always runs when the suspension timer expires. Any activity
+/// cancels the stages that haven't run yet and reverts the CPU governor.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdleStageConfig {
+    pub name: String,
+    /// Idle minutes before the stage runs
+    pub after_minutes: u64,
+    /// Managed services to stop if their unit runs without being held
+    #[serde(default)]
+    pub stop_services: Vec<String>,
+    /// CPU frequency governor to switch every CPU to (e.g. "powersave")
+    pub cpu_governor: Option<String>,
+}
+
+impl IdleStageConfig {
+    /// Idle time before the stage runs
+    pub fn after(&self) -> Duration {
+        Duration::from_secs(self.after_minutes.saturating_mul(60))
+    }
+}
+
+/// Staged idle policy from the `[idle]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdlePolicyConfig {
+    pub stages: Vec<IdleStageConfig>,
+}
+
+impl IdlePolicyConfig {
+    /// Stages ordered by when they run
+    pub fn sorted(mut self) -> Self {
+        self.stages.sort_by_key(|stage| stage.after_minutes);
+        self
+    }
+
+    /// Check that every stage can run within the longest countdown
+    pub fn validate(&self) -> Result<(), String> {
+        let max_minutes = MAX_TIMER_SECONDS / 60;
+        match self.stages.iter().find(|stage| stage.after_minutes > max_minutes) {
+            Some(stage) => Err(format!(
+                "Idle stage {} runs after {} minutes, at most {} are allowed",
+                stage.name, stage.after_minutes, max_minutes
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Progress of a stage in the running countdown, reported in `/status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleStageStatus {
+    pub name: String,
+    /// When the stage runs (or ran)
+    pub at: DateTime<Utc>,
+    pub done: bool,
+}
+
+/// A CPU and the governor it had before an idle stage changed it
+pub type GovernorSetting = (String, String);
+
+/// Stop the services of an idle stage whose unit runs without being held;
+/// returns what went wrong
+pub async fn stop_idle_services(
+    backend: &Backend,
+    stage: &IdleStageConfig,
+    system_state: &SystemState,
+) -> Vec<String> {
+    info!("Running idle stage {}", stage.name);
+    let mut errors = Vec::new();
+
+    for service_name in &stage.stop_services {
+        let Some(config) = ServiceConfig::from_name(service_name) else {
+            errors.push(format!("unknown service {}", service_name));
+            continue;
+        };
+        if system_state.get_service(service_name) {
+            continue;
+        }
+
+        match backend.is_service_active(&config.service_name).await {
+            Ok(true) => match backend.stop_service(&config.service_name).await {
+                Ok(()) => info!("Idle stage {} stopped {}", stage.name, service_name),
+                Err(e) => errors.push(format!("failed to stop {}: {}", service_name, e)),
+            },
+            Ok(false) => {}
+            Err(e) => errors.push(format!("failed to check {}: {}", service_name, e)),
+        }
+    }
+    errors
+}
+
+/// Switch the CPU governor of an idle stage, if it has one; returns the
+/// governors to restore once activity resumes
+pub fn switch_idle_governor(
+    backend: &Backend,
+    sysfs_root: &Path,
+    stage: &IdleStageConfig,
+) -> Result<Vec<GovernorSetting>, String> {
+    let Some(governor) = &stage.cpu_governor else {
+        return Ok(Vec::new());
+    };
+    let previous = backend.set_cpu_governor(sysfs_root, governor)?;
+    info!("Idle stage {} switched the CPU governor to {}", stage.name, governor);
+    Ok(previous)
+}
+
+/// Switch every CPU to `governor`; returns the previous governor of each changed CPU
+pub fn write_cpu_governor(sysfs_root: &Path, governor: &str) -> Result<Vec<GovernorSetting>, String> {
+    let cpus_dir = sysfs_root.join("devices/system/cpu");
+    let entries = fs::read_dir(&cpus_dir)
+        .map_err(|e| format!("Failed to read {}: {}", cpus_dir.display(), e))?;
+
+    let mut previous = Vec::new();
+    for entry in entries.flatten() {
+        let cpu = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path().join("cpufreq/scaling_governor");
+        if !cpu.starts_with("cpu") || !path.exists() {
+            continue;
+        }
+
+        let current = match fs::read_to_string(&path) {
+            Ok(current) => current.trim().to_string(),
+            Err(e) => {
+                let error = format!("Failed to read {}: {}", path.display(), e);
+                return Err(undo_cpu_governors(sysfs_root, &previous, error));
+            }
+        };
+        if current == governor {
+            continue;
+        }
+        if let Err(e) = fs::write(&path, governor) {
+            let error = format!("Failed to set {} governor to {}: {}", cpu, governor, e);
+            return Err(undo_cpu_governors(sysfs_root, &previous, error));
+        }
+        previous.push((cpu, current));
+    }
+
+    if previous.is_empty() {
+        warn!("No CPU changed to the {} governor", governor);
+    }
+    Ok(previous)
+}
+
+/// Put back the CPUs a failed `write_cpu_governor` already switched
+fn undo_cpu_governors(sysfs_root: &Path, switched: &[GovernorSetting], error: String) -> String {
+    match restore_cpu_governors(sysfs_root, switched) {
+        Ok(()) => error,
+        Err(e) => format!("{}; {}", error, e),
+    }
+}
+
+/// Put back the governors saved by `write_cpu_governor`
+pub fn restore_cpu_governors(sysfs_root: &Path, settings: &[GovernorSetting]) -> Result<(), String> {
+    for (cpu, governor) in settings {
+        let path = sysfs_root.join("devices/system/cpu").join(cpu).join("cpufreq/scaling_governor");
+        fs::write(&path, governor)
+            .map_err(|e| format!("Failed to restore {} governor to {}: {}", cpu, governor, e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! logind sleep inhibitor lock management
+
+use std::process::Stdio;
+use tokio::process::{Child, Command};
+use tracing::{debug, info, warn};
+
+/// Name reported to logind as the inhibitor owner ("who")
+pub const INHIBITOR_WHO: &str = "order-coffee";
+
+/// A logind `sleep:idle` block inhibitor held through a `systemd-inhibit` child.
+///
+/// The lock is held for as long as the child process lives. Dropping the
+/// inhibitor kills the child and therefore releases the lock.
+#[derive(Debug, Default)]
+pub struct SleepInhibitor {
+    child: Option<Child>,
+    reason: Option<String>,
+    /// Only track the reason, never spawn systemd-inhibit (`--simulate`)
+    simulated: bool,
+}
+
+impl SleepInhibitor {
+    /// Create an inhibitor that does not hold a lock yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an inhibitor that only pretends to hold the lock
+    pub fn simulated() -> Self {
+        Self {
+            simulated: true,
+            ..Self::default()
+        }
+    }
+
+    /// Check whether the lock is currently held
+    pub fn is_held(&mut self) -> bool {
+        if self.simulated {
+            return self.reason.is_some();
+        }
+
+        match self.child.as_mut().map(|child| child.try_wait()) {
+            Some(Ok(None)) => true,
+            Some(Ok(Some(status))) => {
+                warn!("systemd-inhibit exited unexpectedly ({}), sleep lock lost", status);
+                self.child = None;
+                self.reason = None;
+                false
+            }
+            Some(Err(e)) => {
+                warn!("Failed to check systemd-inhibit process: {}", e);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the reason the current lock was taken with
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Take the lock, or re-take it if the reason changed.
+    ///
+    /// The new lock is acquired before the old one is released so sleep is
+    /// never allowed in between.
+    pub fn acquire(&mut self, reason: &str) -> Result<(), String> {
+        if self.is_held() && self.reason.as_deref() == Some(reason) {
+            return Ok(());
+        }
+
+        if self.simulated {
+            info!("Simulated sleep inhibitor lock: {}", reason);
+            self.reason = Some(reason.to_string());
+            return Ok(());
+        }
+
+        debug!("Taking sleep inhibitor lock: {}", reason);
+        let mut child = Command::new("systemd-inhibit")
+            .args([
+                "--what=sleep:idle",
+                &format!("--who={}", INHIBITOR_WHO),
+                &format!("--why={}", reason),
+                "--mode=block",
+                "sleep",
+                "infinity",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to execute systemd-inhibit: {}", e))?;
+
+        // E.g. logind is not running or refuses the lock; any old lock stays held
+        match child.try_wait() {
+            Ok(Some(status)) => return Err(format!("systemd-inhibit exited right away ({})", status)),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to check systemd-inhibit process: {}", e),
+        }
+
+        if let Some(mut old) = self.child.replace(child) {
+            let _ = old.start_kill();
+        } else {
+            info!("Sleep inhibitor lock taken: {}", reason);
+        }
+        self.reason = Some(reason.to_string());
+        Ok(())
+    }
+
+    /// Release the lock if it is held
+    pub fn release(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            if let Err(e) = child.start_kill() {
+                warn!("Failed to stop systemd-inhibit: {}", e);
+            }
+            info!("Sleep inhibitor lock released");
+        }
+        self.reason = None;
+    }
+}
@@ -0,0 +1,151 @@
+//! Other applications' logind inhibitors and how they affect suspension
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::state::MAX_TIMER_SECONDS;
+use super::inhibitor::INHIBITOR_WHO;
+
+fn default_recheck_seconds() -> u64 {
+    60
+}
+
+/// An inhibitor lock as listed by logind
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inhibitor {
+    /// Colon-separated list of what is inhibited (e.g. "sleep:shutdown")
+    pub what: String,
+    pub who: String,
+    pub why: String,
+    /// "block" or "delay"
+    pub mode: String,
+    pub uid: u32,
+    pub pid: u32,
+}
+
+impl Inhibitor {
+    /// Short description for logs and error messages
+    pub fn describe(&self) -> String {
+        format!("{} ({}: {})", self.who, self.what, self.why)
+    }
+}
+
+/// What to do when a respected inhibitor is present at suspension time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InhibitorAction {
+    /// Check again after `recheck_seconds` and suspend once the inhibitor is gone
+    #[default]
+    Defer,
+    /// Drop this suspension and start a full countdown
+    Skip,
+}
+
+/// An `[[inhibitors.respect]]` entry; every field that is set has to match
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InhibitorRule {
+    /// Exact owner name (e.g. "restic", "packagekit")
+    pub who: Option<String>,
+    /// One of the inhibited operations (e.g. "sleep", "shutdown")
+    pub what: Option<String>,
+}
+
+impl InhibitorRule {
+    fn matches(&self, inhibitor: &Inhibitor) -> bool {
+        let who_matches = self.who.as_ref().is_none_or(|who| *who == inhibitor.who);
+        let what_matches = self.what.as_ref()
+            .is_none_or(|what| inhibitor.what.split(':').any(|item| item == what));
+        who_matches && what_matches
+    }
+}
+
+/// Inhibitor handling from the `[inhibitors]` config table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InhibitorPolicyConfig {
+    pub action: InhibitorAction,
+    /// Delay before checking a deferred suspension again
+    pub recheck_seconds: u64,
+    /// Inhibitors to respect; none by default
+    pub respect: Vec<InhibitorRule>,
+}
+
+impl Default for InhibitorPolicyConfig {
+    fn default() -> Self {
+        Self {
+            action: InhibitorAction::Defer,
+            recheck_seconds: default_recheck_seconds(),
+            respect: Vec::new(),
+        }
+    }
+}
+
+impl InhibitorPolicyConfig {
+    /// Check that a deferred suspension is checked again within the longest countdown
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_TIMER_SECONDS).contains(&self.recheck_seconds) {
+            return Err(format!("recheck_seconds must be 1-{}", MAX_TIMER_SECONDS));
+        }
+        Ok(())
+    }
+
+    /// Check whether any inhibitors are respected at all
+    pub fn is_enabled(&self) -> bool {
+        !self.respect.is_empty()
+    }
+
+    /// Block inhibitors of other applications matching a rule
+    pub fn blocking(&self, inhibitors: &[Inhibitor]) -> Vec<Inhibitor> {
+        inhibitors
+            .iter()
+            .filter(|inhibitor| inhibitor.mode == "block" && inhibitor.who != INHIBITOR_WHO)
+            .filter(|inhibitor| self.respect.iter().any(|rule| rule.matches(inhibitor)))
+            .cloned()
+            .collect()
+    }
+}
+
+/// An `(what, who, why, mode, uid, pid)` entry of `ListInhibitors`
+type InhibitorEntry = (String, String, String, String, u32, u32);
+
+/// Reply of `busctl --json=short call ... ListInhibitors`
+#[derive(Debug, Deserialize)]
+struct ListInhibitorsReply {
+    data: Vec<Vec<InhibitorEntry>>,
+}
+
+/// List the inhibitors currently held, through logind's `ListInhibitors`
+pub async fn list_logind_inhibitors() -> Result<Vec<Inhibitor>, String> {
+    let output = Command::new("busctl")
+        .args([
+            "--json=short",
+            "call",
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            "ListInhibitors",
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute busctl: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to list inhibitors: {}", stderr.trim()));
+    }
+
+    parse_list_inhibitors(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the JSON reply of `ListInhibitors`
+fn parse_list_inhibitors(json: &str) -> Result<Vec<Inhibitor>, String> {
+    let reply: ListInhibitorsReply = serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse inhibitor list: {}", e))?;
+
+    Ok(reply.data
+        .into_iter()
+        .flatten()
+        .map(|(what, who, why, mode, uid, pid)| Inhibitor { what, who, why, mode, uid, pid })
+        .collect())
+}
@@ -0,0 +1,67 @@
+//! Service log streaming via journalctl
+
+use std::{io, process::Stdio};
+use futures::stream::{self, Stream};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader, Lines},
+    process::{Child, ChildStdout, Command},
+};
+use tracing::debug;
+
+/// Default number of lines returned when the client does not ask for a specific amount
+pub const DEFAULT_LOG_LINES: usize = 200;
+
+/// Upper bound on the number of history lines a single request may ask for
+pub const MAX_LOG_LINES: usize = 5000;
+
+/// Stream the journal of a systemd unit line by line.
+///
+/// The journalctl child is owned by the returned stream and is killed as soon as
+/// the stream is dropped, so a client disconnecting from a `follow` request does
+/// not leave a stray `journalctl -f` behind.
+pub fn stream_journal_lines(
+    service_name: &str,
+    lines: usize,
+    follow: bool,
+) -> Result<impl Stream<Item = Result<String, io::Error>>, String> {
+    let lines = lines.min(MAX_LOG_LINES).to_string();
+    debug!("Streaming {} journal (lines={}, follow={})", service_name, lines, follow);
+
+    let mut command = Command::new("journalctl");
+    command
+        .args(["-u", service_name, "-n", &lines, "--no-pager", "-o", "short-iso"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    if follow {
+        command.arg("-f");
+    }
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to execute journalctl: {}", e))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| "Failed to capture journalctl output".to_string())?;
+
+    Ok(child_line_stream(child, BufReader::new(stdout).lines()))
+}
+
+/// Turn the stdout of a child process into a stream of lines, keeping the child alive
+/// for as long as the stream exists
+fn child_line_stream(
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+) -> impl Stream<Item = Result<String, io::Error>> {
+    stream::unfold(Some((child, lines)), |slot| async move {
+        let (child, mut lines) = slot?;
+        match lines.next_line().await {
+            Ok(Some(line)) => Some((Ok(line), Some((child, lines)))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
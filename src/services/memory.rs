@@ -0,0 +1,163 @@
+//! Memory-aware admission control for heavy services
+
+use std::time::Duration;
+use clap::ValueEnum;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::state::AppState;
+use super::services::ServiceConfig;
+
+/// Bytes in one GiB, used for service memory requirements
+pub const GIB: u64 = 1024 * 1024 * 1024;
+
+/// Time given to the kernel to reclaim memory after a service was stopped
+const MEMORY_SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+/// What to do when a service's memory requirement is not met
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AdmissionPolicy {
+    /// Never check memory before starting a service
+    Off,
+    /// Refuse to start the service
+    Reject,
+    /// Stop active lower-priority services until enough memory is available
+    Preempt,
+}
+
+/// Outcome of a successful admission check, explained for the API response
+#[derive(Debug, Clone, Default)]
+pub struct Admission {
+    pub note: Option<String>,
+}
+
+impl Admission {
+    /// Append the admission note (if any) to an API response message
+    pub fn describe(&self, message: String) -> String {
+        match &self.note {
+            Some(note) => format!("{} ({})", message, note),
+            None => message,
+        }
+    }
+}
+
+/// Read `MemAvailable` from /proc/meminfo in bytes
+pub async fn read_available_memory() -> Result<u64, String> {
+    let meminfo = tokio::fs::read_to_string("/proc/meminfo")
+        .await
+        .map_err(|e| format!("Failed to read /proc/meminfo: {}", e))?;
+    parse_mem_available(&meminfo)
+}
+
+/// Extract `MemAvailable` (reported in kB) from the contents of /proc/meminfo
+pub fn parse_mem_available(meminfo: &str) -> Result<u64, String> {
+    let kb = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .ok_or_else(|| "MemAvailable not found in /proc/meminfo".to_string())?;
+    kb.parse::<u64>()
+        .ok()
+        .and_then(|kb| kb.checked_mul(1024))
+        .ok_or_else(|| format!("Invalid MemAvailable in /proc/meminfo: {} kB", kb))
+}
+
+/// Format a byte count as GiB for log and API messages
+pub fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / GIB as f64)
+}
+
+/// Check whether `config` may be started given the memory currently available.
+///
+/// With [`AdmissionPolicy::Preempt`], active services with a lower priority are
+/// stopped (lowest priority first) and their state set to `false` until the
+/// requirement is met. Returns `Err` with an explanation when the service must
+/// not be started.
+pub async fn admit_service(
+    state: &AppState,
+    service_name: &str,
+    config: &ServiceConfig,
+    policy: AdmissionPolicy,
+) -> Result<Admission, String> {
+    let required = match config.min_available_memory {
+        Some(required) if policy != AdmissionPolicy::Off => required,
+        _ => return Ok(Admission::default()),
+    };
+
+    // Restarting an already running service does not need additional memory
+    if state.get_system_state().map(|s| s.get_service(service_name)).unwrap_or(false) {
+        return Ok(Admission::default());
+    }
+
+    let available = match state.backend.available_memory().await {
+        Ok(available) => available,
+        Err(e) => {
+            // Don't block service control on hosts without /proc/meminfo
+            warn!("Skipping memory admission check for {}: {}", service_name, e);
+            return Ok(Admission::default());
+        }
+    };
+
+    if available >= required {
+        return Ok(Admission {
+            note: Some(format!("{} available, {} required", format_gib(available), format_gib(required))),
+        });
+    }
+
+    if policy == AdmissionPolicy::Reject {
+        return Err(format!(
+            "Not enough memory to start {}: {} available, {} required",
+            service_name, format_gib(available), format_gib(required)
+        ));
+    }
+
+    // Preempt: collect active services with a lower priority, lowest first
+    let system_state = state.get_system_state()?;
+    let mut candidates: Vec<(String, ServiceConfig)> = system_state.services
+        .iter()
+        .filter(|(name, &active)| active && name.as_str() != service_name)
+        .filter_map(|(name, _)| state.service_config(name).map(|c| (name.clone(), c)))
+        .filter(|(_, c)| c.priority < config.priority)
+        .collect();
+    candidates.sort_by_key(|(_, c)| c.priority);
+
+    let mut stopped = Vec::new();
+    let mut current = available;
+
+    for (name, candidate) in candidates {
+        info!("Stopping {} to free memory for {} ({} available, {} required)",
+              name, service_name, format_gib(current), format_gib(required));
+
+        if let Err(e) = state.backend.stop_service(&candidate.service_name).await {
+            warn!("Failed to stop {} for preemption: {}", name, e);
+            continue;
+        }
+        if let Err(e) = state.update_state(&format!("{}-preempted", name), |s| s.set_service(&name, false)) {
+            warn!("Failed to update {} state after preemption: {}", name, e);
+        }
+        stopped.push(name);
+
+        sleep(MEMORY_SETTLE_DELAY).await;
+        current = state.backend.available_memory().await.unwrap_or(current);
+        if current >= required {
+            return Ok(Admission {
+                note: Some(format!(
+                    "stopped {} to free memory: {} -> {} available, {} required",
+                    stopped.join(", "), format_gib(available), format_gib(current), format_gib(required)
+                )),
+            });
+        }
+    }
+
+    if stopped.is_empty() {
+        Err(format!(
+            "Not enough memory to start {}: {} available, {} required, and no lower-priority services to stop",
+            service_name, format_gib(available), format_gib(required)
+        ))
+    } else {
+        Err(format!(
+            "Not enough memory to start {}: stopped {} but only {} available, {} required",
+            service_name, stopped.join(", "), format_gib(current), format_gib(required)
+        ))
+    }
+}
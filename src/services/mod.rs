@@ -5,7 +5,31 @@
 
 pub mod services;
 pub mod system;
+pub mod logs;
+pub mod memory;
+pub mod inhibitor;
+pub mod hooks;
+pub mod backend;
+pub mod simulation;
+pub mod idle_policy;
+pub mod inhibitor_policy;
+pub mod wake_alarm;
+pub mod wake_on_lan;
+pub mod schedule;
+pub mod calendar;
 
 // Re-export main functions
 pub use services::*;
 pub use system::*;
+pub use logs::*;
+pub use memory::*;
+pub use inhibitor::*;
+pub use hooks::*;
+pub use backend::*;
+pub use simulation::*;
+pub use idle_policy::*;
+pub use inhibitor_policy::*;
+pub use wake_alarm::*;
+pub use wake_on_lan::*;
+pub use schedule::*;
+pub use calendar::*;
@@ -0,0 +1,227 @@
+//! Time-of-day schedules: work hours, quiet hours and timed service starts/stops
+
+use std::time::Duration;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::state::{AppState, MAX_TIMER_SECONDS, MIN_TIMER_SECONDS};
+use super::{memory::admit_service, services::ServiceConfig};
+
+/// A local time of day, written "HH:MM" in the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(&value, "%H:%M")
+            .map(TimeOfDay)
+            .map_err(|e| format!("invalid time {:?} (expected HH:MM): {}", value, e))
+    }
+}
+
+/// Check whether a schedule runs on `day`; no days means every day
+fn runs_on(days: &[Weekday], day: Weekday) -> bool {
+    days.is_empty() || days.contains(&day)
+}
+
+/// `time` on the local `date`, in UTC (None inside a DST gap)
+fn local_time(date: NaiveDate, time: TimeOfDay) -> Option<DateTime<Utc>> {
+    Local.from_local_datetime(&date.and_time(time.0))
+        .earliest()
+        .map(|local| local.with_timezone(&Utc))
+}
+
+/// End of the daily window `from`-`to` if `now` falls inside it; windows ending
+/// at or before their start run past midnight and belong to the day they start on
+fn window_end(days: &[Weekday], from: TimeOfDay, to: TimeOfDay, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let today = now.with_timezone(&Local).date_naive();
+    [today.pred_opt()?, today]
+        .into_iter()
+        .filter(|day| runs_on(days, day.weekday()))
+        .filter_map(|day| {
+            let end_day = if to.0 <= from.0 { day.succ_opt()? } else { day };
+            Some((local_time(day, from)?, local_time(end_day, to)?))
+        })
+        .find(|(start, end)| *start <= now && now < *end)
+        .map(|(_, end)| end)
+}
+
+/// A `[[schedule.work_hours]]` window during which the machine stays awake
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkHoursConfig {
+    pub name: String,
+    /// Days the window starts on ("mon", "tue", ...); every day when empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub from: TimeOfDay,
+    pub to: TimeOfDay,
+}
+
+impl WorkHoursConfig {
+    /// Name of the hold kept while the window is open
+    pub fn hold_name(&self) -> String {
+        format!("schedule:{}", self.name)
+    }
+
+    /// End of the current window, if `now` is inside one
+    pub fn active_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        window_end(&self.days, self.from, self.to, now)
+    }
+}
+
+/// A `[[schedule.quiet_hours]]` window with a shorter idle timer
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHoursConfig {
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub from: TimeOfDay,
+    pub to: TimeOfDay,
+    /// Idle timer used during the window, unless the configured one is shorter
+    pub timer_minutes: u64,
+}
+
+impl QuietHoursConfig {
+    /// End of the current window, if `now` is inside one
+    pub fn active_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        window_end(&self.days, self.from, self.to, now)
+    }
+}
+
+/// What a scheduled service entry does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledServiceAction {
+    Start,
+    Stop,
+}
+
+impl ScheduledServiceAction {
+    fn noun(self) -> &'static str {
+        match self {
+            ScheduledServiceAction::Start => "Start",
+            ScheduledServiceAction::Stop => "Stop",
+        }
+    }
+}
+
+/// A `[[schedule.services]]` entry starting or stopping a managed service
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduledServiceConfig {
+    /// Managed service name (e.g. "ollama")
+    pub service: String,
+    pub action: ScheduledServiceAction,
+    pub at: TimeOfDay,
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+}
+
+impl ScheduledServiceConfig {
+    /// Check whether the entry is due at some point in `(after, until]`
+    pub fn due_between(&self, after: DateTime<Utc>, until: DateTime<Utc>) -> bool {
+        let first = after.with_timezone(&Local).date_naive();
+        let last = until.with_timezone(&Local).date_naive();
+        first
+            .iter_days()
+            .take_while(|day| *day <= last)
+            .filter(|day| runs_on(&self.days, day.weekday()))
+            .filter_map(|day| local_time(day, self.at))
+            .any(|at| after < at && at <= until)
+    }
+}
+
+/// Time-of-day schedules from the `[schedule]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleConfig {
+    pub work_hours: Vec<WorkHoursConfig>,
+    pub quiet_hours: Vec<QuietHoursConfig>,
+    pub services: Vec<ScheduledServiceConfig>,
+}
+
+impl ScheduleConfig {
+    /// Check whether anything is scheduled at all
+    pub fn is_enabled(&self) -> bool {
+        !self.work_hours.is_empty() || !self.quiet_hours.is_empty() || !self.services.is_empty()
+    }
+
+    /// Idle timer of the quiet hours in effect at `now` (the shortest if several overlap)
+    pub fn quiet_timer(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.quiet_hours
+            .iter()
+            .filter(|quiet| quiet.active_until(now).is_some())
+            .map(|quiet| Duration::from_secs(quiet.timer_minutes.saturating_mul(60)))
+            .min()
+    }
+
+    /// Check that every scheduled service is a managed one and every quiet hours
+    /// timer is within the bounds of PUT /config/timer
+    pub fn validate(&self) -> Result<(), String> {
+        let timer_range = MIN_TIMER_SECONDS..=MAX_TIMER_SECONDS;
+        if let Some(quiet) = self.quiet_hours.iter().find(|quiet| {
+            !quiet.timer_minutes.checked_mul(60).is_some_and(|seconds| timer_range.contains(&seconds))
+        }) {
+            return Err(format!(
+                "Quiet hours timer of {} minutes must be between {} and {} seconds",
+                quiet.timer_minutes, MIN_TIMER_SECONDS, MAX_TIMER_SECONDS
+            ));
+        }
+        match self.services.iter().find(|entry| ServiceConfig::from_name(&entry.service).is_none()) {
+            Some(entry) => Err(format!("Unknown service in schedule: {}", entry.service)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Start or stop a managed service for a schedule entry, updating its state like the API does
+pub async fn run_scheduled_service_action(state: &AppState, entry: &ScheduledServiceConfig) {
+    run_service_action(state, &entry.service, entry.action, "the schedule").await;
+}
+
+/// Start or stop a managed service on behalf of `reason` (e.g. "the schedule"),
+/// going through memory admission and updating its state like the API does
+pub async fn run_service_action(state: &AppState, service: &str, action: ScheduledServiceAction, reason: &str) {
+    let Some(config) = state.service_config(service) else {
+        warn!("Unknown service {} for {}", service, reason);
+        return;
+    };
+
+    let result = match action {
+        ScheduledServiceAction::Start => {
+            match admit_service(state, service, &config, state.admission_policy).await {
+                Ok(_) => state.backend.start_service(&config.service_name).await,
+                Err(reason) => Err(reason),
+            }
+        }
+        ScheduledServiceAction::Stop => state.backend.stop_service(&config.service_name).await,
+    };
+
+    let active = action == ScheduledServiceAction::Start;
+    match result {
+        Ok(()) => {
+            info!("{} of {} for {} done", action.noun(), service, reason);
+            if let Err(e) = state.set_service(service, active) {
+                error!("Failed to update {} state: {}", service, e);
+            }
+        }
+        Err(e) => {
+            let error_msg = format!("{} of {} for {} failed: {}", action.noun(), service, reason, e);
+            warn!("{}", error_msg);
+            if let Err(e) = state.add_error(error_msg) {
+                error!("Failed to add error to state: {}", e);
+            }
+            // A stop that failed still shouldn't keep the machine awake
+            if !active {
+                if let Err(e) = state.set_service(service, false) {
+                    error!("Failed to update {} state: {}", service, e);
+                }
+            }
+        }
+    }
+}
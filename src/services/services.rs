@@ -1,15 +1,20 @@
 //! Generic systemd service management functions
 
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
+use serde::Deserialize;
 use tokio::{process::Command, time::sleep};
 use tracing::{debug, info, warn};
 
+use super::backend::Backend;
+
 /// Service configuration for different services
 #[derive(Debug, Clone)]
 pub struct ServiceConfig {
     pub service_name: String,
     pub process_name: Option<String>, // For force kill operations
     pub recovery_enabled: bool,
+    pub min_available_memory: Option<u64>, // Bytes of MemAvailable required before starting
+    pub priority: u8, // Higher priority services may preempt lower ones
 }
 
 impl ServiceConfig {
@@ -18,6 +23,8 @@ impl ServiceConfig {
             service_name: "ollama.service".to_string(),
             process_name: Some("ollama".to_string()),
             recovery_enabled: true,
+            min_available_memory: None,
+            priority: 10,
         }
     }
     
@@ -26,6 +33,8 @@ impl ServiceConfig {
             service_name: "comfy-unsafe.service".to_string(),
             process_name: Some("comfy-unsafe".to_string()),
             recovery_enabled: true,
+            min_available_memory: None,
+            priority: 20,
         }
     }
     
@@ -34,6 +43,8 @@ impl ServiceConfig {
             service_name: "comfy-safe.service".to_string(),
             process_name: Some("comfy-safe".to_string()),
             recovery_enabled: true,
+            min_available_memory: None,
+            priority: 20,
         }
     }
     
@@ -46,6 +57,40 @@ impl ServiceConfig {
             _ => None,
         }
     }
+
+    /// Apply the `[services.<name>]` settings from the config file
+    pub fn with_settings(mut self, settings: &ServiceSettings) -> Self {
+        if let Some(mb) = settings.min_available_memory_mb {
+            self.min_available_memory = (mb > 0).then_some(mb * 1024 * 1024);
+        }
+        if let Some(priority) = settings.priority {
+            self.priority = priority;
+        }
+        self
+    }
+}
+
+/// Settings of a managed service from a `[services.<name>]` config table;
+/// unset values keep the built-in defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceSettings {
+    /// MiB of MemAvailable required before starting (0 disables the check)
+    pub min_available_memory_mb: Option<u64>,
+    /// Higher priority services may preempt lower ones
+    pub priority: Option<u8>,
+}
+
+/// Check that every `[services.<name>]` table names a managed service
+pub fn validate_service_settings(settings: &HashMap<String, ServiceSettings>) -> Result<(), String> {
+    if let Some(name) = settings.keys().find(|name| ServiceConfig::from_name(name).is_none()) {
+        return Err(format!("Unknown service in services config: {}", name));
+    }
+    // Keeps the conversion to bytes from overflowing
+    match settings.iter().find(|(_, s)| s.min_available_memory_mb.is_some_and(|mb| mb > u64::MAX / (1024 * 1024))) {
+        Some((name, _)) => Err(format!("min_available_memory_mb of {} is too large", name)),
+        None => Ok(()),
+    }
 }
 
 /// Start a systemd service using systemctl
@@ -157,10 +202,10 @@ pub async fn check_systemd_service_status(service_name: &str) -> Result<bool, St
 }
 
 /// Initialize service state on server startup
-pub async fn initialize_service_state(config: &ServiceConfig, desired_state: bool) -> Result<(), String> {
+pub async fn initialize_service_state(backend: &Backend, config: &ServiceConfig, desired_state: bool) -> Result<(), String> {
     info!("Initializing {} service state", config.service_name);
     
-    match check_systemd_service_status(&config.service_name).await {
+    match backend.is_service_active(&config.service_name).await {
         Ok(is_active) => {
             if is_active != desired_state {
                 info!("{} is {}, {} to synchronize with server state", 
@@ -170,9 +215,9 @@ pub async fn initialize_service_state(config: &ServiceConfig, desired_state: boo
                 );
                 
                 if desired_state {
-                    start_systemd_service(&config.service_name).await?;
+                    backend.start_service(&config.service_name).await?;
                 } else {
-                    stop_systemd_service(&config.service_name).await?;
+                    backend.stop_service(&config.service_name).await?;
                 }
                 
                 info!("{} {} successfully during initialization", 
@@ -196,13 +241,13 @@ pub async fn initialize_service_state(config: &ServiceConfig, desired_state: boo
 }
 
 /// Comprehensive service recovery with escalating attempts
-pub async fn recover_systemd_service(config: &ServiceConfig) -> Result<(), String> {
+pub async fn recover_systemd_service(backend: &Backend, config: &ServiceConfig) -> Result<(), String> {
     warn!("Starting {} service recovery process", config.service_name);
 
     // Step 1: Try force kill and restart
     warn!("Recovery step 1: Force kill and start");
     if let Some(process_name) = &config.process_name {
-        if let Err(e) = force_kill_process(process_name).await {
+        if let Err(e) = backend.force_kill(process_name).await {
             warn!("Force kill failed: {}", e);
         }
     }
@@ -210,18 +255,18 @@ pub async fn recover_systemd_service(config: &ServiceConfig) -> Result<(), Strin
     // Wait a moment for processes to clean up
     sleep(Duration::from_secs(2)).await;
     
-    if start_systemd_service(&config.service_name).await.is_ok() {
+    if backend.start_service(&config.service_name).await.is_ok() {
         info!("Recovery successful after force kill");
         return Ok(());
     }
 
     // Step 2: Reload systemd and restart service
     warn!("Recovery step 2: Reload systemd and restart service");
-    if let Err(e) = reload_systemd_daemon().await {
+    if let Err(e) = backend.reload_daemon().await {
         warn!("Systemd reload failed: {}", e);
     }
     
-    if restart_systemd_service(&config.service_name).await.is_ok() {
+    if backend.restart_service(&config.service_name).await.is_ok() {
         info!("Recovery successful after systemd reload and restart");
         return Ok(());
     }
@@ -0,0 +1,473 @@
+//! Simulated host for `--simulate` mode
+//!
+//! Replaces systemctl, pkill and the idle action with an in-memory model so the
+//! HTTP API and the suspension timer can be exercised on any machine without
+//! touching real services or actually suspending.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use chrono::Utc;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Deserialize;
+use tokio::{sync::broadcast, time::{sleep, Instant}};
+use tracing::info;
+
+use super::{
+    idle_policy::GovernorSetting,
+    inhibitor_policy::Inhibitor,
+    logs::MAX_LOG_LINES,
+    system::{IdleAction, SleepCapabilities},
+    wake_on_lan::{WakeSource, WakeSourceKind},
+};
+
+/// Lines kept per simulated unit for the log endpoint, as many as a request may ask for
+const LOG_CAPACITY: usize = MAX_LOG_LINES;
+
+/// Most memory of the simulated machine or one of its services (1 PiB), so the
+/// sizes can be added up in bytes
+pub const MAX_SIMULATED_MEMORY_MB: u64 = 1 << 30;
+
+fn default_start_delay_ms() -> u64 {
+    500
+}
+
+fn default_sleep_seconds() -> u64 {
+    30
+}
+
+fn default_power_states() -> Vec<String> {
+    ["freeze", "mem", "disk"].map(String::from).to_vec()
+}
+
+fn default_disk_modes() -> Vec<String> {
+    ["platform", "shutdown", "suspend"].map(String::from).to_vec()
+}
+
+fn default_memory_total_mb() -> u64 {
+    32 * 1024
+}
+
+/// Memory a running service uses when `memory_mb` is not overridden
+fn default_service_memory_mb(name: &str) -> u64 {
+    match name {
+        "ollama" => 4 * 1024,
+        "comfy-unsafe" | "comfy-safe" => 8 * 1024,
+        _ => 0,
+    }
+}
+
+/// Per-service overrides in `[simulation.services.<name>]`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulatedServiceConfig {
+    pub start_delay_ms: Option<u64>,
+    pub failure_rate: Option<f64>,
+    /// Time the service takes to stop (stopping is instant by default)
+    pub stop_delay_ms: Option<u64>,
+    /// Memory the service uses while running
+    pub memory_mb: Option<u64>,
+}
+
+/// Settings for the simulated host from the `[simulation]` config table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    /// Time a service takes to start
+    pub start_delay_ms: u64,
+    /// Probability (0.0-1.0) that starting a service fails
+    pub failure_rate: f64,
+    /// How long a simulated suspend "sleeps" before resuming
+    pub sleep_seconds: u64,
+    /// Probability (0.0-1.0) that an idle action reports success but the machine stays awake
+    pub suspend_failure_rate: f64,
+    /// Total memory of the simulated machine, shared by the running services
+    pub memory_total_mb: u64,
+    /// Simulated entries of /sys/power/state (e.g. leave out "disk" to rule out hibernation)
+    pub power_states: Vec<String>,
+    /// Simulated entries of /sys/power/disk
+    pub disk_modes: Vec<String>,
+    /// Per-service overrides, keyed by service name (e.g. "comfy-unsafe")
+    pub services: HashMap<String, SimulatedServiceConfig>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            start_delay_ms: default_start_delay_ms(),
+            failure_rate: 0.0,
+            sleep_seconds: default_sleep_seconds(),
+            suspend_failure_rate: 0.0,
+            memory_total_mb: default_memory_total_mb(),
+            power_states: default_power_states(),
+            disk_modes: default_disk_modes(),
+            services: HashMap::new(),
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// Check that the memory sizes are at most `MAX_SIMULATED_MEMORY_MB`
+    pub fn validate(&self) -> Result<(), String> {
+        if self.memory_total_mb > MAX_SIMULATED_MEMORY_MB {
+            return Err(format!("simulation memory_total_mb must be at most {}", MAX_SIMULATED_MEMORY_MB));
+        }
+        match self.services.iter().find(|(_, s)| s.memory_mb.is_some_and(|mb| mb > MAX_SIMULATED_MEMORY_MB)) {
+            Some((name, _)) => Err(format!(
+                "memory_mb of simulated service {} must be at most {}",
+                name, MAX_SIMULATED_MEMORY_MB
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// In-memory stand-in for systemd and the kernel's sleep states
+#[derive(Debug)]
+pub struct SimulatedHost {
+    config: SimulationConfig,
+    units: Mutex<HashMap<String, bool>>,
+    logs: Mutex<HashMap<String, VecDeque<String>>>,
+    log_tx: broadcast::Sender<(String, String)>,
+    resume_tx: broadcast::Sender<Duration>,
+    governor: Mutex<String>,
+    suspend_successes: Mutex<u64>,
+    inhibitors: Mutex<Vec<Inhibitor>>,
+    wake_alarm: Mutex<Option<Instant>>,
+    wake_sources: Mutex<Vec<WakeSource>>,
+    magic_packets: Mutex<Vec<(String, String)>>,
+    rng: Mutex<u64>,
+}
+
+/// Strip the `.service` suffix to get the name used in configuration
+fn short_name(unit: &str) -> &str {
+    unit.strip_suffix(".service").unwrap_or(unit)
+}
+
+impl SimulatedHost {
+    /// Create a simulated host with all services stopped
+    pub fn new(config: SimulationConfig) -> Self {
+        let (log_tx, _) = broadcast::channel(256);
+        let (resume_tx, _) = broadcast::channel(16);
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0x9E37_79B9_7F4A_7C15);
+
+        Self {
+            config,
+            units: Mutex::new(HashMap::new()),
+            logs: Mutex::new(HashMap::new()),
+            log_tx,
+            resume_tx,
+            governor: Mutex::new("schedutil".to_string()),
+            suspend_successes: Mutex::new(0),
+            inhibitors: Mutex::new(Vec::new()),
+            wake_alarm: Mutex::new(None),
+            wake_sources: Mutex::new(vec![
+                WakeSource::new(WakeSourceKind::Acpi, "XHC", true),
+                WakeSource::new(WakeSourceKind::Net, "eth0", false),
+            ]),
+            magic_packets: Mutex::new(Vec::new()),
+            rng: Mutex::new(seed | 1),
+        }
+    }
+
+    /// Subscribe to simulated wake-ups (the payload is the simulated sleep duration)
+    pub fn subscribe_resume(&self) -> broadcast::Receiver<Duration> {
+        self.resume_tx.subscribe()
+    }
+
+    /// Uniform random number in [0, 1) (xorshift64*, good enough for failure injection)
+    fn random(&self) -> f64 {
+        let mut state = match self.rng.lock() {
+            Ok(state) => state,
+            Err(_) => return 1.0,
+        };
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        let value = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Append a line to a unit's log and notify followers
+    fn log(&self, unit: &str, message: &str) {
+        let line = format!("{} simulated {}: {}", Utc::now().format("%Y-%m-%dT%H:%M:%S%z"), unit, message);
+        if let Ok(mut logs) = self.logs.lock() {
+            let buffer = logs.entry(unit.to_string()).or_default();
+            if buffer.len() >= LOG_CAPACITY {
+                buffer.pop_front();
+            }
+            buffer.push_back(line.clone());
+            // Sent under the lock so a follower sees each line exactly once
+            let _ = self.log_tx.send((unit.to_string(), line));
+        }
+    }
+
+    fn set_active(&self, unit: &str, active: bool) {
+        if let Ok(mut units) = self.units.lock() {
+            units.insert(unit.to_string(), active);
+        }
+    }
+
+    /// Simulated `systemctl start`, honouring the configured delay and failure rate
+    pub async fn start_service(&self, unit: &str) -> Result<(), String> {
+        let overrides = self.config.services.get(short_name(unit)).cloned().unwrap_or_default();
+        let delay = overrides.start_delay_ms.unwrap_or(self.config.start_delay_ms);
+        let failure_rate = overrides.failure_rate.unwrap_or(self.config.failure_rate);
+
+        self.log(unit, "Starting...");
+        sleep(Duration::from_millis(delay)).await;
+
+        if self.random() < failure_rate {
+            self.log(unit, "Failed to start (simulated failure)");
+            return Err(format!("simulated start failure for {}", unit));
+        }
+
+        self.set_active(unit, true);
+        self.log(unit, "Started");
+        Ok(())
+    }
+
+    /// Simulated `systemctl stop`, honouring the configured delay
+    pub async fn stop_service(&self, unit: &str) -> Result<(), String> {
+        let delay = self.config.services.get(short_name(unit)).and_then(|overrides| overrides.stop_delay_ms);
+        if let Some(delay) = delay {
+            self.log(unit, "Stopping...");
+            sleep(Duration::from_millis(delay)).await;
+        }
+        self.set_active(unit, false);
+        self.log(unit, "Stopped");
+        Ok(())
+    }
+
+    /// Simulated `systemctl restart`
+    pub async fn restart_service(&self, unit: &str) -> Result<(), String> {
+        self.stop_service(unit).await?;
+        self.start_service(unit).await
+    }
+
+    /// Simulated `systemctl is-active`
+    pub async fn is_service_active(&self, unit: &str) -> Result<bool, String> {
+        Ok(self.units.lock()
+            .map(|units| units.get(unit).copied().unwrap_or(false))
+            .unwrap_or(false))
+    }
+
+    /// Simulated `pkill -f`, stopping every unit whose name matches the process name
+    pub async fn force_kill(&self, process_name: &str) -> Result<(), String> {
+        let killed: Vec<String> = match self.units.lock() {
+            Ok(mut units) => units
+                .iter_mut()
+                .filter(|(unit, active)| **active && short_name(unit) == process_name)
+                .map(|(unit, active)| {
+                    *active = false;
+                    unit.clone()
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        for unit in killed {
+            self.log(&unit, "Killed");
+        }
+        Ok(())
+    }
+
+    /// Simulated idle action: "sleeps" for the configured time (or until the
+    /// wake alarm), then resumes
+    pub async fn run_idle_action(&self, action: IdleAction) -> Result<(), String> {
+        if self.random() < self.config.suspend_failure_rate {
+            info!("Simulating a {} that silently keeps the machine awake", action);
+            return Ok(());
+        }
+
+        let mut slept = Duration::from_secs(self.config.sleep_seconds);
+        if let Some(alarm) = self.wake_alarm.lock().ok().and_then(|mut alarm| alarm.take()) {
+            slept = slept.min(alarm.saturating_duration_since(Instant::now()));
+        }
+        info!("Simulating {} for {}s", action, slept.as_secs());
+        if let Ok(mut successes) = self.suspend_successes.lock() {
+            *successes += 1;
+        }
+
+        let resume_tx = self.resume_tx.clone();
+        tokio::spawn(async move {
+            sleep(slept).await;
+            let _ = resume_tx.send(slept);
+        });
+        Ok(())
+    }
+
+    /// Simulated `/sys/power/suspend_stats/success`
+    pub fn suspend_successes(&self) -> u64 {
+        self.suspend_successes.lock().map(|successes| *successes).unwrap_or(0)
+    }
+
+    /// Inhibitor locks held by simulated applications
+    pub fn inhibitors(&self) -> Vec<Inhibitor> {
+        self.inhibitors.lock().map(|inhibitors| inhibitors.clone()).unwrap_or_default()
+    }
+
+    /// Let a simulated application take an inhibitor lock
+    pub fn add_inhibitor(&self, inhibitor: Inhibitor) {
+        info!("Simulated inhibitor taken: {}", inhibitor.describe());
+        if let Ok(mut inhibitors) = self.inhibitors.lock() {
+            inhibitors.push(inhibitor);
+        }
+    }
+
+    /// Release the simulated inhibitor locks of `who`
+    pub fn remove_inhibitors(&self, who: &str) {
+        info!("Simulated inhibitors of {} released", who);
+        if let Ok(mut inhibitors) = self.inhibitors.lock() {
+            inhibitors.retain(|inhibitor| inhibitor.who != who);
+        }
+    }
+
+    /// Simulated RTC wake alarm `from_now`
+    pub fn set_wake_alarm(&self, from_now: Duration) {
+        info!("Simulated wake alarm in {}s", from_now.as_secs());
+        if let Ok(mut alarm) = self.wake_alarm.lock() {
+            *alarm = Some(Instant::now() + from_now);
+        }
+    }
+
+    /// Clear the simulated RTC wake alarm
+    pub fn clear_wake_alarm(&self) {
+        if let Ok(mut alarm) = self.wake_alarm.lock() {
+            *alarm = None;
+        }
+    }
+
+    /// Simulated magic packet; recorded instead of sent
+    pub fn send_magic_packet(&self, mac: [u8; 6], target: &str) {
+        let mac = mac.iter().map(|octet| format!("{:02x}", octet)).collect::<Vec<_>>().join(":");
+        info!("Simulated magic packet for {} to {}", mac, target);
+        if let Ok(mut packets) = self.magic_packets.lock() {
+            packets.push((mac, target.to_string()));
+        }
+    }
+
+    /// Magic packets sent so far as (MAC, target) pairs
+    pub fn magic_packets(&self) -> Vec<(String, String)> {
+        self.magic_packets.lock().map(|packets| packets.clone()).unwrap_or_default()
+    }
+
+    /// Simulated wake devices: a USB controller and an Ethernet interface
+    pub fn wake_sources(&self) -> Vec<WakeSource> {
+        self.wake_sources.lock().map(|sources| sources.clone()).unwrap_or_default()
+    }
+
+    /// Enable or disable a simulated wake device
+    pub fn set_wake_source(&self, id: &str, enabled: bool) {
+        info!("Simulated wake source {} {}", id, if enabled { "enabled" } else { "disabled" });
+        if let Ok(mut sources) = self.wake_sources.lock() {
+            for source in sources.iter_mut().filter(|source| source.id == id) {
+                source.enabled = enabled;
+            }
+        }
+    }
+
+    /// Current governor of the simulated single CPU
+    pub fn cpu_governor(&self) -> String {
+        self.governor.lock().map(|governor| governor.clone()).unwrap_or_default()
+    }
+
+    /// Simulated governor switch of the single simulated CPU
+    pub fn set_cpu_governor(&self, governor: &str) -> Vec<GovernorSetting> {
+        let Ok(mut current) = self.governor.lock() else {
+            return Vec::new();
+        };
+        if *current == governor {
+            return Vec::new();
+        }
+        info!("Simulated CPU governor: {} -> {}", current, governor);
+        let previous = std::mem::replace(&mut *current, governor.to_string());
+        vec![("cpu0".to_string(), previous)]
+    }
+
+    /// Put back simulated governors, in order like on a real machine
+    pub fn restore_cpu_governors(&self, settings: &[GovernorSetting]) {
+        let Ok(mut current) = self.governor.lock() else {
+            return;
+        };
+        for (_, governor) in settings {
+            info!("Simulated CPU governor restored to {}", governor);
+            *current = governor.clone();
+        }
+    }
+
+    /// The simulated machine supports every sleep state
+    pub fn sleep_capabilities(&self) -> SleepCapabilities {
+        SleepCapabilities {
+            states: self.config.power_states.clone(),
+            disk_modes: self.config.disk_modes.clone(),
+        }
+    }
+
+    /// Memory a running service uses, in MiB
+    fn service_memory_mb(&self, name: &str) -> u64 {
+        self.config.services
+            .get(name)
+            .and_then(|overrides| overrides.memory_mb)
+            .unwrap_or_else(|| default_service_memory_mb(name))
+    }
+
+    /// Total memory minus the memory of every running service
+    pub async fn available_memory(&self) -> Result<u64, String> {
+        let total = self.config.memory_total_mb.saturating_mul(1024 * 1024);
+        let used = self.units.lock()
+            .map(|units| {
+                units
+                    .iter()
+                    .filter(|(_, &active)| active)
+                    .map(|(unit, _)| self.service_memory_mb(short_name(unit)).saturating_mul(1024 * 1024))
+                    .fold(0u64, u64::saturating_add)
+            })
+            .unwrap_or(0);
+        Ok(total.saturating_sub(used))
+    }
+
+    /// Stream the in-memory log of a unit
+    pub fn stream_logs(
+        &self,
+        unit: &str,
+        lines: usize,
+        follow: bool,
+    ) -> BoxStream<'static, Result<String, io::Error>> {
+        // Subscribe together with the snapshot, so no line written in between is lost
+        let (history, rx): (Vec<String>, _) = match self.logs.lock() {
+            Ok(logs) => (
+                logs.get(unit)
+                    .map(|buffer| buffer.iter().skip(buffer.len().saturating_sub(lines)).cloned().collect())
+                    .unwrap_or_default(),
+                self.log_tx.subscribe(),
+            ),
+            Err(_) => (Vec::new(), self.log_tx.subscribe()),
+        };
+        let history = stream::iter(history.into_iter().map(Ok));
+
+        if !follow {
+            return history.boxed();
+        }
+
+        let unit = unit.to_string();
+        let live = stream::unfold(rx, move |mut rx| {
+            let unit = unit.clone();
+            async move {
+                loop {
+                    match rx.recv().await {
+                        Ok((line_unit, line)) if line_unit == unit => return Some((Ok(line), rx)),
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        history.chain(live).boxed()
+    }
+}
@@ -1,32 +1,171 @@
 //! System operations like suspension
 
-use std::sync::Arc;
+use std::{fmt, path::Path};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use tokio::process::Command;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::state::AppState;
+use super::hooks::HookStage;
 
-/// Execute system suspension
-pub async fn execute_system_suspend(state: Arc<AppState>) -> Result<(), String> {
-    info!("Executing system suspension");
-    
-    // Set suspended flag before suspending
-    if let Err(e) = state.set_suspended(true) {
-        tracing::warn!("Failed to set suspended state: {}", e);
-    }
-    
-    let output = Command::new("systemctl")
-        .args(&["suspend"])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute systemctl suspend: {}", e))?;
+/// Default location of sysfs
+pub const DEFAULT_SYSFS_ROOT: &str = "/sys";
+
+/// What to do when the suspension timer expires (or a client asks to sleep now)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdleAction {
+    /// `systemctl suspend`
+    Suspend,
+    /// `systemctl hibernate`
+    Hibernate,
+    /// `systemctl hybrid-sleep`
+    HybridSleep,
+    /// `systemctl suspend-then-hibernate`
+    SuspendThenHibernate,
+    /// `systemctl poweroff`
+    Poweroff,
+    /// Run the configured `--idle-command`
+    Custom,
+}
+
+impl IdleAction {
+    /// The systemctl verb for this action (None for custom commands)
+    pub fn systemctl_verb(&self) -> Option<&'static str> {
+        match self {
+            IdleAction::Suspend => Some("suspend"),
+            IdleAction::Hibernate => Some("hibernate"),
+            IdleAction::HybridSleep => Some("hybrid-sleep"),
+            IdleAction::SuspendThenHibernate => Some("suspend-then-hibernate"),
+            IdleAction::Poweroff => Some("poweroff"),
+            IdleAction::Custom => None,
+        }
+    }
+
+    /// Whether the machine is expected to sleep and wake up again
+    pub fn sleeps(&self) -> bool {
+        matches!(
+            self,
+            IdleAction::Suspend | IdleAction::Hibernate | IdleAction::HybridSleep | IdleAction::SuspendThenHibernate
+        )
+    }
+}
+
+/// Read the number of successful suspends from `<sysfs_root>/power/suspend_stats/success`
+pub fn read_suspend_successes(sysfs_root: &Path) -> Option<u64> {
+    std::fs::read_to_string(sysfs_root.join("power/suspend_stats/success"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+impl fmt::Display for IdleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.systemctl_verb().unwrap_or("custom"))
+    }
+}
+
+/// Sleep states supported by the kernel, read from /sys/power
+#[derive(Debug, Clone, Default)]
+pub struct SleepCapabilities {
+    /// Entries of /sys/power/state (e.g. "freeze", "mem", "disk")
+    pub states: Vec<String>,
+    /// Entries of /sys/power/disk without brackets (e.g. "platform", "shutdown", "suspend")
+    pub disk_modes: Vec<String>,
+}
+
+impl SleepCapabilities {
+    /// Read capabilities from `<sysfs_root>/power/state` and `<sysfs_root>/power/disk`
+    pub fn read(sysfs_root: &Path) -> Self {
+        let read_words = |name: &str| -> Vec<String> {
+            std::fs::read_to_string(sysfs_root.join("power").join(name))
+                .map(|content| {
+                    content
+                        .split_whitespace()
+                        .map(|word| word.trim_matches(|c| c == '[' || c == ']').to_string())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("systemctl suspend failed: {}", stderr));
+        Self {
+            states: read_words("state"),
+            disk_modes: read_words("disk"),
+        }
     }
 
-    info!("System suspension command executed");
+    fn can_suspend(&self) -> bool {
+        self.states.iter().any(|s| s == "mem" || s == "standby" || s == "freeze")
+    }
+
+    fn can_hibernate(&self) -> bool {
+        self.states.iter().any(|s| s == "disk")
+            && !self.disk_modes.is_empty()
+            && !self.disk_modes.iter().any(|m| m == "disabled")
+    }
+
+    /// Check whether `action` can be performed on this machine
+    pub fn check(&self, action: IdleAction, custom_command: Option<&str>) -> Result<(), String> {
+        let supported = match action {
+            IdleAction::Suspend => self.can_suspend(),
+            IdleAction::Hibernate => self.can_hibernate(),
+            IdleAction::HybridSleep => {
+                self.can_hibernate() && self.disk_modes.iter().any(|m| m == "suspend")
+            }
+            IdleAction::SuspendThenHibernate => self.can_suspend() && self.can_hibernate(),
+            IdleAction::Poweroff => true,
+            IdleAction::Custom => {
+                return match custom_command {
+                    Some(command) if !command.trim().is_empty() => Ok(()),
+                    _ => Err("idle action 'custom' requires --idle-command".to_string()),
+                };
+            }
+        };
+
+        if supported {
+            Ok(())
+        } else {
+            Err(format!(
+                "idle action '{}' is not supported by this machine (power/state: [{}], power/disk: [{}])",
+                action, self.states.join(" "), self.disk_modes.join(" ")
+            ))
+        }
+    }
+}
+
+/// Run the pre-suspend hooks; returns the reason if a hook vetoed the suspension
+pub async fn run_pre_suspend_hooks(state: &AppState) -> Option<String> {
+    let reason = state.run_hooks(HookStage::PreSuspend).await.vetoed?;
+    warn!("Suspension vetoed by pre-suspend hook {}", reason);
+    if let Err(e) = state.add_error(format!("Suspension vetoed by hook {}", reason)) {
+        warn!("Failed to add veto error: {}", e);
+    }
+    Some(reason)
+}
+
+/// Execute a specific idle action; if it fails, the services stopped by the
+/// pre-suspend hooks are started again
+pub async fn execute_idle_action(state: &AppState, action: IdleAction) -> Result<(), String> {
+    info!("Executing idle action: {}", action);
+
+    if action.sleeps() {
+        // A missing wake-up is worth knowing about, but not worth staying awake for
+        if let Err(e) = state.program_wake_alarm() {
+            warn!("{}", e);
+            let _ = state.add_error(e);
+        }
+    }
+    if let Err(e) = state.backend.run_idle_action(action, state.idle_command.as_deref()).await {
+        // We stay awake, so bring back what the pre-suspend hooks stopped
+        for message in state.hooks.restore_stopped_services(&state.backend).await {
+            info!("{}", message);
+        }
+        return Err(e);
+    }
+
+    info!("Idle action {} executed", action);
     Ok(())
 }
 
@@ -37,7 +176,7 @@ pub async fn check_systemctl_available() -> Result<(), String> {
         .output()
         .await
         .map_err(|_| "systemctl is not available. This server requires systemd.".to_string())?;
-    
+
     info!("systemctl is available");
     Ok(())
 }
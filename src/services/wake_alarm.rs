@@ -0,0 +1,111 @@
+//! Scheduled wake-ups through the RTC wake alarm
+
+use std::{fs, path::Path};
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::state::MAX_TIMER_SECONDS;
+use super::{schedule::TimeOfDay, services::ServiceConfig};
+
+/// Longest a one-off wake alarm can be ahead, or a wake-up can hold the machine awake
+pub const MAX_WAKE_MINUTES: u64 = MAX_TIMER_SECONDS / 60;
+
+/// A daily wake-up from the `[[wake.alarms]]` config tables
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WakeAlarmConfig {
+    pub name: String,
+    /// Local time of day, "HH:MM"
+    pub at: TimeOfDay,
+    /// Managed services to start after the wake-up
+    #[serde(default)]
+    pub start_services: Vec<String>,
+    /// Minutes the machine is held awake after the wake-up
+    #[serde(default)]
+    pub hold_minutes: u64,
+}
+
+impl WakeAlarmConfig {
+    /// The first occurrence of this alarm after `now`
+    pub fn next_after(&self, now: DateTime<Utc>) -> Result<WakeAlarm, String> {
+        let today = now.with_timezone(&Local).date_naive();
+        let at = [today, today + Duration::days(1), today + Duration::days(2)]
+            .into_iter()
+            .filter_map(|day| Local.from_local_datetime(&day.and_time(self.at.0)).earliest())
+            .map(|local| local.with_timezone(&Utc))
+            .find(|at| *at > now)
+            .ok_or_else(|| format!("No upcoming time for wake alarm {}", self.name))?;
+
+        Ok(WakeAlarm {
+            name: self.name.clone(),
+            at,
+            start_services: self.start_services.clone(),
+            hold_minutes: self.hold_minutes,
+        })
+    }
+}
+
+/// Wake-up schedule from the `[wake]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WakeScheduleConfig {
+    pub alarms: Vec<WakeAlarmConfig>,
+}
+
+impl WakeScheduleConfig {
+    /// Check that every alarm starts managed services only and holds the machine
+    /// awake for at most `MAX_WAKE_MINUTES`
+    pub fn validate(&self) -> Result<(), String> {
+        for alarm in &self.alarms {
+            if alarm.hold_minutes > MAX_WAKE_MINUTES {
+                return Err(format!(
+                    "Wake alarm {} holds for {} minutes, at most {} are allowed",
+                    alarm.name, alarm.hold_minutes, MAX_WAKE_MINUTES
+                ));
+            }
+            if let Some(service) = alarm.start_services.iter().find(|s| ServiceConfig::from_name(s).is_none()) {
+                return Err(format!("Unknown service in wake alarm {}: {}", alarm.name, service));
+            }
+        }
+        Ok(())
+    }
+
+    /// The next scheduled wake-up after `now`, skipping (and reporting) invalid entries
+    pub fn next_after(&self, now: DateTime<Utc>) -> (Option<WakeAlarm>, Vec<String>) {
+        let mut errors = Vec::new();
+        let next = self.alarms
+            .iter()
+            .filter_map(|alarm| alarm.next_after(now).map_err(|e| errors.push(e)).ok())
+            .min_by_key(|alarm| alarm.at);
+        (next, errors)
+    }
+}
+
+/// A wake-up to program into the RTC, and what to do after it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WakeAlarm {
+    pub name: String,
+    pub at: DateTime<Utc>,
+    pub start_services: Vec<String>,
+    pub hold_minutes: u64,
+}
+
+/// Location of the wake alarm of the first RTC below `sysfs_root`
+fn wakealarm_path(sysfs_root: &Path) -> std::path::PathBuf {
+    sysfs_root.join("class/rtc/rtc0/wakealarm")
+}
+
+/// Program the RTC to wake the machine at `at`
+pub fn write_rtc_wake_alarm(sysfs_root: &Path, at: DateTime<Utc>) -> Result<(), String> {
+    let path = wakealarm_path(sysfs_root);
+    // The kernel refuses a new alarm while another one is set
+    fs::write(&path, "0")
+        .and_then(|_| fs::write(&path, at.timestamp().to_string()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Clear the RTC wake alarm
+pub fn clear_rtc_wake_alarm(sysfs_root: &Path) -> Result<(), String> {
+    let path = wakealarm_path(sysfs_root);
+    fs::write(&path, "0").map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
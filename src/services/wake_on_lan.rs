@@ -0,0 +1,172 @@
+//! Wake-on-LAN for peers, and the devices allowed to wake this machine
+
+use std::{fs, path::Path};
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+
+/// Default procfs mount point
+pub const DEFAULT_PROCFS_ROOT: &str = "/proc";
+
+fn default_broadcast() -> String {
+    "255.255.255.255:9".to_string()
+}
+
+/// A machine from the `[[wol.peers]]` config tables
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WolPeerConfig {
+    /// Name used in `POST /wol/:host`
+    pub name: String,
+    /// MAC address, "aa:bb:cc:dd:ee:ff" or "aa-bb-cc-dd-ee-ff"
+    pub mac: String,
+    /// Address the magic packet is sent to
+    #[serde(default = "default_broadcast")]
+    pub broadcast: String,
+}
+
+/// Wake-on-LAN peers from the `[wol]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WakeOnLanConfig {
+    pub peers: Vec<WolPeerConfig>,
+}
+
+impl WakeOnLanConfig {
+    /// Look up a peer by name
+    pub fn peer(&self, name: &str) -> Option<&WolPeerConfig> {
+        self.peers.iter().find(|peer| peer.name == name)
+    }
+}
+
+/// Parse a MAC address written with `:` or `-` separators
+pub fn parse_mac(mac: &str) -> Result<[u8; 6], String> {
+    let octets = mac
+        .split([':', '-'])
+        .map(|octet| u8::from_str_radix(octet, 16).ok().filter(|_| octet.len() == 2))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| format!("Invalid MAC address: {}", mac))?;
+
+    octets.try_into().map_err(|_| format!("Invalid MAC address: {}", mac))
+}
+
+/// Six 0xff bytes followed by the MAC sixteen times
+pub fn magic_packet(mac: [u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xff; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    packet
+}
+
+/// Broadcast a magic packet for `mac` to `target`
+pub async fn send_magic_packet(mac: [u8; 6], target: &str) -> Result<(), String> {
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket.set_broadcast(true)
+        .map_err(|e| format!("Failed to enable broadcast: {}", e))?;
+    socket.send_to(&magic_packet(mac), target)
+        .await
+        .map_err(|e| format!("Failed to send magic packet to {}: {}", target, e))?;
+    Ok(())
+}
+
+/// Where a wake source is configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WakeSourceKind {
+    /// A device in `/proc/acpi/wakeup`
+    Acpi,
+    /// A network interface's `device/power/wakeup` in sysfs
+    Net,
+}
+
+/// A device that may be allowed to wake the machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WakeSource {
+    /// "acpi:<device>" or "net:<interface>"
+    pub id: String,
+    pub kind: WakeSourceKind,
+    pub device: String,
+    pub enabled: bool,
+}
+
+impl WakeSource {
+    pub fn new(kind: WakeSourceKind, device: &str, enabled: bool) -> Self {
+        let prefix = match kind {
+            WakeSourceKind::Acpi => "acpi",
+            WakeSourceKind::Net => "net",
+        };
+        Self {
+            id: format!("{}:{}", prefix, device),
+            kind,
+            device: device.to_string(),
+            enabled,
+        }
+    }
+}
+
+/// Read the ACPI wake devices and the network interfaces that can wake the machine
+pub fn read_wake_sources(sysfs_root: &Path, procfs_root: &Path) -> Result<Vec<WakeSource>, String> {
+    let mut sources = read_acpi_wakeup(procfs_root)?;
+    sources.extend(read_net_wakeup(sysfs_root)?);
+    Ok(sources)
+}
+
+/// Parse `/proc/acpi/wakeup`; a missing file means there are no ACPI wake devices
+fn read_acpi_wakeup(procfs_root: &Path) -> Result<Vec<WakeSource>, String> {
+    let path = procfs_root.join("acpi/wakeup");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    // Device  S-state  Status    Sysfs node
+    // GLAN    S4       *enabled  pci:0000:00:1f.6
+    Ok(content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            let status = fields.nth(1)?;
+            Some(WakeSource::new(WakeSourceKind::Acpi, device, status.trim_start_matches('*') == "enabled"))
+        })
+        .collect())
+}
+
+/// Network interfaces with a `device/power/wakeup` attribute
+fn read_net_wakeup(sysfs_root: &Path) -> Result<Vec<WakeSource>, String> {
+    let net = sysfs_root.join("class/net");
+    let entries = match fs::read_dir(&net) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", net.display(), e)),
+    };
+
+    let mut sources: Vec<WakeSource> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let wakeup = fs::read_to_string(entry.path().join("device/power/wakeup")).ok()?;
+            let interface = entry.file_name().to_string_lossy().into_owned();
+            Some(WakeSource::new(WakeSourceKind::Net, &interface, wakeup.trim() == "enabled"))
+        })
+        .collect();
+    sources.sort_by(|a, b| a.device.cmp(&b.device));
+    Ok(sources)
+}
+
+/// Allow or forbid a wake source to wake the machine
+pub fn write_wake_source(sysfs_root: &Path, procfs_root: &Path, source: &WakeSource, enabled: bool) -> Result<(), String> {
+    let (path, value) = match source.kind {
+        // Writing a device name toggles it, so only write when it has to change
+        WakeSourceKind::Acpi if source.enabled == enabled => return Ok(()),
+        WakeSourceKind::Acpi => (procfs_root.join("acpi/wakeup"), source.device.clone()),
+        WakeSourceKind::Net => (
+            sysfs_root.join("class/net").join(&source.device).join("device/power/wakeup"),
+            if enabled { "enabled" } else { "disabled" }.to_string(),
+        ),
+    };
+    fs::write(&path, value).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
@@ -1,14 +1,50 @@
 //! Main application state management
 
 use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::PathBuf,
     sync::{Arc, Mutex},
-    time::Instant,
+    time::Duration,
 };
 use chrono::{DateTime, Utc};
-use tokio::sync::{broadcast, watch};
-use tracing::{info, warn};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot, watch},
+    time::Instant,
+};
+use tracing::{debug, info, warn};
+
+use crate::services::{
+    AdmissionPolicy, HookPipeline, HookRun, HookStage, Backend, IdleAction, IdlePolicyConfig,
+    IdleStageStatus, Inhibitor, InhibitorPolicyConfig, ServiceConfig, ServiceSettings, SleepInhibitor, WakeAlarm,
+    CalendarConfig, ScheduleConfig, WakeOnLanConfig, WakeScheduleConfig, DEFAULT_PROCFS_ROOT, DEFAULT_SYSFS_ROOT,
+};
+use crate::utils::{Clock, SystemClock};
+use super::{
+    History, HistoryEntry, HistoryEvent, Hold, PersistedState, PhaseEvent, PhaseState, ResumeEvent,
+    SuspendPhase, SystemState, TimerCommand, COFFEE_HOLD, MAX_TIMER_SECONDS, MIN_TIMER_SECONDS,
+    TimerHysteresis, TimerRequest, TimerState,
+};
+
+/// Start of the error reported while the sleep inhibitor lock can't be taken
+const INHIBITOR_ERROR_PREFIX: &str = "Sleep inhibitor failed:";
 
-use super::{SystemState, TimerState};
+/// Ids tried before taking a hold gives up on finding an unused one
+const HOLD_ID_ATTEMPTS: usize = 4;
+
+/// How long a suspend confirmation token stays valid
+pub const SUSPEND_CONFIRM_TTL: Duration = Duration::from_secs(60);
+
+/// A confirmation token handed out for a suspend request while states are active
+#[derive(Debug, Clone)]
+pub struct SuspendConfirmation {
+    pub token: String,
+    pub expires: Instant,
+    /// The request the token confirms; it is only valid for the same one
+    pub action: IdleAction,
+    pub delay: Duration,
+}
 
 /// Main application state that manages all system states and timer
 #[derive(Debug)]
@@ -16,8 +52,56 @@ pub struct AppState {
     /// Current system states (coffee, ollama, errors)
     pub system_state: Arc<Mutex<SystemState>>,
     /// Timer configuration and state
-    pub timer_duration_minutes: u64,
+    pub timer_duration: Arc<Mutex<Duration>>,
     pub timer_state: Arc<Mutex<TimerState>>,
+    /// Settings changed at runtime and the file they are kept in
+    pub persisted: Mutex<PersistedState>,
+    pub state_file: Option<PathBuf>,
+    /// Suspension state machine, driven by the timer task
+    pub phase: Arc<Mutex<PhaseState>>,
+    /// Backend controlling services and sleep (systemd or simulated)
+    pub backend: Arc<Backend>,
+    /// Where sysfs is mounted (power states, CPU governors, RTC)
+    pub sysfs_root: PathBuf,
+    /// Where procfs is mounted (ACPI wake devices)
+    pub procfs_root: PathBuf,
+    /// Memory admission policy applied before starting services
+    pub admission_policy: AdmissionPolicy,
+    /// Per-service memory requirement and priority from the config file
+    pub service_settings: HashMap<String, ServiceSettings>,
+    /// logind sleep lock held while any state is active
+    pub inhibitor: Mutex<SleepInhibitor>,
+    pub inhibitor_enabled: bool,
+    /// What to do when the suspension timer expires
+    pub idle_action: IdleAction,
+    pub idle_command: Option<String>,
+    /// Stages run while the suspension countdown is running
+    pub idle_policy: IdlePolicyConfig,
+    /// Debounce and blip handling of the suspension timer
+    pub hysteresis: TimerHysteresis,
+    /// Other applications' inhibitors respected before suspending
+    pub inhibitor_policy: InhibitorPolicyConfig,
+    /// How long the timer stays held after a wake-up
+    pub resume_grace: Duration,
+    /// Work hours, quiet hours and timed service starts/stops
+    pub schedule: ScheduleConfig,
+    /// Idle timer of the quiet hours in effect, if any
+    pub quiet_timer: Mutex<Option<Duration>>,
+    /// Keep-awake holds from a local .ics file
+    pub calendar: CalendarConfig,
+    /// Daily wake-ups from the config file
+    pub wake_schedule: WakeScheduleConfig,
+    /// One-off wake-up requested through POST /wake-alarm
+    pub wake_request: Mutex<Option<WakeAlarm>>,
+    /// Wake-up programmed into the RTC before the last sleep
+    pub programmed_wake: Mutex<Option<WakeAlarm>>,
+    /// Machines woken through POST /wol/:host
+    pub wol: WakeOnLanConfig,
+    /// Hooks run around suspension and their latest results
+    pub hooks: HookPipeline,
+    pub last_hook_run: Arc<Mutex<Option<HookRun>>>,
+    /// Time source for countdowns, uptime and timestamps
+    pub clock: Arc<dyn Clock>,
     /// Server metadata
     pub start_time: Instant,
     pub port: u16,
@@ -31,6 +115,18 @@ pub struct AppState {
     pub timer_update_tx: watch::Sender<TimerState>,
     /// Keep the receiver alive to prevent channel closure
     pub _timer_update_rx: watch::Receiver<TimerState>,
+    /// Commands for the suspension timer task (extend, snooze, ...)
+    pub timer_command_tx: mpsc::Sender<TimerRequest>,
+    /// Receiving end, taken by the suspension timer task when it starts
+    pub timer_command_rx: Mutex<Option<mpsc::Receiver<TimerRequest>>>,
+    /// Outstanding confirmation token for POST /suspend
+    pub suspend_confirmation: Mutex<Option<SuspendConfirmation>>,
+    /// Channel for suspension phase transitions
+    pub phase_tx: broadcast::Sender<PhaseEvent>,
+    /// Channel for wake-up notifications
+    pub resume_tx: broadcast::Sender<ResumeEvent>,
+    /// Recent notable events (sleeps, ...)
+    pub history: Arc<Mutex<History>>,
 }
 
 impl AppState {
@@ -38,11 +134,43 @@ impl AppState {
     pub fn new(port: u16, host: String, timer_duration_minutes: u64) -> Self {
         let (state_change_tx, _) = broadcast::channel(100);
         let (timer_update_tx, timer_update_rx) = watch::channel(TimerState::new());
+        let (phase_tx, _) = broadcast::channel(64);
+        let (timer_command_tx, timer_command_rx) = mpsc::channel(16);
+        let (resume_tx, _) = broadcast::channel(16);
 
         Self {
             system_state: Arc::new(Mutex::new(SystemState::new())),
-            timer_duration_minutes,
+            timer_duration: Arc::new(Mutex::new(Duration::from_secs(timer_duration_minutes.saturating_mul(60)))),
             timer_state: Arc::new(Mutex::new(TimerState::new())),
+            persisted: Mutex::new(PersistedState::default()),
+            state_file: None,
+            phase: Arc::new(Mutex::new(PhaseState {
+                phase: SuspendPhase::Idle,
+                entered_at: Utc::now(),
+            })),
+            backend: Arc::new(Backend::Systemd),
+            sysfs_root: PathBuf::from(DEFAULT_SYSFS_ROOT),
+            procfs_root: PathBuf::from(DEFAULT_PROCFS_ROOT),
+            admission_policy: AdmissionPolicy::Reject,
+            service_settings: HashMap::new(),
+            inhibitor: Mutex::new(SleepInhibitor::new()),
+            inhibitor_enabled: true,
+            idle_action: IdleAction::Suspend,
+            idle_command: None,
+            idle_policy: IdlePolicyConfig::default(),
+            hysteresis: TimerHysteresis::default(),
+            inhibitor_policy: InhibitorPolicyConfig::default(),
+            resume_grace: Duration::ZERO,
+            schedule: ScheduleConfig::default(),
+            quiet_timer: Mutex::new(None),
+            calendar: CalendarConfig::default(),
+            wake_schedule: WakeScheduleConfig::default(),
+            wake_request: Mutex::new(None),
+            programmed_wake: Mutex::new(None),
+            wol: WakeOnLanConfig::default(),
+            hooks: HookPipeline::default(),
+            last_hook_run: Arc::new(Mutex::new(None)),
+            clock: Arc::new(SystemClock),
             start_time: Instant::now(),
             port,
             host,
@@ -51,47 +179,432 @@ impl AppState {
             state_change_tx,
             timer_update_tx,
             _timer_update_rx: timer_update_rx,
+            timer_command_tx,
+            timer_command_rx: Mutex::new(Some(timer_command_rx)),
+            suspend_confirmation: Mutex::new(None),
+            phase_tx,
+            resume_tx,
+            history: Arc::new(Mutex::new(History::new())),
         }
     }
 
+    /// Set the backend controlling services and sleep (systemd or simulated)
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        if backend.is_simulated() {
+            self.inhibitor = Mutex::new(SleepInhibitor::simulated());
+        }
+        self.backend = Arc::new(backend);
+        self
+    }
+
+    /// Set the time source (e.g. a `VirtualClock` in tests); uptime restarts from it
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.start_time = clock.now();
+        if let Ok(mut phase) = self.phase.lock() {
+            phase.entered_at = clock.utc();
+        }
+        self.clock = clock;
+        self
+    }
+
+    /// Keep runtime settings in `path` and apply the ones saved there
+    pub fn with_state_file(mut self, path: Option<PathBuf>) -> Self {
+        if let Some(path) = &path {
+            match PersistedState::load(path) {
+                Ok(persisted) => {
+                    match persisted.timer_seconds {
+                        Some(seconds) if !(MIN_TIMER_SECONDS..=MAX_TIMER_SECONDS).contains(&seconds) => {
+                            warn!(
+                                "Ignoring saved timer duration of {}s from {}, it must be {}-{}s",
+                                seconds,
+                                path.display(),
+                                MIN_TIMER_SECONDS,
+                                MAX_TIMER_SECONDS
+                            );
+                        }
+                        Some(seconds) => {
+                            info!("Using saved timer duration of {}s from {}", seconds, path.display());
+                            self.timer_duration = Arc::new(Mutex::new(Duration::from_secs(seconds)));
+                        }
+                        None => {}
+                    }
+                    self.persisted = Mutex::new(persisted);
+                }
+                Err(e) => warn!("Ignoring saved settings: {}", e),
+            }
+        }
+        self.state_file = path;
+        self
+    }
+
+    /// Set the memory admission policy used when starting services
+    pub fn with_admission_policy(mut self, policy: AdmissionPolicy) -> Self {
+        self.admission_policy = policy;
+        self
+    }
+
+    /// Set the per-service memory requirements and priorities
+    pub fn with_service_settings(mut self, settings: HashMap<String, ServiceSettings>) -> Self {
+        self.service_settings = settings;
+        self
+    }
+
+    /// Configuration of a managed service, with the settings from the config file applied
+    pub fn service_config(&self, name: &str) -> Option<ServiceConfig> {
+        let config = ServiceConfig::from_name(name)?;
+        Some(match self.service_settings.get(name) {
+            Some(settings) => config.with_settings(settings),
+            None => config,
+        })
+    }
+
+    /// Enable or disable the logind sleep inhibitor lock
+    pub fn with_inhibitor(mut self, enabled: bool) -> Self {
+        self.inhibitor_enabled = enabled;
+        self
+    }
+
+    /// Set the idle action and the command used for `IdleAction::Custom`
+    pub fn with_idle_action(mut self, action: IdleAction, command: Option<String>) -> Self {
+        self.idle_action = action;
+        self.idle_command = command;
+        self
+    }
+
+    /// Set the stages run while the suspension countdown is running
+    pub fn with_idle_policy(mut self, idle_policy: IdlePolicyConfig) -> Self {
+        self.idle_policy = idle_policy.sorted();
+        self
+    }
+
+    /// Set how the suspension timer reacts to flapping states
+    pub fn with_hysteresis(mut self, hysteresis: TimerHysteresis) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Set which inhibitors of other applications hold off suspension
+    pub fn with_inhibitor_policy(mut self, policy: InhibitorPolicyConfig) -> Self {
+        self.inhibitor_policy = policy;
+        self
+    }
+
+    /// Set where sysfs is mounted
+    pub fn with_sysfs_root(mut self, sysfs_root: PathBuf) -> Self {
+        self.sysfs_root = sysfs_root;
+        self
+    }
+
+    /// Set where procfs is mounted
+    pub fn with_procfs_root(mut self, procfs_root: PathBuf) -> Self {
+        self.procfs_root = procfs_root;
+        self
+    }
+
+    /// Set the Wake-on-LAN peers
+    pub fn with_wake_on_lan(mut self, wol: WakeOnLanConfig) -> Self {
+        self.wol = wol;
+        self
+    }
+
+    /// Set the daily wake-ups
+    pub fn with_wake_schedule(mut self, schedule: WakeScheduleConfig) -> Self {
+        self.wake_schedule = schedule;
+        self
+    }
+
+    /// Set how long the timer stays held after a wake-up
+    pub fn with_resume_grace(mut self, grace: Duration) -> Self {
+        self.resume_grace = grace;
+        self
+    }
+
+    /// Set the time-of-day schedules
+    pub fn with_schedule(mut self, schedule: ScheduleConfig) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Set the calendar providing keep-awake holds
+    pub fn with_calendar(mut self, calendar: CalendarConfig) -> Self {
+        self.calendar = calendar;
+        self
+    }
+
+    /// Set the hook pipeline run around suspension
+    pub fn with_hooks(mut self, hooks: HookPipeline) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
     /// Update a specific state and trigger state change notifications
     pub fn update_state<F>(&self, action: &str, updater: F) -> Result<SystemState, String>
     where
         F: FnOnce(&mut SystemState),
+    {
+        self.update_state_if(action, |state| {
+            updater(state);
+            true
+        })
+        .map(|new_state| new_state.expect("unconditional update"))
+    }
+
+    /// Like `update_state`, but nothing is recorded or broadcast when the
+    /// updater returns false; `None` then
+    fn update_state_if<F>(&self, action: &str, updater: F) -> Result<Option<SystemState>, String>
+    where
+        F: FnOnce(&mut SystemState) -> bool,
     {
         // Lock the system state and apply the update
         let mut state = self.system_state.lock()
             .map_err(|e| format!("Failed to lock system state: {}", e))?;
         
-        updater(&mut *state);
+        if !updater(&mut *state) {
+            return Ok(None);
+        }
+        state.sync_coffee();
         let new_state = state.clone();
         drop(state); // Release the lock early
 
+        // Hold or release the logind lock to match the new state
+        self.sync_inhibitor(&new_state);
+
         // Update last action tracking
+        self.record_action(action);
+
+        // Notify state change listeners (this will trigger timer logic)
+        if let Err(e) = self.state_change_tx.send(new_state.clone()) {
+            warn!("Failed to send state change notification: {}", e);
+        }
+
+        Ok(Some(new_state))
+    }
+
+    /// Record an action as the last action
+    pub fn record_action(&self, action: &str) {
         if let Ok(mut last_action) = self.last_action.lock() {
             *last_action = Some(action.to_string());
         }
         if let Ok(mut last_time) = self.last_action_time.lock() {
-            *last_time = Some(Utc::now());
+            *last_time = Some(self.clock.utc());
         }
+    }
 
-        // Notify state change listeners (this will trigger timer logic)
-        if let Err(e) = self.state_change_tx.send(new_state.clone()) {
-            warn!("Failed to send state change notification: {}", e);
+    /// Take the sleep inhibitor lock while any state is active, release it otherwise
+    pub fn sync_inhibitor(&self, system_state: &SystemState) {
+        if !self.inhibitor_enabled {
+            return;
         }
 
-        Ok(new_state)
+        let result = match self.inhibitor.lock() {
+            Ok(mut inhibitor) => {
+                if system_state.any_active() {
+                    let reason = format!("Keeping awake for: {}", system_state.active_holds().join(", "));
+                    inhibitor.acquire(&reason)
+                } else {
+                    inhibitor.release();
+                    Ok(())
+                }
+            }
+            Err(e) => Err(format!("Failed to lock inhibitor: {}", e)),
+        };
+
+        // A failure reported earlier is over once the lock behaves again
+        let error = result.err().map(|e| format!("{} {}", INHIBITOR_ERROR_PREFIX, e));
+        if let Err(e) = self.set_inhibitor_error(error) {
+            warn!("Failed to update inhibitor error: {}", e);
+        }
     }
 
-    /// Set the coffee state
+    /// Replace the reported sleep inhibitor failure, notifying listeners only
+    /// when it changed
+    fn set_inhibitor_error(&self, error: Option<String>) -> Result<(), String> {
+        let mut state = self.system_state.lock()
+            .map_err(|e| format!("Failed to lock system state: {}", e))?;
+
+        let current: Vec<&String> = state.errors
+            .iter()
+            .filter(|e| e.starts_with(INHIBITOR_ERROR_PREFIX))
+            .collect();
+        let unchanged = match &error {
+            Some(error) => current == [error],
+            None => current.is_empty(),
+        };
+        if unchanged {
+            return Ok(());
+        }
+
+        state.errors.retain(|e| !e.starts_with(INHIBITOR_ERROR_PREFIX));
+        if let Some(error) = error {
+            warn!("Adding error to state: {}", error);
+            state.add_error(error);
+        }
+        let new_state = state.clone();
+        drop(state);
+
+        if let Err(e) = self.state_change_tx.send(new_state) {
+            warn!("Failed to send inhibitor error notification: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Release the sleep inhibitor lock, e.g. on shutdown
+    pub fn release_inhibitor(&self) {
+        if let Ok(mut inhibitor) = self.inhibitor.lock() {
+            inhibitor.release();
+        }
+    }
+
+    /// Get whether the sleep inhibitor lock is held and its reason
+    pub fn get_inhibitor(&self) -> (bool, Option<String>) {
+        match self.inhibitor.lock() {
+            Ok(mut inhibitor) => (inhibitor.is_held(), inhibitor.reason().map(str::to_string)),
+            Err(_) => (false, None),
+        }
+    }
+
+    /// Take or release the default coffee hold; this also ends a running coffee lease.
+    /// Holds taken through `POST /holds` are left alone.
     pub fn set_coffee(&self, active: bool) -> Result<SystemState, String> {
         info!("Setting coffee state to: {}", active);
+        let hold = active.then(|| self.coffee_hold(None));
         self.update_state(
             if active { "coffee" } else { "chill" },
-            |state| state.coffee = active,
+            |state| match hold {
+                Some(hold) => {
+                    state.holds.insert(COFFEE_HOLD.to_string(), hold);
+                }
+                None => {
+                    state.holds.remove(COFFEE_HOLD);
+                }
+            },
         )
     }
 
+    /// Take the default coffee hold until `until`, when the hold expiry task releases it
+    pub fn set_coffee_lease(&self, until: DateTime<Utc>) -> Result<SystemState, String> {
+        info!("Setting coffee state until: {}", until);
+        let hold = self.coffee_hold(Some(until));
+        self.update_state("coffee-lease", |state| {
+            state.holds.insert(COFFEE_HOLD.to_string(), hold);
+        })
+    }
+
+    fn coffee_hold(&self, until: Option<DateTime<Utc>>) -> Hold {
+        Hold {
+            owner: "coffee".to_string(),
+            reason: "coffee".to_string(),
+            created_at: self.clock.utc(),
+            until,
+            ttl_seconds: None,
+            last_heartbeat: None,
+        }
+    }
+
+    /// Take a new hold for a client, returning its id; a hold with a `ttl` runs
+    /// out unless renewed with `renew_hold` within that time
+    pub fn take_hold(&self, owner: &str, reason: &str, ttl: Option<Duration>) -> Result<(String, SystemState), String> {
+        let now = self.clock.utc();
+        let until = match ttl {
+            Some(ttl) => Some(
+                chrono::Duration::from_std(ttl)
+                    .ok()
+                    .and_then(|ttl| now.checked_add_signed(ttl))
+                    .ok_or_else(|| format!("Hold ttl of {}s is too long", ttl.as_secs()))?,
+            ),
+            None => None,
+        };
+        let hold = Hold {
+            owner: owner.to_string(),
+            reason: reason.to_string(),
+            created_at: now,
+            until,
+            ttl_seconds: ttl.map(|ttl| ttl.as_secs()),
+            last_heartbeat: None,
+        };
+        // The id is the only thing allowing a client to release its hold, so it
+        // must neither be guessable nor replace another client's hold
+        for _ in 0..HOLD_ID_ATTEMPTS {
+            let id = random_id()?;
+            let taken = self.update_state_if(&format!("hold-{}", id), |state| {
+                if state.holds.contains_key(&id) {
+                    return false;
+                }
+                state.holds.insert(id.clone(), hold.clone());
+                true
+            })?;
+            if let Some(system_state) = taken {
+                info!("Hold {} taken by {}: {}", id, owner, reason);
+                return Ok((id, system_state));
+            }
+        }
+        Err("Failed to find an unused hold id".to_string())
+    }
+
+    /// Add or replace a hold keeping the machine awake
+    pub fn set_hold(&self, name: &str, hold: Hold) -> Result<SystemState, String> {
+        info!("Holding the machine awake: {} ({})", name, hold.reason);
+        self.update_state(&format!("hold-{}", name), |state| {
+            state.holds.insert(name.to_string(), hold);
+        })
+    }
+
+    /// Push the end of a renewable hold `ttl_seconds` from now (a heartbeat)
+    pub fn renew_hold(&self, id: &str) -> Result<SystemState, String> {
+        let now = self.clock.utc();
+        debug!("Heartbeat for hold {}", id);
+        self.update_state(&format!("heartbeat-{}", id), |state| {
+            if let Some(hold) = state.holds.get_mut(id) {
+                let until = hold.ttl_seconds
+                    .and_then(|ttl| i64::try_from(ttl).ok())
+                    .and_then(chrono::Duration::try_seconds)
+                    .and_then(|ttl| now.checked_add_signed(ttl));
+                if let Some(until) = until {
+                    hold.until = Some(until);
+                    hold.last_heartbeat = Some(now);
+                }
+            }
+        })
+    }
+
+    /// Release a hold before its time is up
+    pub fn release_hold(&self, name: &str) -> Result<SystemState, String> {
+        info!("Releasing hold: {}", name);
+        self.update_state(&format!("release-{}", name), |state| {
+            state.holds.remove(name);
+        })
+    }
+
+    /// Release the holds (including a coffee lease) whose time is up, returning their names
+    pub fn expire_holds(&self) -> Result<Vec<String>, String> {
+        let now = self.clock.utc();
+        let due = self.get_system_state()?.next_hold_expiry().is_some_and(|until| until <= now);
+        if !due {
+            return Ok(Vec::new());
+        }
+
+        let mut expired = Vec::new();
+        self.update_state("hold-expired", |state| expired = state.expire_holds(now))?;
+
+        let mut names = Vec::new();
+        for (name, hold) in expired {
+            // Holds with a ttl only run out when their client stopped sending heartbeats,
+            // possibly before the first one
+            if hold.ttl_seconds.is_some() {
+                warn!("Hold {} of {} missed its heartbeat", name, hold.owner);
+                self.record_history(HistoryEvent::HeartbeatMissed {
+                    id: name.clone(),
+                    owner: hold.owner,
+                    reason: hold.reason,
+                    last_heartbeat: hold.last_heartbeat.unwrap_or(hold.created_at),
+                });
+            }
+            names.push(name);
+        }
+        info!("Holds expired: {}", names.join(", "));
+        Ok(names)
+    }
+
     /// Set a service state
     pub fn set_service(&self, service_name: &str, active: bool) -> Result<SystemState, String> {
         info!("Setting {} service state to: {}", service_name, active);
@@ -153,13 +666,190 @@ impl AppState {
             .map_err(|e| format!("Failed to lock timer state: {}", e))
     }
 
-    /// Update timer state
-    pub fn update_timer_state(&self, active: bool, remaining_seconds: Option<u64>) -> Result<(), String> {
+    /// Get the configured idle timer duration
+    pub fn get_timer_duration(&self) -> Duration {
+        self.timer_duration.lock()
+            .map(|duration| *duration)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// The idle timer duration in effect: the configured one, shortened during quiet hours
+    pub fn effective_timer_duration(&self) -> Duration {
+        let configured = self.get_timer_duration();
+        match self.get_quiet_timer() {
+            Some(quiet) => configured.min(quiet),
+            None => configured,
+        }
+    }
+
+    /// Idle timer of the quiet hours in effect, if any
+    pub fn get_quiet_timer(&self) -> Option<Duration> {
+        self.quiet_timer.lock().ok().and_then(|quiet| *quiet)
+    }
+
+    /// Enter (`Some`) or leave (`None`) quiet hours; returns the previous effective duration
+    pub fn set_quiet_timer(&self, quiet_timer: Option<Duration>) -> Result<Duration, String> {
+        let previous = self.effective_timer_duration();
+        let mut current = self.quiet_timer.lock()
+            .map_err(|e| format!("Failed to lock quiet timer: {}", e))?;
+        *current = quiet_timer;
+        Ok(previous)
+    }
+
+    /// Change the idle timer duration; returns the previous effective duration
+    pub fn set_timer_duration(&self, duration: Duration) -> Result<Duration, String> {
+        let effective = self.effective_timer_duration();
+        let mut current = self.timer_duration.lock()
+            .map_err(|e| format!("Failed to lock timer duration: {}", e))?;
+        let previous = std::mem::replace(&mut *current, duration);
+        drop(current);
+
+        info!("Timer duration changed from {}s to {}s", previous.as_secs(), duration.as_secs());
+        Ok(effective)
+    }
+
+    /// Change the persisted settings and write them to the state file, if any
+    pub fn update_persisted<F>(&self, updater: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut PersistedState),
+    {
+        let mut persisted = self.persisted.lock()
+            .map_err(|e| format!("Failed to lock persisted state: {}", e))?;
+        updater(&mut persisted);
+
+        match &self.state_file {
+            Some(path) => persisted.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Arm the suspension timer to expire `duration` from now and return the deadline
+    pub fn arm_timer(&self, duration: Duration) -> Result<Instant, String> {
+        let too_long = || format!("Timer of {}s is too long", duration.as_secs());
+        let deadline = self.clock.now().checked_add(duration).ok_or_else(too_long)?;
+        let suspend_at = chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| self.clock.utc().checked_add_signed(duration))
+            .ok_or_else(too_long)?;
+        self.modify_timer_state(|timer_state| {
+            timer_state.active = true;
+            timer_state.deadline = Some(deadline);
+            timer_state.suspend_at = Some(suspend_at);
+        })?;
+        Ok(deadline)
+    }
+
+    /// Disarm the suspension timer, dropping its idle stages
+    pub fn disarm_timer(&self) -> Result<(), String> {
+        self.modify_timer_state(|timer_state| *timer_state = TimerState::inactive())
+    }
+
+    /// Publish the inhibitors that held off the last suspension attempt
+    pub fn set_blocking_inhibitors(&self, inhibitors: Vec<Inhibitor>) -> Result<(), String> {
+        self.modify_timer_state(|timer_state| timer_state.blocking_inhibitors = inhibitors)
+    }
+
+    /// Request a one-off wake-up, replacing the previous request
+    pub fn request_wake_alarm(&self, alarm: WakeAlarm) -> Result<(), String> {
+        let mut request = self.wake_request.lock()
+            .map_err(|e| format!("Failed to lock wake alarm: {}", e))?;
+        info!("Wake alarm {} requested for {}", alarm.name, alarm.at);
+        *request = Some(alarm);
+        Ok(())
+    }
+
+    /// Drop the one-off wake-up request, returning it
+    pub fn cancel_wake_alarm(&self) -> Result<Option<WakeAlarm>, String> {
+        let mut request = self.wake_request.lock()
+            .map_err(|e| format!("Failed to lock wake alarm: {}", e))?;
+        Ok(request.take())
+    }
+
+    /// The next wake-up: the requested one or the next scheduled one, whichever comes first
+    pub fn next_wake_alarm(&self) -> Option<WakeAlarm> {
+        let now = self.clock.utc();
+        let (scheduled, errors) = self.wake_schedule.next_after(now);
+        for error in errors {
+            warn!("{}", error);
+        }
+
+        let requested = self.wake_request.lock().ok()
+            .and_then(|request| request.clone())
+            .filter(|alarm| alarm.at > now);
+
+        match (requested, scheduled) {
+            (Some(requested), Some(scheduled)) if scheduled.at < requested.at => Some(scheduled),
+            (Some(requested), _) => Some(requested),
+            (None, scheduled) => scheduled,
+        }
+    }
+
+    /// Program the next wake-up into the RTC before sleeping
+    pub fn program_wake_alarm(&self) -> Result<Option<WakeAlarm>, String> {
+        let Some(alarm) = self.next_wake_alarm() else {
+            return Ok(None);
+        };
+
+        let from_now = (alarm.at - self.clock.utc()).to_std().unwrap_or(Duration::ZERO);
+        self.backend.set_wake_alarm(&self.sysfs_root, alarm.at, from_now)?;
+        info!("Wake alarm {} programmed for {}", alarm.name, alarm.at);
+
+        let mut programmed = self.programmed_wake.lock()
+            .map_err(|e| format!("Failed to lock wake alarm: {}", e))?;
+        *programmed = Some(alarm.clone());
+        Ok(Some(alarm))
+    }
+
+    /// Take the wake-up programmed before the last sleep
+    pub fn take_programmed_wake(&self) -> Option<WakeAlarm> {
+        self.programmed_wake.lock().ok()?.take()
+    }
+
+    /// Drop the one-off request once its wake-up happened
+    pub fn complete_wake_alarm(&self, alarm: &WakeAlarm) {
+        if let Ok(mut request) = self.wake_request.lock() {
+            if request.as_ref() == Some(alarm) {
+                *request = None;
+            }
+        }
+    }
+
+    /// Start the post-resume grace period and return its end
+    pub fn start_grace(&self, duration: Duration) -> Result<Instant, String> {
+        let too_long = || format!("Grace period of {}s is too long", duration.as_secs());
+        let deadline = self.clock.now().checked_add(duration).ok_or_else(too_long)?;
+        let ends_at = chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| self.clock.utc().checked_add_signed(duration))
+            .ok_or_else(too_long)?;
+        self.modify_timer_state(|timer_state| {
+            timer_state.grace_deadline = Some(deadline);
+            timer_state.grace_ends_at = Some(ends_at);
+        })?;
+        Ok(deadline)
+    }
+
+    /// End the post-resume grace period
+    pub fn end_grace(&self) -> Result<(), String> {
+        self.modify_timer_state(|timer_state| {
+            timer_state.grace_deadline = None;
+            timer_state.grace_ends_at = None;
+        })
+    }
+
+    /// Publish the progress of the idle stages of the running countdown
+    pub fn set_idle_stages(&self, stages: Vec<IdleStageStatus>) -> Result<(), String> {
+        self.modify_timer_state(|timer_state| timer_state.stages = stages)
+    }
+
+    /// Change the timer state and notify watchers
+    fn modify_timer_state<F>(&self, updater: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut TimerState),
+    {
         let mut timer_state = self.timer_state.lock()
             .map_err(|e| format!("Failed to lock timer state: {}", e))?;
-        
-        timer_state.active = active;
-        timer_state.remaining_seconds = remaining_seconds;
+        updater(&mut timer_state);
         let new_timer_state = timer_state.clone();
         drop(timer_state);
 
@@ -171,9 +861,64 @@ impl AppState {
         Ok(())
     }
 
+    /// Take the receiving end of the timer command channel (once, for the timer task)
+    pub fn take_timer_commands(&self) -> Option<mpsc::Receiver<TimerRequest>> {
+        self.timer_command_rx.lock().ok().and_then(|mut rx| rx.take())
+    }
+
+    /// Send a command to the suspension timer task and wait for its outcome
+    pub async fn send_timer_command(&self, command: TimerCommand) -> Result<String, String> {
+        let (reply, outcome) = oneshot::channel();
+        self.timer_command_tx
+            .send(TimerRequest { command, reply })
+            .await
+            .map_err(|_| "Suspension timer is not running".to_string())?;
+        outcome
+            .await
+            .map_err(|_| "Suspension timer dropped the command".to_string())?
+    }
+
+    /// Hand out a new single-use token confirming a suspend request for `action` in `delay`
+    pub fn issue_suspend_token(&self, action: IdleAction, delay: Duration) -> Result<(String, DateTime<Utc>), String> {
+        let token = random_id()?;
+
+        let mut confirmation = self.suspend_confirmation.lock()
+            .map_err(|e| format!("Failed to lock suspend confirmation: {}", e))?;
+        *confirmation = Some(SuspendConfirmation {
+            token: token.clone(),
+            expires: self.clock.now() + SUSPEND_CONFIRM_TTL,
+            action,
+            delay,
+        });
+
+        let expires_at = self.clock.utc()
+            + chrono::Duration::from_std(SUSPEND_CONFIRM_TTL).unwrap_or_else(|_| chrono::Duration::zero());
+        Ok((token, expires_at))
+    }
+
+    /// Check and consume a suspend confirmation token issued for the same `action` and `delay`
+    pub fn redeem_suspend_token(&self, token: &str, action: IdleAction, delay: Duration) -> bool {
+        let mut confirmation = match self.suspend_confirmation.lock() {
+            Ok(confirmation) => confirmation,
+            Err(_) => return false,
+        };
+        let valid = confirmation
+            .as_ref()
+            .is_some_and(|pending| {
+                pending.token == token
+                    && pending.action == action
+                    && pending.delay == delay
+                    && self.clock.now() < pending.expires
+            });
+        if valid {
+            *confirmation = None;
+        }
+        valid
+    }
+
     /// Calculate server uptime as a formatted string
     pub fn get_uptime(&self) -> String {
-        let duration = self.start_time.elapsed();
+        let duration = self.clock.now().saturating_duration_since(self.start_time);
         let hours = duration.as_secs() / 3600;
         let minutes = (duration.as_secs() % 3600) / 60;
         let seconds = duration.as_secs() % 60;
@@ -208,21 +953,104 @@ impl AppState {
         Ok(())
     }
 
-    /// Set the suspended state (internal use only)
-    pub fn set_suspended(&self, suspended: bool) -> Result<(), String> {
-        let mut state = self.system_state.lock()
-            .map_err(|e| format!("Failed to lock system state: {}", e))?;
-        
-        state.set_suspended(suspended);
-        info!("Suspended state set to: {}", suspended);
+    /// Move the suspension state machine to `next` and broadcast the transition.
+    ///
+    /// Staying in the current phase is a no-op; transitions the state machine
+    /// doesn't allow are rejected and leave the phase unchanged. Only the
+    /// suspension timer task moves between phases.
+    pub(crate) fn transition(&self, next: SuspendPhase) -> Result<(), String> {
+        let mut phase = self.phase.lock()
+            .map_err(|e| format!("Failed to lock suspend phase: {}", e))?;
+
+        let from = phase.phase;
+        if from == next {
+            return Ok(());
+        }
+        if !from.can_transition_to(next) {
+            return Err(format!("Invalid suspend phase transition: {} -> {}", from, next));
+        }
+
+        let at = self.clock.utc();
+        *phase = PhaseState { phase: next, entered_at: at };
+        drop(phase);
+
+        info!("Suspend phase: {} -> {}", from, next);
+        // No subscribers is fine
+        let _ = self.phase_tx.send(PhaseEvent { from, to: next, at });
         Ok(())
     }
 
-    /// Check if the system was suspended (internal use only)
+    /// Get the current suspend phase and when it was entered
+    pub fn get_phase(&self) -> Result<PhaseState, String> {
+        self.phase.lock()
+            .map(|phase| *phase)
+            .map_err(|e| format!("Failed to lock suspend phase: {}", e))
+    }
+
+    /// Check whether we are putting (or have put) the machine to sleep ourselves
     pub fn is_suspended(&self) -> Result<bool, String> {
-        let state = self.system_state.lock()
-            .map_err(|e| format!("Failed to lock system state: {}", e))?;
-        
-        Ok(state.is_suspended())
+        Ok(self.get_phase()?.phase.is_suspending())
+    }
+
+    /// Record an event in the history
+    pub fn record_history(&self, event: HistoryEvent) {
+        match self.history.lock() {
+            Ok(mut history) => history.record(self.clock.utc(), event),
+            Err(e) => warn!("Failed to lock history: {}", e),
+        }
+    }
+
+    /// Get recorded history entries, oldest first
+    pub fn get_history(&self) -> Result<Vec<HistoryEntry>, String> {
+        self.history.lock()
+            .map(|history| history.entries())
+            .map_err(|e| format!("Failed to lock history: {}", e))
     }
+
+    /// Run the hooks of a stage and record the results in the history and status
+    pub async fn run_hooks(&self, stage: HookStage) -> HookRun {
+        let run = self.hooks.run(&self.backend, self.clock.as_ref(), stage).await;
+
+        self.record_history(HistoryEvent::Hooks {
+            stage,
+            results: run.results.clone(),
+            vetoed: run.vetoed.clone(),
+        });
+        if let Ok(mut last_run) = self.last_hook_run.lock() {
+            *last_run = Some(run.clone());
+        }
+
+        run
+    }
+
+    /// Get the results of the latest hook run
+    pub fn get_last_hook_run(&self) -> Option<HookRun> {
+        self.last_hook_run.lock().ok().and_then(|run| run.clone())
+    }
+
+    /// Record a wake-up in the history and notify resume listeners
+    pub fn notify_resume(&self, event: ResumeEvent) {
+        info!("System resumed after sleeping {}s (initiated by us: {})",
+              event.slept_seconds, event.initiated_by_us);
+
+        self.record_history(HistoryEvent::Sleep {
+            suspended_at: event.suspended_at,
+            resumed_at: event.resumed_at,
+            duration_seconds: event.slept_seconds,
+            initiated_by_us: event.initiated_by_us,
+        });
+
+        // No subscribers is fine, e.g. during startup
+        let _ = self.resume_tx.send(event);
+    }
+}
+
+/// A random 32-digit hex string for tokens and hold ids, read from the kernel's
+/// random source
+fn random_id() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .map_err(|e| format!("Failed to read /dev/urandom: {}", e))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
 }
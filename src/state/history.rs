@@ -0,0 +1,100 @@
+//! Event history kept for client visibility
+
+use std::collections::VecDeque;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::services::{HookResult, HookStage, IdleAction};
+
+/// Maximum number of entries kept in memory
+const HISTORY_CAPACITY: usize = 100;
+
+/// Notable events recorded in the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    /// The machine was asleep and has resumed
+    Sleep {
+        suspended_at: DateTime<Utc>,
+        resumed_at: DateTime<Utc>,
+        duration_seconds: u64,
+        /// Whether order-coffee itself requested the suspension
+        initiated_by_us: bool,
+    },
+    /// Pre-suspend or post-resume hooks ran
+    Hooks {
+        stage: HookStage,
+        results: Vec<HookResult>,
+        vetoed: Option<String>,
+    },
+    /// The idle action failed or the machine didn't go to sleep
+    SuspendFailed {
+        action: IdleAction,
+        /// Consecutive failures so far
+        attempt: u32,
+        reason: String,
+        retry_in_seconds: u64,
+    },
+    /// The machine woke up for a wake alarm
+    ScheduledWake {
+        name: String,
+        alarm_at: DateTime<Utc>,
+        /// End of the hold keeping the machine awake afterwards
+        hold_until: Option<DateTime<Utc>>,
+        start_services: Vec<String>,
+    },
+    /// A hold renewed through heartbeats ran out because they stopped
+    HeartbeatMissed {
+        id: String,
+        owner: String,
+        reason: String,
+        /// When the hold was last renewed
+        last_heartbeat: DateTime<Utc>,
+    },
+}
+
+/// A single history entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: HistoryEvent,
+}
+
+/// Bounded, oldest-first list of recent events
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl History {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event at `timestamp`, dropping the oldest entry when full
+    pub fn record(&mut self, timestamp: DateTime<Utc>, event: HistoryEvent) {
+        if self.entries.len() >= HISTORY_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry {
+            timestamp,
+            event,
+        });
+    }
+
+    /// Get all entries, oldest first
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// Notification sent when the machine resumes from sleep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeEvent {
+    pub suspended_at: DateTime<Utc>,
+    pub resumed_at: DateTime<Utc>,
+    pub slept_seconds: u64,
+    pub initiated_by_us: bool,
+}
@@ -5,8 +5,16 @@
 pub mod system_state;
 pub mod app_state;
 pub mod timer_state;
+pub mod history;
+pub mod suspend_phase;
+pub mod persisted;
 
 // Re-export main types
-pub use system_state::SystemState;
+pub use system_state::{is_server_hold, Hold, SystemState, COFFEE_HOLD};
 pub use app_state::AppState;
-pub use timer_state::TimerState;
+pub use timer_state::{
+    BlipPolicy, TimerCommand, TimerHysteresis, TimerRequest, TimerState, MAX_TIMER_SECONDS, MIN_TIMER_SECONDS,
+};
+pub use history::{History, HistoryEntry, HistoryEvent, ResumeEvent};
+pub use persisted::{PersistedState, DEFAULT_STATE_FILE};
+pub use suspend_phase::{PhaseEvent, PhaseState, SuspendPhase};
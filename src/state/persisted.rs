@@ -0,0 +1,44 @@
+//! Settings changed at runtime and kept across restarts
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// Default location of the state file (systemd `StateDirectory=order-coffee`)
+pub const DEFAULT_STATE_FILE: &str = "/var/lib/order-coffee/state.json";
+
+/// Contents of the state file given with `--state-file`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistedState {
+    /// Idle timer duration set through PUT /config/timer, overrides `--timer`
+    pub timer_seconds: Option<u64>,
+}
+
+impl PersistedState {
+    /// Read the state file; a missing file means nothing was saved yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read state file {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse state file {}: {}", path.display(), e))
+    }
+
+    /// Write the state file atomically (temporary file, then rename)
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize state: {}", e))?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, content)
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        std::fs::rename(&tmp, path)
+            .map_err(|e| format!("Failed to replace state file {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,91 @@
+//! Suspension state machine
+
+use std::fmt;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where the server is in the suspension cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuspendPhase {
+    /// Nothing holds the machine awake and no countdown is running
+    #[default]
+    Idle,
+    /// At least one state (coffee, a service, ...) keeps the machine awake
+    Held,
+    /// The suspension timer is running
+    CountingDown,
+    /// Pre-suspend hooks are running
+    PreSuspendHooks,
+    /// The idle action is being executed
+    Suspending,
+    /// The idle action was executed, the machine is asleep (or about to be)
+    Suspended,
+    /// A wake-up was detected and is being handled
+    Resuming,
+    /// Post-resume grace period before the timer may run again
+    Grace,
+}
+
+impl SuspendPhase {
+    /// Check whether moving from this phase to `next` is allowed
+    pub fn can_transition_to(self, next: SuspendPhase) -> bool {
+        use SuspendPhase::*;
+
+        match (self, next) {
+            // A wake-up can interrupt anything except an ongoing resume: the
+            // machine may have been put to sleep by someone else
+            (Resuming, Resuming) => false,
+            (_, Resuming) => true,
+
+            (Idle, Held | CountingDown | PreSuspendHooks | Suspending) => true,
+            (Held, Idle | CountingDown | PreSuspendHooks | Suspending) => true,
+            (CountingDown, Idle | Held | PreSuspendHooks | Suspending) => true,
+            // A veto or a failure puts us back where we came from
+            (PreSuspendHooks, Idle | Held | CountingDown | Suspending) => true,
+            (Suspending, Idle | Held | CountingDown | Suspended) => true,
+            // Activity while marked suspended means the machine never slept
+            (Suspended, Held | CountingDown) => true,
+            (Resuming, Idle | Held | CountingDown | Grace) => true,
+            (Grace, Idle | Held | CountingDown | PreSuspendHooks | Suspending) => true,
+
+            _ => false,
+        }
+    }
+
+    /// Check whether we put the machine to sleep ourselves
+    pub fn is_suspending(self) -> bool {
+        matches!(self, SuspendPhase::Suspending | SuspendPhase::Suspended)
+    }
+}
+
+impl fmt::Display for SuspendPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SuspendPhase::Idle => "idle",
+            SuspendPhase::Held => "held",
+            SuspendPhase::CountingDown => "counting_down",
+            SuspendPhase::PreSuspendHooks => "pre_suspend_hooks",
+            SuspendPhase::Suspending => "suspending",
+            SuspendPhase::Suspended => "suspended",
+            SuspendPhase::Resuming => "resuming",
+            SuspendPhase::Grace => "grace",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The current phase and when it was entered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PhaseState {
+    pub phase: SuspendPhase,
+    pub entered_at: DateTime<Utc>,
+}
+
+/// Broadcast for every phase transition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseEvent {
+    pub from: SuspendPhase,
+    pub to: SuspendPhase,
+    pub at: DateTime<Utc>,
+}
@@ -1,18 +1,53 @@
 //! System state structure and management
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+/// Name of the default hold taken by /coffee and released by /chill
+pub const COFFEE_HOLD: &str = "coffee";
+
+/// Name prefixes of the holds the server takes itself (schedules, calendar events
+/// and wake alarms); clients can't release them
+pub const SERVER_HOLD_PREFIXES: [&str; 3] = ["schedule:", "calendar:", "wake:"];
+
+/// Check whether a hold is taken and released by the server rather than a client
+pub fn is_server_hold(name: &str) -> bool {
+    SERVER_HOLD_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// A hold keeping the machine awake, taken by a client (`POST /holds`, /coffee)
+/// or by the server itself (e.g. after a scheduled wake-up)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hold {
+    /// Who took the hold: the name a client gave, or the server component
+    pub owner: String,
+    pub reason: String,
+    /// When the hold was taken
+    pub created_at: DateTime<Utc>,
+    /// When the hold is released automatically
+    pub until: Option<DateTime<Utc>>,
+    /// How far `POST /holds/{id}/heartbeat` pushes `until` from now; none if
+    /// the hold can't be renewed
+    pub ttl_seconds: Option<u64>,
+    /// When a heartbeat last renewed the hold; none until the first one
+    #[serde(default)]
+    pub last_heartbeat: Option<DateTime<Utc>>,
+}
 
 /// System state structure - holds all states that can prevent suspension
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemState {
-    /// Manual sleep prevention state (controlled by /coffee and /chill endpoints)
+    /// Whether the default coffee hold is taken (/coffee and /chill), kept for older clients
     pub coffee: bool,
+    /// When the coffee lease runs out (`/coffee?for=2h`); none for a plain `/coffee`
+    #[serde(default)]
+    pub coffee_until: Option<DateTime<Utc>>,
     /// Generic services state (replaces ollama: bool)
     pub services: HashMap<String, bool>,
-    /// Internal flag to track if system was suspended (not exposed in API)
-    #[serde(skip)]
-    suspended: bool,
+    /// Holds keyed by name or id (e.g. "coffee", "wake:nightly-backup")
+    #[serde(default)]
+    pub holds: BTreeMap<String, Hold>,
     /// List of current errors for client visibility
     pub errors: Vec<String>,
 }
@@ -27,15 +62,60 @@ impl SystemState {
         
         Self {
             coffee: false,
+            coffee_until: None,
             services,
-            suspended: false,
+            holds: BTreeMap::new(),
             errors: Vec::new(),
         }
     }
 
     /// Check if any state is active (true)
     pub fn any_active(&self) -> bool {
-        self.coffee || self.services.values().any(|&active| active)
+        self.services.values().any(|&active| active) || !self.holds.is_empty()
+    }
+
+    /// Names of all active states, coffee first, then services and holds in name order
+    pub fn active_holds(&self) -> Vec<String> {
+        let mut holds = Vec::new();
+        if self.coffee {
+            holds.push("coffee".to_string());
+        }
+        let mut services: Vec<String> = self.services
+            .iter()
+            .filter(|(_, &active)| active)
+            .map(|(name, _)| name.clone())
+            .collect();
+        services.sort();
+        holds.extend(services);
+        holds.extend(self.holds.keys().filter(|name| *name != COFFEE_HOLD).cloned());
+        holds
+    }
+
+    /// Derive the `coffee` and `coffee_until` fields from the default coffee hold
+    pub fn sync_coffee(&mut self) {
+        let coffee = self.holds.get(COFFEE_HOLD);
+        self.coffee = coffee.is_some();
+        self.coffee_until = coffee.and_then(|hold| hold.until);
+    }
+
+    /// The earliest time a hold is released automatically
+    pub fn next_hold_expiry(&self) -> Option<DateTime<Utc>> {
+        self.holds.values().filter_map(|hold| hold.until).min()
+    }
+
+    /// Drop the holds whose time is up at `now`, returning them with their names
+    pub fn expire_holds(&mut self, now: DateTime<Utc>) -> Vec<(String, Hold)> {
+        let names: Vec<String> = self.holds
+            .iter()
+            .filter(|(_, hold)| hold.until.is_some_and(|until| until <= now))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let expired = names
+            .into_iter()
+            .filter_map(|name| self.holds.remove(&name).map(|hold| (name, hold)))
+            .collect();
+        self.sync_coffee();
+        expired
     }
 
     /// Check if all states are inactive (false)
@@ -67,16 +147,6 @@ impl SystemState {
     pub fn get_service(&self, service_name: &str) -> bool {
         self.services.get(service_name).copied().unwrap_or(false)
     }
-
-    /// Set the suspended state (internal use only)
-    pub(crate) fn set_suspended(&mut self, suspended: bool) {
-        self.suspended = suspended;
-    }
-
-    /// Check if the system was suspended (internal use only)
-    pub(crate) fn is_suspended(&self) -> bool {
-        self.suspended
-    }
 }
 
 impl Default for SystemState {
@@ -1,10 +1,38 @@
 //! Timer state structure and management
 
-/// Timer state for tracking suspension countdown
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::oneshot, time::Instant};
+
+use crate::services::{IdleAction, IdleStageStatus, Inhibitor};
+
+/// Shortest idle timer accepted (PUT /config/timer, config and state files)
+pub const MIN_TIMER_SECONDS: u64 = 10;
+
+/// Longest idle timer or countdown accepted (one week)
+pub const MAX_TIMER_SECONDS: u64 = 7 * 24 * 3600;
+
+/// Timer state for tracking the suspension deadline.
+///
+/// Only the deadline is stored; the remaining time is derived from it when
+/// read, so a running countdown never has to be rewritten.
 #[derive(Debug, Clone)]
 pub struct TimerState {
     pub active: bool,
-    pub remaining_seconds: Option<u64>,
+    /// When the idle action runs (monotonic, drives the timer task)
+    pub deadline: Option<Instant>,
+    /// When the idle action runs, as wall-clock time for clients
+    pub suspend_at: Option<DateTime<Utc>>,
+    /// Idle stages of the running countdown
+    pub stages: Vec<IdleStageStatus>,
+    /// Inhibitors of other applications that held off the last suspension attempt
+    pub blocking_inhibitors: Vec<Inhibitor>,
+    /// End of the post-resume grace period (monotonic)
+    pub grace_deadline: Option<Instant>,
+    /// End of the post-resume grace period, as wall-clock time for clients
+    pub grace_ends_at: Option<DateTime<Utc>>,
 }
 
 impl TimerState {
@@ -12,38 +40,32 @@ impl TimerState {
     pub fn new() -> Self {
         Self {
             active: false,
-            remaining_seconds: None,
-        }
-    }
-
-    /// Create an active timer state with remaining seconds
-    pub fn active(remaining_seconds: u64) -> Self {
-        Self {
-            active: true,
-            remaining_seconds: Some(remaining_seconds),
+            deadline: None,
+            suspend_at: None,
+            stages: Vec::new(),
+            blocking_inhibitors: Vec::new(),
+            grace_deadline: None,
+            grace_ends_at: None,
         }
     }
 
     /// Create an inactive timer state
     pub fn inactive() -> Self {
-        Self {
-            active: false,
-            remaining_seconds: None,
-        }
+        Self::new()
     }
 
-    /// Check if the timer is active
-    pub fn is_active(&self) -> bool {
-        self.active
+    /// Get remaining seconds at `now` if timer is active
+    pub fn remaining_seconds(&self, now: Instant) -> Option<u64> {
+        match (self.active, self.deadline) {
+            (true, Some(deadline)) => Some(deadline.saturating_duration_since(now).as_secs()),
+            _ => None,
+        }
     }
 
-    /// Get remaining seconds if timer is active
-    pub fn remaining_seconds(&self) -> Option<u64> {
-        if self.active {
-            self.remaining_seconds
-        } else {
-            None
-        }
+    /// Get remaining seconds of the post-resume grace period at `now`
+    pub fn grace_remaining_seconds(&self, now: Instant) -> Option<u64> {
+        self.grace_deadline
+            .map(|deadline| deadline.saturating_duration_since(now).as_secs())
     }
 }
 
@@ -52,3 +74,61 @@ impl Default for TimerState {
         Self::new()
     }
 }
+
+/// What a brief activity does to a running countdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlipPolicy {
+    /// Start over with the full duration once everything is inactive again
+    #[default]
+    Reset,
+    /// Continue with the time that was left, if the activity ended within the blip window
+    Pause,
+}
+
+/// How the timer reacts to states flapping between active and inactive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerHysteresis {
+    /// How long everything must stay inactive before a countdown starts
+    pub debounce: Duration,
+    pub blip_policy: BlipPolicy,
+    /// Longest activity still counted as a blip by `BlipPolicy::Pause`
+    pub blip_window: Duration,
+}
+
+impl Default for TimerHysteresis {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::ZERO,
+            blip_policy: BlipPolicy::Reset,
+            blip_window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Requests to the running suspension timer, sent from the API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerCommand {
+    /// Push the running countdown's deadline back
+    Extend(Duration),
+    /// Restart the countdown with a short duration from now
+    Snooze(Duration),
+    /// Restart the countdown with the full timer duration
+    Reset,
+    /// Stop the countdown until the next state change
+    Cancel,
+    /// End the post-resume grace period early
+    EndGrace,
+    /// The configured duration changed from `previous`, re-compute the countdown
+    DurationChanged { previous: Duration },
+    /// Run `action` after `delay`, regardless of active states
+    SuspendNow { action: IdleAction, delay: Duration },
+}
+
+/// A timer command with the channel its outcome is reported on
+#[derive(Debug)]
+pub struct TimerRequest {
+    pub command: TimerCommand,
+    /// Receives a description of what was done, or why the command was refused
+    pub reply: oneshot::Sender<Result<String, String>>,
+}
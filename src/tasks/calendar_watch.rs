@@ -0,0 +1,115 @@
+//! Calendar hold background task
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use tokio::time::interval;
+use tracing::{error, info, warn};
+
+use crate::{
+    services::{
+        calendar_version, read_calendar, run_service_action, running_events, CalendarEvent, ScheduledServiceAction,
+    },
+    state::{AppState, Hold},
+};
+
+/// Background task turning running events of the `[calendar]` file into holds.
+///
+/// The file is read again whenever its modification time or size changes; holds
+/// of events that ended early or were removed from the file are released.
+pub async fn calendar_task(state: Arc<AppState>) {
+    let Some(path) = state.calendar.path.clone() else {
+        return;
+    };
+    info!("Starting calendar task for {}", path.display());
+
+    let mut interval = interval(Duration::from_secs(state.calendar.poll_seconds.max(1)));
+    let mut version = None;
+    let mut events = Vec::new();
+    let mut last_error: Option<String> = None;
+    let mut started = HashSet::new();
+
+    loop {
+        interval.tick().await;
+
+        let read = calendar_version(&path).and_then(|current| {
+            if version == Some(current) {
+                return Ok(());
+            }
+            events = read_calendar(&path)?;
+            version = Some(current);
+            info!("Calendar {} read: {} events", path.display(), events.len());
+            Ok(())
+        });
+        match read {
+            Ok(()) => {
+                if last_error.take().is_some() {
+                    let _ = state.clear_errors_for("calendar");
+                }
+            }
+            // Keep the events read last time, report the error once
+            Err(e) if last_error.as_ref() != Some(&e) => {
+                warn!("{}", e);
+                let _ = state.clear_errors_for("calendar");
+                let _ = state.add_error(e.clone());
+                last_error = Some(e);
+            }
+            Err(_) => {}
+        }
+
+        apply_calendar(&state, &events, &mut started).await;
+    }
+}
+
+/// Hold the machine awake for running events and release the holds of the others
+async fn apply_calendar(state: &AppState, events: &[CalendarEvent], started: &mut HashSet<String>) {
+    let now = state.clock.utc();
+    let active: Vec<CalendarEvent> = running_events(events, now)
+        .into_iter()
+        .filter(|event| state.calendar.matches(event))
+        .collect();
+
+    let holds = match state.get_system_state() {
+        Ok(system_state) => system_state.holds,
+        Err(e) => {
+            error!("Failed to get system state: {}", e);
+            return;
+        }
+    };
+
+    for event in &active {
+        let name = event.hold_name();
+        if holds.get(&name).is_some_and(|hold| hold.until == Some(event.end) && hold.reason == event.summary) {
+            continue;
+        }
+        let hold = Hold {
+            owner: "calendar".to_string(),
+            reason: event.summary.clone(),
+            created_at: now,
+            until: Some(event.end),
+            ttl_seconds: None,
+            last_heartbeat: None,
+        };
+        if let Err(e) = state.set_hold(&name, hold) {
+            error!("Failed to hold the machine awake for {}: {}", event.summary, e);
+        }
+    }
+
+    let active_names: HashSet<String> = active.iter().map(|event| event.hold_name()).collect();
+    for name in holds.keys().filter(|name| name.starts_with("calendar:") && !active_names.contains(*name)) {
+        if let Err(e) = state.release_hold(name) {
+            error!("Failed to release hold {}: {}", name, e);
+        }
+    }
+
+    // Start the service once per event occurrence
+    started.retain(|occurrence| active_names.contains(occurrence));
+    let Some(service_name) = &state.calendar.start_service else {
+        return;
+    };
+    for occurrence in active_names {
+        if !started.insert(occurrence) {
+            continue;
+        }
+        info!("Calendar event began, starting {}", service_name);
+        run_service_action(state, service_name, ScheduledServiceAction::Start, "a calendar event").await;
+    }
+}
@@ -0,0 +1,59 @@
+//! Hold expiry background task
+
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::broadcast::error::RecvError, time::sleep};
+use tracing::{error, info};
+
+use crate::state::AppState;
+
+/// Longest the task sleeps before comparing the holds with the wall clock
+/// again; tokio's timer stands still while the machine is suspended
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Background task that releases holds and coffee leases once their time is up.
+///
+/// It sleeps until the earliest `until` of the current holds (or the end of the
+/// coffee lease), at most `RECHECK_INTERVAL` at a time, and looks again after
+/// every state change, since holds may have been added or released, and after
+/// every wake-up, since holds may have run out while the machine slept.
+pub async fn hold_expiry_task(state: Arc<AppState>) {
+    info!("Starting hold expiry task");
+
+    let mut state_rx = state.state_change_tx.subscribe();
+    let mut resume_rx = state.resume_tx.subscribe();
+
+    loop {
+        let next_expiry = match state.get_system_state() {
+            Ok(current_state) => current_state.next_hold_expiry(),
+            Err(e) => {
+                error!("Failed to get system state: {}", e);
+                None
+            }
+        };
+        let wait = next_expiry
+            .map(|until| (until - state.clock.utc()).to_std().unwrap_or(Duration::ZERO).min(RECHECK_INTERVAL));
+
+        tokio::select! {
+            _ = sleep(wait.unwrap_or(Duration::ZERO)), if wait.is_some() => {
+                if let Err(e) = state.expire_holds() {
+                    error!("Failed to release expired holds: {}", e);
+                }
+            }
+
+            result = state_rx.recv() => {
+                if let Err(RecvError::Closed) = result {
+                    return;
+                }
+            }
+
+            result = resume_rx.recv() => {
+                if let Err(RecvError::Closed) = result {
+                    return;
+                }
+                if let Err(e) = state.expire_holds() {
+                    error!("Failed to release expired holds: {}", e);
+                }
+            }
+        }
+    }
+}
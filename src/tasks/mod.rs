@@ -4,7 +4,15 @@
 
 pub mod suspension_timer;
 pub mod wake_up_recovery;
+pub mod service_watchdog;
+pub mod hold_expiry;
+pub mod scheduler;
+pub mod calendar_watch;
 
 // Re-export main functions
 pub use suspension_timer::suspension_timer_task;
 pub use wake_up_recovery::wake_up_recovery_task;
+pub use service_watchdog::service_watchdog_task;
+pub use hold_expiry::hold_expiry_task;
+pub use scheduler::schedule_task;
+pub use calendar_watch::calendar_task;
@@ -0,0 +1,104 @@
+//! Time-of-day schedule background task
+
+use std::{sync::Arc, time::Duration};
+use tokio::time::interval;
+use tracing::{debug, error, info};
+
+use crate::{
+    services::run_scheduled_service_action,
+    state::{AppState, Hold, TimerCommand},
+};
+
+/// How often the schedules are evaluated
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Service entries further in the past than this (e.g. while the machine was
+/// asleep) are skipped rather than run late
+const MISSED_ACTION_LIMIT: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Background task applying the `[schedule]` config: work-hours holds, the
+/// quiet-hours timer and timed service starts/stops
+pub async fn schedule_task(state: Arc<AppState>) {
+    if !state.schedule.is_enabled() {
+        return;
+    }
+    info!("Starting schedule task");
+
+    let mut interval = interval(SCHEDULE_CHECK_INTERVAL);
+    let mut last_check = state.clock.utc();
+
+    loop {
+        interval.tick().await;
+        let now = state.clock.utc();
+
+        apply_work_hours(&state, now);
+        apply_quiet_hours(&state, now).await;
+
+        let after = last_check.max(now - MISSED_ACTION_LIMIT);
+        for entry in state.schedule.services.iter().filter(|entry| entry.due_between(after, now)) {
+            run_scheduled_service_action(&state, entry).await;
+        }
+        last_check = now;
+    }
+}
+
+/// Hold the machine awake until the end of every open work-hours window
+fn apply_work_hours(state: &AppState, now: chrono::DateTime<chrono::Utc>) {
+    let holds = match state.get_system_state() {
+        Ok(system_state) => system_state.holds,
+        Err(e) => {
+            error!("Failed to get system state: {}", e);
+            return;
+        }
+    };
+
+    for window in &state.schedule.work_hours {
+        let Some(until) = window.active_until(now) else {
+            continue;
+        };
+        let name = window.hold_name();
+        if holds.get(&name).is_some_and(|hold| hold.until == Some(until)) {
+            continue;
+        }
+
+        // Released by the hold expiry task when the window closes
+        let hold = Hold {
+            owner: "schedule".to_string(),
+            reason: format!("work hours {}", window.name),
+            created_at: now,
+            until: Some(until),
+            ttl_seconds: None,
+            last_heartbeat: None,
+        };
+        if let Err(e) = state.set_hold(&name, hold) {
+            error!("Failed to hold the machine awake for {}: {}", window.name, e);
+        }
+    }
+}
+
+/// Switch the idle timer when quiet hours begin or end
+async fn apply_quiet_hours(state: &AppState, now: chrono::DateTime<chrono::Utc>) {
+    let quiet_timer = state.schedule.quiet_timer(now);
+    if quiet_timer == state.get_quiet_timer() {
+        return;
+    }
+
+    let previous = match state.set_quiet_timer(quiet_timer) {
+        Ok(previous) => previous,
+        Err(e) => {
+            error!("Failed to set the quiet hours timer: {}", e);
+            return;
+        }
+    };
+    match quiet_timer {
+        Some(timer) => info!("Quiet hours: idle timer {}s", timer.as_secs()),
+        None => info!("Quiet hours over"),
+    }
+
+    if previous != state.effective_timer_duration() {
+        match state.send_timer_command(TimerCommand::DurationChanged { previous }).await {
+            Ok(message) => info!("{}", message),
+            Err(e) => debug!("Running countdown not re-computed: {}", e),
+        }
+    }
+}
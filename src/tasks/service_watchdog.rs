@@ -0,0 +1,130 @@
+//! Service watchdog background task
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::{interval, Instant};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    services::{recover_systemd_service, ServiceConfig},
+    state::AppState,
+};
+
+/// How long a restarted service must stay up before its restart budget is refilled
+const STABLE_RESET_AFTER: Duration = Duration::from_secs(600);
+
+/// Restart bookkeeping for a single service
+#[derive(Debug)]
+struct RestartRecord {
+    attempts: u32,
+    last_attempt: Instant,
+}
+
+/// Background task that restarts services which died while marked active.
+///
+/// A service whose state is `true` but whose unit is no longer active is recovered
+/// with the regular escalating recovery path. After `max_restarts` failed attempts
+/// the service state is set to `false` and an error is recorded, which lets the
+/// suspension timer run instead of keeping the machine awake for a dead process.
+pub async fn service_watchdog_task(state: Arc<AppState>, check_interval: Duration, max_restarts: u32) {
+    info!("Starting service watchdog task (interval={}s, max_restarts={})",
+          check_interval.as_secs(), max_restarts);
+
+    let mut interval = interval(check_interval);
+    let mut restarts: HashMap<String, RestartRecord> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let current_state = match state.get_system_state() {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Watchdog failed to read system state: {}", e);
+                continue;
+            }
+        };
+
+        // Forget services that are no longer desired on
+        restarts.retain(|name, _| current_state.get_service(name));
+
+        let desired_on: Vec<String> = current_state.services
+            .iter()
+            .filter(|(_, &active)| active)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for service_name in desired_on {
+            let config = match ServiceConfig::from_name(&service_name) {
+                Some(config) => config,
+                None => continue,
+            };
+
+            match state.backend.is_service_active(&config.service_name).await {
+                Ok(true) => {
+                    // Refill the restart budget once the service has been stable for a while
+                    if restarts.get(&service_name)
+                        .map(|record| state.clock.now().saturating_duration_since(record.last_attempt) >= STABLE_RESET_AFTER)
+                        .unwrap_or(false)
+                    {
+                        debug!("{} has been stable, resetting watchdog restart count", service_name);
+                        restarts.remove(&service_name);
+                    }
+                    continue;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("Watchdog failed to check {} status: {}", service_name, e);
+                    continue;
+                }
+            }
+
+            // The state may have been turned off while we were checking (e.g. /service/x/stop)
+            if !state.get_system_state().map(|s| s.get_service(&service_name)).unwrap_or(false) {
+                continue;
+            }
+
+            let record = restarts.entry(service_name.clone()).or_insert(RestartRecord {
+                attempts: 0,
+                last_attempt: state.clock.now(),
+            });
+
+            // Services without recovery are marked failed straight away
+            let limit = if config.recovery_enabled { max_restarts } else { 0 };
+
+            if record.attempts >= limit {
+                error!("{} died and could not be restarted after {} attempts, marking it failed",
+                       service_name, record.attempts);
+
+                let attempts = record.attempts;
+                restarts.remove(&service_name);
+
+                if let Err(e) = state.update_state(
+                    &format!("{}-watchdog-failed", service_name),
+                    |s| s.set_service(&service_name, false),
+                ) {
+                    error!("Failed to set {} state to false: {}", service_name, e);
+                }
+                if let Err(e) = state.add_error(format!(
+                    "{} service stopped unexpectedly and {} restart attempts failed",
+                    service_name, attempts
+                )) {
+                    error!("Failed to add error to state: {}", e);
+                }
+                continue;
+            }
+
+            record.attempts += 1;
+            record.last_attempt = state.clock.now();
+            warn!("{} is marked active but its unit is not running, restarting (attempt {}/{})",
+                  service_name, record.attempts, limit);
+
+            match recover_systemd_service(&state.backend, &config).await {
+                Ok(()) => info!("Watchdog restarted {} successfully", service_name),
+                Err(e) => warn!("Watchdog restart of {} failed: {}", service_name, e),
+            }
+        }
+    }
+}
@@ -1,136 +1,769 @@
 //! Suspension timer background task
 
-use std::{sync::Arc, time::{Duration, Instant}};
-use tokio::time::sleep;
-use tracing::{debug, error, info};
+use std::{pin::Pin, sync::Arc, time::Duration};
+use chrono::{DateTime, Utc};
+use futures::future::OptionFuture;
+use tokio::{
+    sync::broadcast::Receiver,
+    task::{JoinError, JoinHandle},
+    time::{sleep, sleep_until, Instant, Sleep},
+};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    services::execute_system_suspend,
-    state::AppState,
+    services::{
+        execute_idle_action, run_pre_suspend_hooks, stop_idle_services, switch_idle_governor, GovernorSetting,
+        HookStage, IdleAction, IdleStageStatus, Inhibitor, InhibitorAction,
+    },
+    state::{AppState, BlipPolicy, HistoryEvent, SuspendPhase, SystemState, TimerCommand, MAX_TIMER_SECONDS},
 };
 
-/// Background task that manages the suspension timer based on system state changes
+/// How long to wait for the wake-up after a sleeping idle action before
+/// concluding the machine never went to sleep. Monotonic time stands still
+/// while asleep, so this only has to cover the time it takes to fall asleep
+/// and to notice the wake-up.
+const SUSPEND_VERIFY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Countdown before the first retry of a failed suspension; doubled for every
+/// further failure, up to the full timer duration
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+
+/// Background task that manages the suspension timer based on system state changes.
+///
+/// The task owns the suspension state machine: it moves between `Idle`, `Held`
+/// and `CountingDown` as states change (debounced and with brief activity
+/// pausing the countdown, if configured), runs the idle stages and then the
+/// idle action when the deadline is reached (unless a respected inhibitor of
+/// another application holds it off; checking that the machine really slept
+/// and retrying with backoff otherwise), handles wake-ups (`Resuming`,
+/// then `Grace` if configured) and executes the timer commands sent by the API. It sleeps until the deadline
+/// instead of ticking; clients derive the remaining time from the deadline
+/// stored in the timer state.
 pub async fn suspension_timer_task(state: Arc<AppState>) {
     info!("Starting suspension timer task");
-    
+
     let mut state_rx = state.state_change_tx.subscribe();
-    
+    let mut resume_rx = state.resume_tx.subscribe();
+    let mut commands = match state.take_timer_commands() {
+        Some(commands) => commands,
+        None => {
+            error!("Suspension timer task is already running");
+            return;
+        }
+    };
+
+    let mut timer = Countdown::new(Arc::clone(&state));
+
     loop {
-        // Wait for a state change notification
-        match state_rx.recv().await {
-            Ok(current_state) => {
-                let active_services: Vec<String> = current_state.services
-                    .iter()
-                    .filter(|(_, &active)| active)
-                    .map(|(name, _)| name.clone())
-                    .collect();
-                
-                debug!("Timer task received state change: coffee={}, active_services={:?}", 
-                       current_state.coffee, active_services);
-                
-                if current_state.any_active() {
-                    let active_items: Vec<String> = {
-                        let mut items = Vec::new();
-                        if current_state.coffee {
-                            items.push("coffee".to_string());
-                        }
-                        for (service, &active) in &current_state.services {
-                            if active {
-                                items.push(service.clone());
-                            }
-                        }
-                        items
-                    };
-                    debug!("Active states preventing suspension: {:?}", active_items);
-                } else {
-                    debug!("All states inactive, suspension timer eligible");
+        tokio::select! {
+            // Deadline reached - trigger suspension
+            _ = &mut timer.expiry, if timer.armed => {
+                timer.expire(&mut state_rx).await;
+            }
+
+            // Everything stayed inactive for the debounce time
+            _ = &mut timer.debounce_expiry, if timer.debouncing => {
+                timer.debounce_elapsed();
+            }
+
+            // Post-resume grace period is over
+            _ = &mut timer.grace_expiry, if timer.in_grace => {
+                info!("Post-resume grace period over");
+                timer.end_grace();
+            }
+
+            // No wake-up after the idle action - did the machine sleep at all?
+            _ = &mut timer.verify_expiry, if timer.verifying.is_some() => {
+                timer.verify_sleep();
+            }
+
+            // Next idle stage is due
+            _ = &mut timer.stage_expiry, if timer.stage_pending => {
+                timer.start_next_stage();
+            }
+
+            // The services of the running idle stage are stopped
+            Some(result) = OptionFuture::from(timer.stage_task.as_mut()), if timer.stage_task.is_some() => {
+                timer.finish_stage(result);
+            }
+
+            // State change - start, keep or cancel the countdown
+            result = state_rx.recv() => match result {
+                Ok(current_state) => timer.handle_state_change(&current_state),
+                Err(e) => {
+                    error!("Error receiving state change: {}", e);
+                    // Wait a bit before retrying
+                    sleep(Duration::from_secs(1)).await;
                 }
-                
-                if current_state.all_inactive() {
-                    // All states are inactive, start suspension timer
-                    info!("All states inactive, starting suspension timer for {} minutes", 
-                          state.timer_duration_minutes);
-                    
-                    // Update timer state to active
-                    if let Err(e) = state.update_timer_state(true, Some(state.timer_duration_minutes * 60)) {
-                        error!("Failed to update timer state: {}", e);
-                        continue;
-                    }
-                    
-                    // Start countdown
-                    let timer_duration = Duration::from_secs(state.timer_duration_minutes * 60);
-                    let start_time = Instant::now();
-                    
-                    // Create a timer that can be cancelled
-                    let mut interval = tokio::time::interval(Duration::from_secs(1));
-                    let mut cancelled = false;
-                    
-                    loop {
-                        tokio::select! {
-                            // Timer tick - update remaining time
-                            _ = interval.tick() => {
-                                let elapsed = start_time.elapsed();
-                                if elapsed >= timer_duration {
-                                    // Timer expired, trigger suspension
-                                    info!("Suspension timer expired, triggering system suspension");
-                                    
-                                    // Update timer state to inactive
-                                    if let Err(e) = state.update_timer_state(false, None) {
-                                        error!("Failed to update timer state: {}", e);
-                                    }
-                                    
-                                    // Execute system suspension
-                                    if let Err(e) = execute_system_suspend(Arc::clone(&state)).await {
-                                        error!("Failed to suspend system: {}", e);
-                                        if let Err(e) = state.add_error(format!("System suspension failed: {}", e)) {
-                                            error!("Failed to add suspension error: {}", e);
-                                        }
-                                    }
-                                    
-                                    break;
-                                } else {
-                                    // Update remaining time
-                                    let remaining = timer_duration - elapsed;
-                                    if let Err(e) = state.update_timer_state(true, Some(remaining.as_secs())) {
-                                        error!("Failed to update timer remaining time: {}", e);
-                                    }
-                                }
-                            }
-                            
-                            // State change - check if we should cancel timer
-                            Ok(new_state) = state_rx.recv() => {
-                                if new_state.any_active() {
-                                    // Some state became active, cancel timer
-                                    info!("State became active, cancelling suspension timer");
-                                    cancelled = true;
-                                    
-                                    // Update timer state to inactive
-                                    if let Err(e) = state.update_timer_state(false, None) {
-                                        error!("Failed to update timer state: {}", e);
-                                    }
-                                    
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    
-                    if cancelled {
-                        debug!("Timer was cancelled, continuing to wait for next state change");
-                    }
+            },
+
+            // Wake-up - the machine slept (our suspend, lid close, ...), start over
+            Ok(event) = resume_rx.recv() => {
+                info!("System resumed after {}s", event.slept_seconds);
+                if timer.verifying.take().is_some() {
+                    timer.sleep_verified();
+                }
+                enter_phase(&state, SuspendPhase::Resuming);
+                timer.paused = None;
+                timer.disarm();
+                timer.start_grace();
+            }
+
+            // Request from the API (extend, snooze, ...)
+            Some(request) = commands.recv() => {
+                let outcome = timer.handle_command(request.command);
+                // The requester may have gone away, that's fine
+                let _ = request.reply.send(outcome);
+            }
+        }
+    }
+}
+
+/// Move to `phase`, logging transitions the state machine rejects
+fn enter_phase(state: &AppState, phase: SuspendPhase) {
+    if let Err(e) = state.transition(phase) {
+        warn!("{}", e);
+    }
+}
+
+/// A countdown interrupted by activity under `BlipPolicy::Pause`
+#[derive(Debug, Clone, Copy)]
+struct Paused {
+    at: Instant,
+    remaining: Duration,
+}
+
+/// A sleeping idle action waiting for its wake-up
+#[derive(Debug, Clone, Copy)]
+struct Verification {
+    action: IdleAction,
+    /// Successful suspends reported by the kernel before the action
+    successes_before: Option<u64>,
+}
+
+/// The countdown owned by the timer task
+struct Countdown {
+    state: Arc<AppState>,
+    /// Sleeps until the deadline while `armed`
+    expiry: Pin<Box<Sleep>>,
+    armed: bool,
+    /// Deadline of the running countdown
+    deadline: Instant,
+    /// When the running countdown started (moved forward by paused blips)
+    started: Instant,
+    /// Whether the countdown runs the timer duration (plus extensions), so a
+    /// changed duration applies; not for snoozes, retries or deferrals
+    follows_timer: bool,
+    /// Idle action requested through POST /suspend; such a countdown is not
+    /// cancelled by active states
+    forced: Option<IdleAction>,
+    /// Sleeps until the countdown may start while `debouncing`
+    debounce_expiry: Pin<Box<Sleep>>,
+    debouncing: bool,
+    /// Countdown to continue if the current activity is only a blip
+    paused: Option<Paused>,
+    /// Sleeps until the end of the post-resume grace period while `in_grace`
+    grace_expiry: Pin<Box<Sleep>>,
+    in_grace: bool,
+    /// Sleeps until the sleep is considered failed while `verifying`
+    verify_expiry: Pin<Box<Sleep>>,
+    verifying: Option<Verification>,
+    /// Consecutive failed suspensions, for the retry backoff
+    failures: u32,
+    /// Sleeps until the next idle stage while `stage_pending`
+    stage_expiry: Pin<Box<Sleep>>,
+    stage_pending: bool,
+    /// Start of the idle period the stages are timed from
+    idle_since: Instant,
+    idle_since_utc: DateTime<Utc>,
+    /// Index of the next idle stage to run
+    next_stage: usize,
+    /// Stops the services of the running idle stage, off the task so activity
+    /// can cancel it; returns what went wrong
+    stage_task: Option<JoinHandle<Vec<String>>>,
+    /// CPU governors to restore once the idle period ends
+    governors: Vec<GovernorSetting>,
+}
+
+impl Countdown {
+    fn new(state: Arc<AppState>) -> Self {
+        let now = state.clock.now();
+        let now_utc = state.clock.utc();
+        Self {
+            state,
+            expiry: Box::pin(sleep_until(now)),
+            armed: false,
+            deadline: now,
+            started: now,
+            follows_timer: false,
+            forced: None,
+            debounce_expiry: Box::pin(sleep_until(now)),
+            debouncing: false,
+            paused: None,
+            grace_expiry: Box::pin(sleep_until(now)),
+            in_grace: false,
+            verify_expiry: Box::pin(sleep_until(now)),
+            verifying: None,
+            failures: 0,
+            stage_expiry: Box::pin(sleep_until(now)),
+            stage_pending: false,
+            idle_since: now,
+            idle_since_utc: now_utc,
+            next_stage: 0,
+            stage_task: None,
+            governors: Vec::new(),
+        }
+    }
+
+    /// The timer duration in effect (shorter during quiet hours)
+    fn full_duration(&self) -> Duration {
+        self.state.effective_timer_duration()
+    }
+
+    /// (Re)arm the countdown to expire `duration` (at most the longest timer) from now
+    fn arm(&mut self, duration: Duration) {
+        let duration = duration.min(Duration::from_secs(MAX_TIMER_SECONDS));
+        self.deadline = match self.state.arm_timer(duration) {
+            Ok(deadline) => deadline,
+            Err(e) => {
+                error!("Failed to update timer state: {}", e);
+                self.state.clock.now() + duration
+            }
+        };
+        self.armed = true;
+        self.expiry.as_mut().reset(self.deadline);
+    }
+
+    /// Start a full countdown and time the idle stages from now
+    fn start(&mut self) {
+        let duration = self.full_duration();
+        self.start_with(duration);
+        self.follows_timer = true;
+    }
+
+    /// Start a countdown of `duration` and time the idle stages from now
+    fn start_with(&mut self, duration: Duration) {
+        self.debouncing = false;
+        self.paused = None;
+        self.arm(duration);
+        self.started = self.state.clock.now();
+        self.follows_timer = false;
+
+        self.idle_since = self.state.clock.now();
+        self.idle_since_utc = self.state.clock.utc();
+        self.cancel_stage();
+        self.next_stage = 0;
+        self.schedule_stage();
+    }
+
+    /// Continue a paused countdown; the blip doesn't count as idle time
+    fn resume(&mut self, paused: Paused) {
+        let blip = self.state.clock.now().saturating_duration_since(paused.at);
+        self.arm(paused.remaining);
+        self.started += blip;
+
+        self.idle_since += blip;
+        self.idle_since_utc += chrono::Duration::from_std(blip).unwrap_or_else(|_| chrono::Duration::zero());
+        self.cancel_stage();
+        self.next_stage = 0;
+        self.schedule_stage();
+    }
+
+    /// Hold the timer for the post-resume grace period, if any
+    fn start_grace(&mut self) {
+        let grace = self.state.resume_grace;
+        if grace.is_zero() {
+            self.settle();
+            return;
+        }
+
+        let deadline = match self.state.start_grace(grace) {
+            Ok(deadline) => deadline,
+            Err(e) => {
+                error!("Skipping the post-resume grace period: {}", e);
+                self.settle();
+                return;
+            }
+        };
+        info!("Holding the suspension timer for a {}s post-resume grace period", grace.as_secs());
+        enter_phase(&self.state, SuspendPhase::Grace);
+        self.grace_expiry.as_mut().reset(deadline);
+        self.in_grace = true;
+    }
+
+    /// Stop holding the timer after a wake-up and look at the states again
+    fn end_grace(&mut self) {
+        self.leave_grace();
+        self.settle();
+    }
+
+    /// Forget the grace period without re-evaluating the states
+    fn leave_grace(&mut self) {
+        if !self.in_grace {
+            return;
+        }
+        self.in_grace = false;
+        if let Err(e) = self.state.end_grace() {
+            error!("Failed to update timer state: {}", e);
+        }
+    }
+
+    /// The debounce time passed without activity, start the countdown
+    fn debounce_elapsed(&mut self) {
+        info!("States stayed inactive for {}s, starting suspension timer for {}s",
+              self.state.hysteresis.debounce.as_secs(), self.full_duration().as_secs());
+        enter_phase(&self.state, SuspendPhase::CountingDown);
+        self.start();
+    }
+
+    /// Stop the countdown (or the debounce), cancel the pending idle stages
+    /// and revert the governors
+    fn disarm(&mut self) {
+        self.armed = false;
+        self.debouncing = false;
+        self.forced = None;
+        self.stage_pending = false;
+        self.cancel_stage();
+        self.next_stage = 0;
+
+        if !self.governors.is_empty() {
+            let governors = std::mem::take(&mut self.governors);
+            match self.state.backend.restore_cpu_governors(&self.state.sysfs_root, &governors) {
+                Ok(()) => info!("Restored {} CPU governor(s) after the idle stages", governors.len()),
+                Err(e) => warn!("Failed to restore CPU governors: {}", e),
+            }
+        }
+
+        if let Err(e) = self.state.disarm_timer() {
+            error!("Failed to update timer state: {}", e);
+        }
+    }
+
+    /// Publish the stage progress and wait for the next stage, if any
+    fn schedule_stage(&mut self) {
+        let stages = &self.state.idle_policy.stages;
+        let statuses: Vec<IdleStageStatus> = stages
+            .iter()
+            .enumerate()
+            .map(|(index, stage)| IdleStageStatus {
+                name: stage.name.clone(),
+                at: i64::try_from(stage.after_minutes)
+                    .ok()
+                    .and_then(chrono::Duration::try_minutes)
+                    .and_then(|after| self.idle_since_utc.checked_add_signed(after))
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC),
+                done: index < self.next_stage,
+            })
+            .collect();
+
+        match stages.get(self.next_stage).and_then(|stage| self.idle_since.checked_add(stage.after())) {
+            Some(at) => {
+                self.stage_expiry.as_mut().reset(at);
+                self.stage_pending = true;
+            }
+            None => self.stage_pending = false,
+        }
+
+        if let Err(e) = self.state.set_idle_stages(statuses) {
+            error!("Failed to update timer state: {}", e);
+        }
+    }
+
+    /// Start stopping the services of the idle stage that is due
+    fn start_next_stage(&mut self) {
+        self.stage_pending = false;
+        let Some(stage) = self.state.idle_policy.stages.get(self.next_stage).cloned() else {
+            return;
+        };
+        let current_state = match self.state.get_system_state() {
+            Ok(current_state) => current_state,
+            Err(e) => {
+                error!("Failed to get system state: {}", e);
+                self.next_stage += 1;
+                self.schedule_stage();
+                return;
+            }
+        };
+
+        let state = Arc::clone(&self.state);
+        self.stage_task = Some(tokio::spawn(async move {
+            stop_idle_services(&state.backend, &stage, &current_state).await
+        }));
+    }
+
+    /// Switch the governor of the stage whose services are stopped and schedule
+    /// the next one
+    fn finish_stage(&mut self, result: Result<Vec<String>, JoinError>) {
+        self.stage_task = None;
+        let Some(stage) = self.state.idle_policy.stages.get(self.next_stage).cloned() else {
+            return;
+        };
+
+        let mut errors = result.unwrap_or_else(|e| vec![format!("failed to stop services: {}", e)]);
+        match switch_idle_governor(&self.state.backend, &self.state.sysfs_root, &stage) {
+            Ok(governors) => self.keep_governors(governors),
+            Err(e) => errors.push(e),
+        }
+        if !errors.is_empty() {
+            let error = format!("Idle stage {}: {}", stage.name, errors.join("; "));
+            warn!("{}", error);
+            if let Err(e) = self.state.add_error(error) {
+                error!("Failed to add idle stage error: {}", e);
+            }
+        }
+
+        self.next_stage += 1;
+        self.schedule_stage();
+    }
+
+    /// Stop the idle stage whose services are being stopped, if any
+    fn cancel_stage(&mut self) {
+        if let Some(task) = self.stage_task.take() {
+            info!("Cancelling the running idle stage");
+            task.abort();
+        }
+    }
+
+    /// Remember the governors to restore; a CPU switched by several stages
+    /// goes back to the governor it had before the first one
+    fn keep_governors(&mut self, governors: Vec<GovernorSetting>) {
+        for (cpu, governor) in governors {
+            if !self.governors.iter().any(|(saved, _)| *saved == cpu) {
+                self.governors.push((cpu, governor));
+            }
+        }
+    }
+
+    /// Start the countdown when everything is inactive, hold otherwise
+    fn handle_state_change(&mut self, current_state: &SystemState) {
+        let active_services: Vec<String> = current_state.services
+            .iter()
+            .filter(|(_, &active)| active)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        debug!("Timer task received state change: coffee={}, active_services={:?}",
+               current_state.coffee, active_services);
+
+        if self.forced.is_some() {
+            debug!("Requested suspension pending, ignoring state change");
+            return;
+        }
+        if self.in_grace {
+            debug!("Post-resume grace period, ignoring state change");
+            return;
+        }
+        if self.verifying.is_some() && !current_state.any_active() {
+            debug!("Waiting for the machine to sleep, ignoring state change");
+            return;
+        }
+
+        let hysteresis = self.state.hysteresis;
+        let now = self.state.clock.now();
+
+        if current_state.any_active() {
+            debug!("Active states preventing suspension: {:?}", current_state.active_holds());
+            if self.armed && hysteresis.blip_policy == BlipPolicy::Pause {
+                // Keep the remaining time in case the activity is only a blip
+                let remaining = self.deadline.saturating_duration_since(now);
+                info!("State became active, pausing suspension timer with {}s left", remaining.as_secs());
+                self.paused = Some(Paused { at: now, remaining });
+            } else if self.armed {
+                // Some state became active, cancel timer
+                info!("State became active, cancelling suspension timer");
+            }
+            enter_phase(&self.state, SuspendPhase::Held);
+            self.disarm();
+        } else if !self.armed && !self.debouncing {
+            if let Some(paused) = self.paused.take() {
+                if now.saturating_duration_since(paused.at) <= hysteresis.blip_window {
+                    info!("States inactive again, resuming suspension timer with {}s left",
+                          paused.remaining.as_secs());
+                    enter_phase(&self.state, SuspendPhase::CountingDown);
+                    self.resume(paused);
+                    return;
+                }
+            }
+
+            if hysteresis.debounce.is_zero() {
+                // All states are inactive, start suspension timer
+                info!("All states inactive, starting suspension timer for {}s",
+                      self.full_duration().as_secs());
+                enter_phase(&self.state, SuspendPhase::CountingDown);
+                self.start();
+            } else {
+                info!("All states inactive, starting suspension timer in {}s unless activity resumes",
+                      hysteresis.debounce.as_secs());
+                enter_phase(&self.state, SuspendPhase::Idle);
+                self.debounce_expiry.as_mut().reset(now + hysteresis.debounce);
+                self.debouncing = true;
+            }
+        }
+    }
+
+    /// Re-evaluate the current states, e.g. after a wake-up or a vetoed suspension
+    fn settle(&mut self) {
+        match self.state.get_system_state() {
+            Ok(current_state) => self.handle_state_change(&current_state),
+            Err(e) => error!("Failed to get system state: {}", e),
+        }
+    }
+
+    /// Run the idle action (pre-suspend hooks first)
+    async fn expire(&mut self, state_rx: &mut Receiver<SystemState>) {
+        info!("Suspension timer expired, triggering system suspension");
+        let blocking = self.blocking_inhibitors().await;
+        if !blocking.is_empty() {
+            self.inhibited(blocking);
+            return;
+        }
+
+        let action = self.forced.unwrap_or(self.state.idle_action);
+        self.disarm();
+        let successes_before = self.state.backend.suspend_successes(&self.state.sysfs_root);
+
+        if self.state.hooks.has_hooks(HookStage::PreSuspend) {
+            enter_phase(&self.state, SuspendPhase::PreSuspendHooks);
+            if let Some(reason) = run_pre_suspend_hooks(&self.state).await {
+                // A hook vetoed the suspension, re-arm the countdown
+                info!("Suspension vetoed ({}), restarting suspension timer", reason);
+                self.settle();
+                return;
+            }
+        }
+
+        enter_phase(&self.state, SuspendPhase::Suspending);
+        match execute_idle_action(&self.state, action).await {
+            Ok(()) => {
+                // Changes made while suspending (e.g. hooks stopping services)
+                // must not restart the countdown
+                while state_rx.try_recv().is_ok() {}
+
+                if action.sleeps() {
+                    enter_phase(&self.state, SuspendPhase::Suspended);
+                    self.verifying = Some(Verification { action, successes_before });
+                    let timeout = self.state.clock.now() + SUSPEND_VERIFY_TIMEOUT;
+                    self.verify_expiry.as_mut().reset(timeout);
                 } else {
-                    // Some states are active, ensure timer is inactive
-                    debug!("Some states are active, ensuring timer is inactive");
-                    if let Err(e) = state.update_timer_state(false, None) {
-                        error!("Failed to update timer state: {}", e);
-                    }
+                    // Nothing to wake up from (a custom command, ...), carry on
+                    self.settle();
                 }
             }
             Err(e) => {
-                error!("Error receiving state change: {}", e);
-                // Wait a bit before retrying
-                sleep(Duration::from_secs(1)).await;
+                error!("Failed to suspend system: {}", e);
+                self.suspension_failed(action, e);
+            }
+        }
+    }
+
+    /// Inhibitors of other applications that hold off the suspension
+    async fn blocking_inhibitors(&self) -> Vec<Inhibitor> {
+        let policy = &self.state.inhibitor_policy;
+        if !policy.is_enabled() {
+            return Vec::new();
+        }
+
+        match self.state.backend.list_inhibitors().await {
+            Ok(inhibitors) => policy.blocking(&inhibitors),
+            Err(e) => {
+                warn!("{}, suspending anyway", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Defer or skip the suspension because of `blocking` inhibitors
+    fn inhibited(&mut self, blocking: Vec<Inhibitor>) {
+        let names: Vec<String> = blocking.iter().map(Inhibitor::describe).collect();
+        let policy = &self.state.inhibitor_policy;
+
+        match policy.action {
+            InhibitorAction::Defer => {
+                let recheck = Duration::from_secs(policy.recheck_seconds);
+                info!("Suspension deferred by {}, checking again in {}s", names.join(", "), recheck.as_secs());
+                // The idle stages and a requested action stay as they are
+                self.arm(recheck);
+                self.follows_timer = false;
+            }
+            InhibitorAction::Skip => {
+                info!("Suspension skipped because of {}, restarting suspension timer", names.join(", "));
+                self.disarm();
+                self.start();
+            }
+        }
+
+        if let Err(e) = self.state.set_blocking_inhibitors(blocking) {
+            error!("Failed to update timer state: {}", e);
+        }
+    }
+
+    /// The wake-up after our idle action arrived
+    fn sleep_verified(&mut self) {
+        if self.failures > 0 {
+            info!("Suspension succeeded after {} failed attempt(s)", self.failures);
+            let _ = self.state.clear_errors_for("System suspension failed");
+        }
+        self.failures = 0;
+    }
+
+    /// No wake-up was noticed in time; ask the kernel whether we slept
+    fn verify_sleep(&mut self) {
+        let Some(verification) = self.verifying.take() else {
+            return;
+        };
+
+        let successes = self.state.backend.suspend_successes(&self.state.sysfs_root);
+        match (verification.successes_before, successes) {
+            (Some(before), Some(after)) if after > before => {
+                // The wake-up went unnoticed, handle it like one
+                info!("suspend_stats reports a completed suspend, treating it as a wake-up");
+                self.sleep_verified();
+                enter_phase(&self.state, SuspendPhase::Resuming);
+                self.disarm();
+                self.start_grace();
+            }
+            _ => {
+                let reason = format!(
+                    "the machine was still awake {}s after {}",
+                    SUSPEND_VERIFY_TIMEOUT.as_secs(), verification.action
+                );
+                warn!("Suspension did not happen: {}", reason);
+                self.suspension_failed(verification.action, reason);
+            }
+        }
+    }
+
+    /// Record a failed suspension and count down to the next attempt
+    fn suspension_failed(&mut self, action: IdleAction, reason: String) {
+        self.failures += 1;
+        let backoff = RETRY_BASE_DELAY.saturating_mul(1 << (self.failures - 1).min(16));
+        let delay = backoff.min(self.full_duration());
+
+        let message = format!(
+            "System suspension failed (attempt {}): {}; retrying in {}s",
+            self.failures, reason, delay.as_secs()
+        );
+        // Keep only the latest failure
+        let _ = self.state.clear_errors_for("System suspension failed");
+        if let Err(e) = self.state.add_error(message) {
+            error!("Failed to add suspension error: {}", e);
+        }
+        self.state.record_history(HistoryEvent::SuspendFailed {
+            action,
+            attempt: self.failures,
+            reason,
+            retry_in_seconds: delay.as_secs(),
+        });
+
+        match self.state.get_system_state() {
+            Ok(current_state) if current_state.any_active() => {
+                info!("Not retrying the suspension while states are active");
+                enter_phase(&self.state, SuspendPhase::Held);
             }
+            Ok(_) => {
+                info!("Retrying the suspension in {}s", delay.as_secs());
+                enter_phase(&self.state, SuspendPhase::CountingDown);
+                self.start_with(delay);
+            }
+            Err(e) => error!("Failed to get system state: {}", e),
+        }
+    }
+
+    /// Execute a command from the API
+    fn handle_command(&mut self, command: TimerCommand) -> Result<String, String> {
+        debug!("Timer command: {:?}", command);
+        let phase = self.state.get_phase()?.phase;
+        if matches!(phase, SuspendPhase::PreSuspendHooks | SuspendPhase::Suspending | SuspendPhase::Suspended) {
+            return Err(format!("Suspension already in progress ({})", phase));
         }
+
+        let not_running = || Err("No countdown is running".to_string());
+
+        let message = match command {
+            TimerCommand::SuspendNow { action, delay } => {
+                self.leave_grace();
+                enter_phase(&self.state, SuspendPhase::CountingDown);
+                self.debouncing = false;
+                self.paused = None;
+                self.arm(delay);
+                self.follows_timer = false;
+                self.forced = Some(action);
+                info!("Running {} in {}s on request", action, delay.as_secs());
+                return Ok(format!("Running {} in {}s", action, delay.as_secs()));
+            }
+            TimerCommand::Cancel => {
+                if !self.armed && !self.debouncing {
+                    return not_running();
+                }
+                self.paused = None;
+                let holds_active = self.state.get_system_state()?.any_active();
+                enter_phase(&self.state, if holds_active { SuspendPhase::Held } else { SuspendPhase::Idle });
+                self.disarm();
+                info!("Suspension timer cancelled on request");
+                return Ok("Timer cancelled until the next state change".to_string());
+            }
+            TimerCommand::EndGrace => {
+                if !self.in_grace {
+                    return Err("No grace period is running".to_string());
+                }
+                info!("Post-resume grace period ended on request");
+                self.end_grace();
+                return Ok("Grace period ended".to_string());
+            }
+            TimerCommand::Reset => {
+                self.forced = None;
+                let duration = self.full_duration();
+                if self.armed {
+                    self.arm(duration);
+                    self.started = self.state.clock.now();
+                    self.follows_timer = true;
+                } else {
+                    // Also restarts a cancelled countdown, but never overrides a hold
+                    let holds = self.state.get_system_state()?.active_holds();
+                    if !holds.is_empty() {
+                        return Err(format!("Timer is held by: {}", holds.join(", ")));
+                    }
+                    self.leave_grace();
+                    enter_phase(&self.state, SuspendPhase::CountingDown);
+                    self.start();
+                }
+                format!("Timer reset to {}s", duration.as_secs())
+            }
+            TimerCommand::DurationChanged { previous } => {
+                if !self.armed || !self.follows_timer || self.forced.is_some() {
+                    return Ok("No countdown to re-compute".to_string());
+                }
+                // Swap the old duration for the new one, keeping extensions and
+                // the time already counted down
+                let length = self.deadline.saturating_duration_since(self.started);
+                let length = length.saturating_sub(previous).saturating_add(self.full_duration());
+                let elapsed = self.state.clock.now().saturating_duration_since(self.started);
+                self.arm(length.saturating_sub(elapsed));
+                "Countdown re-computed".to_string()
+            }
+            TimerCommand::Extend(extra) => {
+                if !self.armed {
+                    return not_running();
+                }
+                let remaining = self.deadline.saturating_duration_since(self.state.clock.now());
+                match remaining.checked_add(extra) {
+                    Some(duration) if duration.as_secs() <= MAX_TIMER_SECONDS => self.arm(duration),
+                    _ => return Err(format!("The countdown can't be longer than {}s", MAX_TIMER_SECONDS)),
+                }
+                format!("Timer extended by {} minutes", extra.as_secs() / 60)
+            }
+            TimerCommand::Snooze(duration) => {
+                if !self.armed {
+                    return not_running();
+                }
+                self.arm(duration);
+                self.follows_timer = false;
+                format!("Timer snoozed for {} minutes", duration.as_secs() / 60)
+            }
+        };
+
+        let remaining = self.deadline.saturating_duration_since(self.state.clock.now());
+        info!("{}, suspending in {}s", message, remaining.as_secs());
+        Ok(format!("{}, suspending in {}s", message, remaining.as_secs()))
     }
 }
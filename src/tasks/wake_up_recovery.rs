@@ -1,41 +1,149 @@
 //! Wake-up recovery background task
 
 use std::{sync::Arc, time::Duration};
-use tokio::time::interval;
-use tracing::{info, warn};
+use tokio::{sync::broadcast, time::interval};
+use tracing::{debug, error, info, warn};
 
-use crate::state::AppState;
+use crate::{
+    services::{run_service_action, Backend, HookStage, ScheduledServiceAction, WakeAlarm},
+    state::{AppState, Hold, HistoryEvent, ResumeEvent},
+    utils::clocks::suspended_time,
+};
 
-/// Background task that checks for system wake-up and triggers state recovery
+/// How often the boottime/monotonic gap is sampled
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Gap growth below this is treated as clock jitter rather than a sleep
+const MIN_SLEEP: Duration = Duration::from_secs(2);
+
+/// How early a wake-up may come and still count as the wake alarm firing
+const WAKE_ALARM_SLACK: chrono::Duration = chrono::Duration::seconds(60);
+
+/// Background task that detects system wake-up and triggers state recovery.
+///
+/// Wake-ups are detected from the growth of the gap between `CLOCK_BOOTTIME` and
+/// `CLOCK_MONOTONIC`, so suspends triggered by anyone (lid close, desktop idle,
+/// manual `systemctl suspend`) are noticed, not only the ones we requested.
 pub async fn wake_up_recovery_task(state: Arc<AppState>) {
     info!("Starting wake-up recovery task");
-    
-    let mut interval = interval(Duration::from_secs(15));
-    
+
+    // The simulated backend announces its fake sleeps directly
+    if let Backend::Simulated(sim) = state.backend.as_ref() {
+        let mut resume_rx = sim.subscribe_resume();
+        loop {
+            match resume_rx.recv().await {
+                Ok(slept) => handle_wake_up(&state, slept),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+
+    let mut last_gap = match suspended_time() {
+        Ok(gap) => gap,
+        Err(e) => {
+            error!("Wake-up detection unavailable: {}", e);
+            return;
+        }
+    };
+
+    let mut interval = interval(CHECK_INTERVAL);
+
     loop {
         interval.tick().await;
-        
-        // Check if system was suspended
-        match state.is_suspended() {
-            Ok(true) => {
-                info!("System wake-up detected, triggering state check");
-                
-                // Trigger state check to restart timer if needed
-                if let Err(e) = state.trigger_state_check() {
-                    warn!("Failed to trigger wake-up state check: {}", e);
-                }
-                
-                // Clear the suspended flag
-                if let Err(e) = state.set_suspended(false) {
-                    warn!("Failed to clear suspended state: {}", e);
-                }
-            }
-            Ok(false) => {
-                // System not suspended, continue monitoring
-            }
+
+        let gap = match suspended_time() {
+            Ok(gap) => gap,
             Err(e) => {
-                warn!("Failed to check suspended state: {}", e);
+                warn!("Failed to read system clocks: {}", e);
+                continue;
             }
+        };
+
+        let slept = gap.saturating_sub(last_gap);
+        last_gap = gap;
+
+        if slept < MIN_SLEEP {
+            continue;
         }
+
+        debug!("Clock gap grew by {:?}, system was asleep", slept);
+        handle_wake_up(&state, slept);
     }
 }
+
+/// Record a wake-up and run post-resume hooks; the timer task handles the resume itself
+fn handle_wake_up(state: &Arc<AppState>, slept: Duration) {
+    // Only our own idle action goes through the suspending phases
+    let initiated_by_us = state.is_suspended().unwrap_or(false);
+
+    let resumed_at = state.clock.utc();
+    let suspended_at = resumed_at
+        - chrono::Duration::from_std(slept).unwrap_or_else(|_| chrono::Duration::zero());
+
+    state.notify_resume(ResumeEvent {
+        suspended_at,
+        resumed_at,
+        slept_seconds: slept.as_secs(),
+        initiated_by_us,
+    });
+
+    if let Some(alarm) = state.take_programmed_wake() {
+        if resumed_at + WAKE_ALARM_SLACK >= alarm.at {
+            handle_scheduled_wake(state, alarm, resumed_at);
+        } else if let Err(e) = state.backend.clear_wake_alarm(&state.sysfs_root) {
+            // Woken up early by something else, the alarm is re-programmed before the next sleep
+            warn!("{}", e);
+        }
+    }
+
+    // Post-resume hooks may take a while, don't hold up wake-up detection
+    if state.hooks.has_hooks(HookStage::PostResume) {
+        let hook_state = Arc::clone(state);
+        tokio::spawn(async move {
+            hook_state.run_hooks(HookStage::PostResume).await;
+        });
+    }
+}
+
+/// Hold the machine awake and start the designated services after a wake alarm
+fn handle_scheduled_wake(state: &Arc<AppState>, alarm: WakeAlarm, resumed_at: chrono::DateTime<chrono::Utc>) {
+    info!("Woke up for wake alarm {}", alarm.name);
+    state.complete_wake_alarm(&alarm);
+
+    let hold_until = (alarm.hold_minutes > 0)
+        .then(|| i64::try_from(alarm.hold_minutes).ok().and_then(chrono::Duration::try_minutes))
+        .flatten()
+        .and_then(|hold| resumed_at.checked_add_signed(hold));
+    if let Some(until) = hold_until {
+        let hold = Hold {
+            owner: "wake-alarm".to_string(),
+            reason: format!("woke up for {}", alarm.name),
+            created_at: resumed_at,
+            until: Some(until),
+            ttl_seconds: None,
+            last_heartbeat: None,
+        };
+        if let Err(e) = state.set_hold(&format!("wake:{}", alarm.name), hold) {
+            error!("Failed to hold the machine awake after wake alarm {}: {}", alarm.name, e);
+        }
+    }
+
+    state.record_history(HistoryEvent::ScheduledWake {
+        name: alarm.name.clone(),
+        alarm_at: alarm.at,
+        hold_until,
+        start_services: alarm.start_services.clone(),
+    });
+
+    if alarm.start_services.is_empty() {
+        return;
+    }
+    let service_state = Arc::clone(state);
+    tokio::spawn(async move {
+        let reason = format!("wake alarm {}", alarm.name);
+        for service_name in &alarm.start_services {
+            run_service_action(&service_state, service_name, ScheduledServiceAction::Start, &reason).await;
+        }
+    });
+}
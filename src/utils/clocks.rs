@@ -0,0 +1,86 @@
+//! Clock helpers
+//!
+//! `Clock` is the time source used by `AppState` and the background tasks. The
+//! default `SystemClock` reads tokio's clock, so pausing tokio time in tests
+//! (`#[tokio::test(start_paused = true)]`) also pauses every countdown.
+//!
+//! The kernel clock helpers detect sleeps: `CLOCK_MONOTONIC` stops while the
+//! machine is suspended, `CLOCK_BOOTTIME` does not. The gap between the two
+//! therefore only grows across a sleep, no matter who triggered it (our timer,
+//! a lid close, GNOME's idle suspend, ...).
+
+use std::{fmt::Debug, time::Duration};
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+
+/// Source of monotonic and wall-clock time
+pub trait Clock: Debug + Send + Sync {
+    /// Monotonic time for countdowns and uptime
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time for timestamps
+    fn utc(&self) -> DateTime<Utc>;
+}
+
+/// The real clock: tokio's monotonic clock and the system wall clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn utc(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock whose wall time is derived from tokio's clock.
+///
+/// With paused tokio time both readings advance together and only when the
+/// runtime advances, which keeps timestamps deterministic in tests.
+#[derive(Debug, Clone, Copy)]
+pub struct VirtualClock {
+    origin: Instant,
+    origin_utc: DateTime<Utc>,
+}
+
+impl VirtualClock {
+    /// Start the virtual wall clock at `origin_utc`
+    pub fn new(origin_utc: DateTime<Utc>) -> Self {
+        Self {
+            origin: Instant::now(),
+            origin_utc,
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn utc(&self) -> DateTime<Utc> {
+        let elapsed = Instant::now().saturating_duration_since(self.origin);
+        self.origin_utc + chrono::Duration::from_std(elapsed).unwrap_or_else(|_| chrono::Duration::zero())
+    }
+}
+
+/// Read a kernel clock as a duration since its epoch
+fn read_clock(clock_id: libc::clockid_t) -> Result<Duration, String> {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: `ts` is a valid, writable timespec for the duration of the call
+    let result = unsafe { libc::clock_gettime(clock_id, &mut ts) };
+    if result != 0 {
+        return Err(format!("clock_gettime failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// Total time the machine has spent suspended since boot
+pub fn suspended_time() -> Result<Duration, String> {
+    let monotonic = read_clock(libc::CLOCK_MONOTONIC)?;
+    let boottime = read_clock(libc::CLOCK_BOOTTIME)?;
+    Ok(boottime.saturating_sub(monotonic))
+}
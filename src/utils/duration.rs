@@ -0,0 +1,49 @@
+//! Human-readable durations for query parameters
+
+use std::time::Duration;
+
+/// Longest duration accepted, a year
+pub const MAX_DURATION: Duration = Duration::from_secs(365 * 24 * 3600);
+
+/// Parse durations such as "2h", "90m", "1h30m", "45s" or "1d"; a bare number is minutes
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let invalid = || format!("Invalid duration {:?} (e.g. 2h, 90m, 1h30m)", text);
+    let too_long = || format!("Duration {:?} is longer than {} days", text, MAX_DURATION.as_secs() / 86400);
+
+    let mut seconds = 0u64;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 24 * 3600,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+        seconds = value.checked_mul(unit)
+            .and_then(|part| seconds.checked_add(part))
+            .ok_or_else(too_long)?;
+    }
+
+    if !number.is_empty() {
+        // A bare number is minutes, a trailing one is an error
+        seconds = match (seconds, number.parse::<u64>()) {
+            (0, Ok(minutes)) => minutes.checked_mul(60).ok_or_else(too_long)?,
+            _ => return Err(invalid()),
+        };
+    }
+    if seconds == 0 {
+        return Err(invalid());
+    }
+    if seconds > MAX_DURATION.as_secs() {
+        return Err(too_long());
+    }
+    Ok(Duration::from_secs(seconds))
+}
@@ -3,6 +3,10 @@
 //! This module contains utility functions used throughout the application.
 
 pub mod signals;
+pub mod clocks;
+pub mod duration;
 
 // Re-export main functions
 pub use signals::shutdown_signal;
+pub use clocks::{Clock, SystemClock, VirtualClock};
+pub use duration::parse_duration;
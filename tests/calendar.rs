@@ -0,0 +1,187 @@
+//! Keep-awake holds from a local .ics file
+
+mod common;
+
+use std::{fs, path::{Path, PathBuf}, time::Duration};
+
+use chrono::{TimeZone, Utc};
+use common::Harness;
+use order_coffee::services::{parse_ics, running_events, CalendarConfig};
+
+fn calendar_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("order-coffee-{}-{}.ics", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn event(uid: &str, summary: &str, start: &str, end: &str) -> String {
+    format!("BEGIN:VEVENT\r\nUID:{}\r\nSUMMARY:{}\r\nDTSTART:{}\r\nDTEND:{}\r\nEND:VEVENT\r\n", uid, summary, start, end)
+}
+
+fn calendar(events: &[String]) -> String {
+    format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n", events.concat())
+}
+
+fn config(path: &Path) -> CalendarConfig {
+    CalendarConfig {
+        path: Some(path.to_path_buf()),
+        summary_contains: Some("render".to_string()),
+        start_service: Some("comfy-unsafe".to_string()),
+        poll_seconds: 60,
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn matching_events_hold_the_machine_awake_and_start_the_service() {
+    let path = calendar_file("events");
+    fs::write(&path, calendar(&[
+        event("job-1", "Render: trailer", "20250101T000500Z", "20250101T003000Z"),
+        event("lunch", "Lunch", "20250101T000500Z", "20250101T010000Z"),
+    ])).unwrap();
+    let harness = Harness::start_configured(10, |state| state.with_calendar(config(&path))).await;
+
+    harness.advance(Duration::from_secs(6 * 60)).await;
+    let status = harness.status().await;
+    assert_eq!(status["states"]["holds"]["calendar:job-1@20250101T000500Z"]["reason"], "Render: trailer");
+    assert_eq!(status["states"]["holds"]["calendar:job-1@20250101T000500Z"]["until"], "2025-01-01T00:30:00Z");
+    assert!(status["states"]["holds"].get("calendar:lunch@20250101T000500Z").is_none());
+    assert_eq!(status["states"]["services"]["comfy-unsafe"], true);
+    assert_eq!(status["timer_active"], false);
+    assert!(harness.state.backend.is_service_active("comfy-unsafe.service").await.unwrap());
+
+    // The started service keeps the machine awake past the event, like one started through the API
+    harness.advance(Duration::from_secs(25 * 60)).await;
+    let status = harness.status().await;
+    assert!(status["states"]["holds"].as_object().unwrap().is_empty());
+    assert_eq!(status["timer_active"], false);
+
+    harness.post("/service/comfy-unsafe/stop").await;
+    assert_eq!(harness.status().await["phase"], "counting_down");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn changed_file_is_read_again() {
+    let path = calendar_file("changes");
+    fs::write(&path, calendar(&[])).unwrap();
+    let calendar_config = CalendarConfig { start_service: None, ..config(&path) };
+    let harness = Harness::start_configured(10, |state| state.with_calendar(calendar_config)).await;
+
+    fs::write(&path, calendar(&[event("job-2", "Render farm", "20241231T230000Z", "20250101T020000Z")])).unwrap();
+    harness.advance(Duration::from_secs(60)).await;
+    assert_eq!(harness.status().await["states"]["holds"]["calendar:job-2@20241231T230000Z"]["reason"], "Render farm");
+
+    // A cancelled booking releases the hold right away
+    fs::write(&path, calendar(&[])).unwrap();
+    harness.advance(Duration::from_secs(60)).await;
+    let status = harness.status().await;
+    assert!(status["states"]["holds"].as_object().unwrap().is_empty());
+    assert_eq!(status["phase"], "counting_down");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn recurring_events_hold_every_occurrence_unless_overridden() {
+    let path = calendar_file("recurring");
+    fs::write(&path, calendar(&[
+        "BEGIN:VEVENT\r\nUID:nightly\r\nSUMMARY:Render nightly\r\nDTSTART:20241230T000500Z\r\n\
+         DTEND:20241230T003000Z\r\nRRULE:FREQ=DAILY;COUNT=5\r\nEND:VEVENT\r\n".to_string(),
+        "BEGIN:VEVENT\r\nUID:nightly\r\nSUMMARY:Render nightly (moved)\r\nRECURRENCE-ID:20250101T000500Z\r\n\
+         DTSTART:20250101T001000Z\r\nDTEND:20250101T002000Z\r\nEND:VEVENT\r\n".to_string(),
+    ])).unwrap();
+    let calendar_config = CalendarConfig { start_service: None, ..config(&path) };
+    let harness = Harness::start_configured(10, |state| state.with_calendar(calendar_config)).await;
+
+    // Today's occurrence was moved from 00:05 to 00:10
+    harness.advance(Duration::from_secs(6 * 60)).await;
+    assert!(harness.status().await["states"]["holds"].as_object().unwrap().is_empty());
+
+    harness.advance(Duration::from_secs(5 * 60)).await;
+    let holds = harness.status().await["states"]["holds"].clone();
+    assert_eq!(holds.as_object().unwrap().len(), 1);
+    assert_eq!(holds["calendar:nightly@20250101T001000Z"]["reason"], "Render nightly (moved)");
+    assert_eq!(holds["calendar:nightly@20250101T001000Z"]["until"], "2025-01-01T00:20:00Z");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn weekly_rules_are_expanded_with_exceptions() {
+    let ics = "BEGIN:VCALENDAR\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:review\r\n\
+        SUMMARY:Render review\r\n\
+        DTSTART:20250106T100000Z\r\n\
+        DTEND:20250106T110000Z\r\n\
+        RRULE:FREQ=WEEKLY;INTERVAL=2;UNTIL=20250317\r\n\
+        EXDATE:20250120T100000Z\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:review\r\n\
+        RECURRENCE-ID:20250203T100000Z\r\n\
+        STATUS:CANCELLED\r\n\
+        DTSTART:20250203T100000Z\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+    let events = parse_ics(ics);
+    let running = |day, hour, minute| running_events(&events, Utc.with_ymd_and_hms(2025, 1, day, hour, minute, 0).unwrap());
+
+    assert_eq!(running(6, 10, 30)[0].hold_name(), "calendar:review@20250106T100000Z");
+    assert!(running(13, 10, 30).is_empty()); // every other week
+    assert!(running(20, 10, 30).is_empty()); // EXDATE
+    assert!(running(6, 11, 0).is_empty());
+
+    let running = |month, day| running_events(&events, Utc.with_ymd_and_hms(2025, month, day, 10, 30, 0).unwrap());
+    assert!(running(2, 3).is_empty()); // cancelled
+    assert_eq!(running(3, 3)[0].end, Utc.with_ymd_and_hms(2025, 3, 3, 11, 0, 0).unwrap());
+    assert_eq!(running(3, 17).len(), 1); // UNTIL is a date, so it includes that day
+    assert!(running(3, 31).is_empty());
+}
+
+#[test]
+fn events_with_unsupported_recurrence_rules_are_skipped() {
+    let ics = calendar(&[
+        "BEGIN:VEVENT\r\nUID:standup\r\nDTSTART:20250106T100000Z\r\nDTEND:20250106T101500Z\r\n\
+            RRULE:FREQ=WEEKLY;BYDAY=MO,WE\r\nEND:VEVENT\r\n".to_string(),
+        event("render", "Render", "20250106T120000Z", "20250106T130000Z"),
+        "BEGIN:VEVENT\r\nUID:sync\r\nDTSTART:20250106T100000Z\r\nDTEND:20250106T110000Z\r\n\
+            RRULE:FREQ=DAILY;COUNT=3\r\nEND:VEVENT\r\n".to_string(),
+    ]);
+    let events = parse_ics(&ics);
+    let uids: Vec<_> = events.iter().map(|event| event.uid.as_deref().unwrap()).collect();
+    assert_eq!(uids, ["render", "sync"]);
+
+    let running = running_events(&events, Utc.with_ymd_and_hms(2025, 1, 8, 10, 30, 0).unwrap());
+    assert_eq!(running[0].hold_name(), "calendar:sync@20250108T100000Z");
+}
+
+#[test]
+fn events_are_parsed_with_folding_durations_and_cancellations() {
+    let ics = "BEGIN:VCALENDAR\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:a\r\n\
+        SUMMARY:Render\\, final\r\n  cut\r\n\
+        DTSTART:20250101T100000Z\r\n\
+        DURATION:PT1H30M\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:b\r\n\
+        STATUS:CANCELLED\r\n\
+        DTSTART:20250101T100000Z\r\n\
+        DTEND:20250101T110000Z\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:c\r\n\
+        DTSTART:20250101T100000Z\r\n\
+        DURATION:P9223372036854775807W\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    let events = parse_ics(ics);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].summary, "Render, final cut");
+    assert_eq!(events[0].start, Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap());
+    assert_eq!(events[0].end, Utc.with_ymd_and_hms(2025, 1, 1, 11, 30, 0).unwrap());
+}
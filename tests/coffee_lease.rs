@@ -0,0 +1,135 @@
+//! Coffee leases: `/coffee?for=` and `/coffee?until=` end by themselves
+
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeZone, Utc};
+use common::Harness;
+use order_coffee::{
+    state::ResumeEvent,
+    utils::{parse_duration, Clock, VirtualClock},
+};
+use tokio::time::Instant;
+
+#[tokio::test(start_paused = true)]
+async fn lease_expires_and_starts_the_countdown() {
+    let harness = Harness::start(10).await;
+    let (code, body) = harness.post("/coffee?for=2h").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["states"]["coffee"], true);
+    assert_eq!(body["states"]["coffee_until"], "2025-01-01T02:00:00Z");
+
+    harness.advance(Duration::from_secs(3600)).await;
+    let status = harness.status().await;
+    assert_eq!(status["coffee_remaining_seconds"], 3600);
+    assert_eq!(status["timer_active"], false);
+
+    harness.advance(Duration::from_secs(3600)).await;
+    let status = harness.status().await;
+    assert_eq!(status["states"]["coffee"], false);
+    assert!(status["coffee_remaining_seconds"].is_null());
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["last_action"], "hold-expired");
+}
+
+#[tokio::test(start_paused = true)]
+async fn plain_coffee_replaces_a_lease_and_stays_on() {
+    let harness = Harness::start(10).await;
+    harness.post("/coffee?until=2025-01-01T00:30:00Z").await;
+    assert_eq!(harness.status().await["coffee_remaining_seconds"], 1800);
+
+    harness.post("/coffee").await;
+    harness.advance(Duration::from_secs(3600)).await;
+    let status = harness.status().await;
+    assert_eq!(status["states"]["coffee"], true);
+    assert!(status["states"]["coffee_until"].is_null());
+
+    harness.post("/chill").await;
+    assert_eq!(harness.status().await["phase"], "counting_down");
+}
+
+#[tokio::test(start_paused = true)]
+async fn invalid_leases_are_rejected() {
+    let harness = Harness::start(10).await;
+
+    let (code, _) = harness.post("/coffee?for=soon").await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post("/coffee?until=2024-12-31T23:00:00Z").await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post("/coffee?for=1h&until=2025-01-01T05:00:00Z").await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post("/coffee?for=9999999999999999d").await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+
+    assert_eq!(harness.status().await["states"]["coffee"], false);
+}
+
+#[test]
+fn durations_accept_units_and_plain_minutes() {
+    assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+    assert_eq!(parse_duration("45"), Ok(Duration::from_secs(2700)));
+    assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+    assert!(parse_duration("2x").is_err());
+    assert!(parse_duration("h").is_err());
+    assert!(parse_duration("0m").is_err());
+}
+
+#[test]
+fn durations_are_limited_to_a_year() {
+    assert_eq!(parse_duration("365d"), Ok(Duration::from_secs(365 * 86400)));
+    assert!(parse_duration("366d").is_err());
+    assert!(parse_duration("18446744073709551615d").is_err());
+    assert!(parse_duration("18446744073709551615").is_err());
+}
+
+/// A virtual clock whose wall time can jump ahead while tokio's clock stands
+/// still, like across a real suspend
+#[derive(Debug)]
+struct SleepingClock {
+    inner: VirtualClock,
+    slept: Mutex<chrono::Duration>,
+}
+
+impl Clock for SleepingClock {
+    fn now(&self) -> Instant {
+        self.inner.now()
+    }
+
+    fn utc(&self) -> DateTime<Utc> {
+        self.inner.utc() + *self.slept.lock().unwrap()
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn lease_ending_during_a_suspend_is_released_on_wake_up() {
+    let clock = Arc::new(SleepingClock {
+        inner: VirtualClock::new(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+        slept: Mutex::new(chrono::Duration::zero()),
+    });
+    let harness = Harness::start_configured(10, {
+        let clock = Arc::clone(&clock);
+        |state| state.with_clock(clock)
+    })
+    .await;
+    harness.post("/coffee?for=10m").await;
+
+    // Asleep for an hour: tokio's clock doesn't move, the wall clock does
+    *clock.slept.lock().unwrap() = chrono::Duration::hours(1);
+    harness.state.notify_resume(ResumeEvent {
+        suspended_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+        resumed_at: Utc.with_ymd_and_hms(2025, 1, 1, 1, 0, 0).unwrap(),
+        slept_seconds: 3600,
+        initiated_by_us: false,
+    });
+    common::settle().await;
+
+    let status = harness.status().await;
+    assert_eq!(status["states"]["coffee"], false);
+    assert_eq!(status["last_action"], "hold-expired");
+}
@@ -0,0 +1,221 @@
+//! Shared harness for the integration tests
+//!
+//! Builds an `AppState` on the simulated backend with a `VirtualClock`, spawns
+//! the background tasks and drives the HTTP router in-process. Tests run with
+//! paused tokio time (`#[tokio::test(start_paused = true)]`), so a ten minute
+//! countdown takes no real time and every step is deterministic.
+
+#![allow(dead_code)]
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, Response, StatusCode},
+    Router,
+};
+use chrono::{TimeZone, Utc};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use order_coffee::{
+    api::create_router,
+    services::{
+        Backend, SimulatedHost, SimulationConfig,
+    },
+    state::AppState,
+    tasks::{
+        calendar_task, hold_expiry_task, schedule_task, service_watchdog_task, suspension_timer_task,
+        wake_up_recovery_task,
+    },
+    utils::VirtualClock,
+};
+
+/// Upper bound for response bodies read by the harness
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Service watchdog settings, the `--watchdog-*` defaults
+pub const WATCHDOG_INTERVAL: Duration = Duration::from_secs(30);
+pub const WATCHDOG_MAX_RESTARTS: u32 = 3;
+
+/// A running server without a socket
+pub struct Harness {
+    pub state: Arc<AppState>,
+    router: Router,
+}
+
+impl Harness {
+    /// Start a server with the given timer and default simulation settings
+    pub async fn start(timer_minutes: u64) -> Self {
+        Self::start_with(timer_minutes, simulation()).await
+    }
+
+    /// Start a server with the given timer and simulation settings
+    pub async fn start_with(timer_minutes: u64, simulation: SimulationConfig) -> Self {
+        Self::start_full(timer_minutes, simulation, |state| state).await
+    }
+
+    /// Start a server with the given timer and default simulation settings,
+    /// configuring the state first (e.g. `|state| state.with_schedule(schedule)`)
+    pub async fn start_configured<F>(timer_minutes: u64, configure: F) -> Self
+    where
+        F: FnOnce(AppState) -> AppState,
+    {
+        Self::start_full(timer_minutes, simulation(), configure).await
+    }
+
+    /// The simulated host behind the backend
+    pub fn host(&self) -> &SimulatedHost {
+        match &*self.state.backend {
+            Backend::Simulated(host) => host,
+            Backend::Systemd => unreachable!("the harness always runs on the simulated backend"),
+        }
+    }
+
+    /// Start a server with the given simulation settings, configuring the state first
+    pub async fn start_full<F>(timer_minutes: u64, simulation: SimulationConfig, configure: F) -> Self
+    where
+        F: FnOnce(AppState) -> AppState,
+    {
+        let clock = VirtualClock::new(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+        let state = Arc::new(configure(
+            AppState::new(20553, "127.0.0.1".to_string(), timer_minutes)
+                .with_clock(Arc::new(clock))
+                .with_backend(Backend::Simulated(Box::new(SimulatedHost::new(simulation)))),
+        ));
+
+        tokio::spawn(suspension_timer_task(Arc::clone(&state)));
+        tokio::spawn(wake_up_recovery_task(Arc::clone(&state)));
+        tokio::spawn(hold_expiry_task(Arc::clone(&state)));
+        tokio::spawn(schedule_task(Arc::clone(&state)));
+        tokio::spawn(calendar_task(Arc::clone(&state)));
+        tokio::spawn(service_watchdog_task(Arc::clone(&state), WATCHDOG_INTERVAL, WATCHDOG_MAX_RESTARTS));
+        settle().await;
+
+        state.trigger_state_check().expect("initial state check");
+        settle().await;
+
+        let router = create_router(Arc::clone(&state));
+        Self { state, router }
+    }
+
+    /// Send a request and decode the JSON response (`Value::Null` for empty bodies)
+    pub async fn request(&self, method: Method, uri: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .expect("valid request");
+        self.send(request).await
+    }
+
+    /// Send a request with a JSON body
+    pub async fn request_json(&self, method: Method, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("valid request");
+        self.send(request).await
+    }
+
+    /// GET an endpoint with an `Accept` header and hand back the response
+    /// unread, e.g. to follow a stream
+    pub async fn get_response(&self, uri: &str, accept: &str) -> Response<Body> {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .expect("valid request");
+        self.router.clone().oneshot(request).await.expect("router is infallible")
+    }
+
+    async fn send(&self, request: Request<Body>) -> (StatusCode, Value) {
+        let response = self.router.clone().oneshot(request).await.expect("router is infallible");
+        let status = response.status();
+        let body = to_bytes(response.into_body(), MAX_BODY_BYTES).await.expect("readable body");
+        let json = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&body).expect("JSON body")
+        };
+        settle().await;
+        (status, json)
+    }
+
+    /// POST to an endpoint
+    pub async fn post(&self, uri: &str) -> (StatusCode, Value) {
+        self.request(Method::POST, uri).await
+    }
+
+    /// POST a JSON body to an endpoint
+    pub async fn post_json(&self, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.request_json(Method::POST, uri, body).await
+    }
+
+    /// DELETE an endpoint
+    pub async fn delete(&self, uri: &str) -> (StatusCode, Value) {
+        self.request(Method::DELETE, uri).await
+    }
+
+    /// PUT a JSON body to an endpoint
+    pub async fn put_json(&self, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.request_json(Method::PUT, uri, body).await
+    }
+
+    /// GET an endpoint
+    pub async fn get(&self, uri: &str) -> (StatusCode, Value) {
+        self.request(Method::GET, uri).await
+    }
+
+    /// GET /status
+    pub async fn status(&self) -> Value {
+        let (code, body) = self.get("/status").await;
+        assert_eq!(code, StatusCode::OK);
+        body
+    }
+
+    /// Entries of GET /history with the given type
+    pub async fn history(&self, event_type: &str) -> Vec<Value> {
+        let (code, body) = self.get("/history").await;
+        assert_eq!(code, StatusCode::OK);
+        body["entries"]
+            .as_array()
+            .expect("entries array")
+            .iter()
+            .filter(|entry| entry["type"] == event_type)
+            .cloned()
+            .collect()
+    }
+
+    /// Let virtual time pass; background tasks run every timer that falls due
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+        settle().await;
+    }
+}
+
+/// Simulation settings with instant, reliable services
+pub fn simulation() -> SimulationConfig {
+    SimulationConfig {
+        start_delay_ms: 0,
+        failure_rate: 0.0,
+        sleep_seconds: 30,
+        ..SimulationConfig::default()
+    }
+}
+
+/// A fresh path for a state file in the temporary directory
+pub fn temp_state_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("order-coffee-{}-{}.json", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Give spawned tasks a chance to react to notifications
+pub async fn settle() {
+    for _ in 0..20 {
+        tokio::task::yield_now().await;
+    }
+}
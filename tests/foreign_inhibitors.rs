@@ -0,0 +1,85 @@
+//! Inhibitors of other applications deferring or skipping suspension
+
+mod common;
+
+use std::time::Duration;
+
+use common::Harness;
+use order_coffee::services::{Inhibitor, InhibitorAction, InhibitorPolicyConfig, InhibitorRule};
+
+fn respect_backups(action: InhibitorAction) -> InhibitorPolicyConfig {
+    InhibitorPolicyConfig {
+        action,
+        recheck_seconds: 60,
+        respect: vec![InhibitorRule {
+            who: Some("restic".to_string()),
+            what: Some("sleep".to_string()),
+        }],
+    }
+}
+
+fn inhibitor(who: &str, what: &str, mode: &str) -> Inhibitor {
+    Inhibitor {
+        what: what.to_string(),
+        who: who.to_string(),
+        why: "Backup in progress".to_string(),
+        mode: mode.to_string(),
+        uid: 0,
+        pid: 4242,
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn respected_inhibitor_defers_the_suspension() {
+    let harness = Harness::start_configured(1, |state| state.with_inhibitor_policy(respect_backups(InhibitorAction::Defer))).await;
+    harness.host().add_inhibitor(inhibitor("restic", "sleep:shutdown", "block"));
+
+    harness.advance(Duration::from_secs(61)).await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["timer_remaining_seconds"], 59);
+    assert_eq!(status["blocking_inhibitors"][0]["who"], "restic");
+    assert_eq!(status["blocking_inhibitors"][0]["why"], "Backup in progress");
+
+    // Still there at the next check
+    harness.advance(Duration::from_secs(60)).await;
+    assert_eq!(harness.status().await["phase"], "counting_down");
+
+    harness.host().remove_inhibitors("restic");
+    harness.advance(Duration::from_secs(60)).await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "suspended");
+    assert_eq!(status["blocking_inhibitors"].as_array().unwrap().len(), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn respected_inhibitor_can_skip_the_suspension() {
+    let harness = Harness::start_configured(2, |state| state.with_inhibitor_policy(respect_backups(InhibitorAction::Skip))).await;
+    harness.host().add_inhibitor(inhibitor("restic", "sleep", "block"));
+
+    harness.advance(Duration::from_secs(121)).await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["timer_remaining_seconds"], 119);
+    assert_eq!(status["blocking_inhibitors"][0]["who"], "restic");
+}
+
+#[tokio::test(start_paused = true)]
+async fn other_inhibitors_are_ignored() {
+    let harness = Harness::start_configured(1, |state| state.with_inhibitor_policy(respect_backups(InhibitorAction::Defer))).await;
+    harness.host().add_inhibitor(inhibitor("restic", "sleep", "delay"));
+    harness.host().add_inhibitor(inhibitor("restic", "shutdown", "block"));
+    harness.host().add_inhibitor(inhibitor("order-coffee", "sleep:idle", "block"));
+    harness.host().add_inhibitor(inhibitor("packagekit", "sleep", "block"));
+
+    harness.advance(Duration::from_secs(61)).await;
+    assert_eq!(harness.status().await["phase"], "suspended");
+}
+
+#[test]
+fn recheck_interval_must_be_positive() {
+    assert!(respect_backups(InhibitorAction::Defer).validate().is_ok());
+
+    let policy = InhibitorPolicyConfig { recheck_seconds: 0, ..respect_backups(InhibitorAction::Defer) };
+    assert!(policy.validate().is_err());
+}
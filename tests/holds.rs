@@ -0,0 +1,179 @@
+//! Named holds from several clients, their heartbeats, and /coffee as the default hold
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use chrono::{TimeZone, Utc};
+use common::Harness;
+use order_coffee::state::Hold;
+use serde_json::json;
+
+#[tokio::test(start_paused = true)]
+async fn machine_stays_awake_until_the_last_hold_is_released() {
+    let harness = Harness::start(10).await;
+    let (code, alice) = harness.post_json("/holds", json!({"owner": "alice", "reason": "training run"})).await;
+    assert_eq!(code, StatusCode::OK);
+    let (_, bob) = harness.post_json("/holds", json!({"owner": "bob", "reason": "rendering"})).await;
+    let alice_id = alice["id"].as_str().unwrap();
+    let bob_id = bob["id"].as_str().unwrap();
+    assert_ne!(alice_id, bob_id);
+
+    let status = harness.status().await;
+    assert_eq!(status["states"]["holds"][alice_id]["owner"], "alice");
+    assert_eq!(status["states"]["holds"][alice_id]["reason"], "training run");
+    assert_eq!(status["states"]["holds"][alice_id]["created_at"], "2025-01-01T00:00:00Z");
+    assert_eq!(status["states"]["holds"][bob_id]["owner"], "bob");
+
+    let (code, body) = harness.delete(&format!("/holds/{}", alice_id)).await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["status"], "active");
+    assert_eq!(harness.status().await["timer_active"], false);
+
+    let (code, body) = harness.delete(&format!("/holds/{}", bob_id)).await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["status"], "inactive");
+    assert_eq!(harness.status().await["phase"], "counting_down");
+}
+
+#[tokio::test(start_paused = true)]
+async fn chill_keeps_named_holds() {
+    let harness = Harness::start(10).await;
+    harness.post("/coffee").await;
+    let (_, body) = harness.post_json("/holds", json!({"owner": "bob", "reason": "backup"})).await;
+    let id = body["id"].as_str().unwrap().to_string();
+
+    let status = harness.status().await;
+    assert_eq!(status["states"]["coffee"], true);
+    assert_eq!(status["states"]["holds"]["coffee"]["owner"], "coffee");
+
+    harness.post("/chill").await;
+    let status = harness.status().await;
+    assert_eq!(status["states"]["coffee"], false);
+    assert!(status["states"]["holds"].get("coffee").is_none());
+    assert_eq!(status["states"]["holds"][&id]["owner"], "bob");
+    assert_eq!(status["timer_active"], false);
+}
+
+#[tokio::test(start_paused = true)]
+async fn hold_with_ttl_is_released_by_itself() {
+    let harness = Harness::start(10).await;
+    let (_, body) = harness.post_json("/holds", json!({"owner": "ci", "reason": "job", "ttl": "30m"})).await;
+    assert_eq!(body["hold"]["until"], "2025-01-01T00:30:00Z");
+
+    harness.advance(Duration::from_secs(1800)).await;
+    let status = harness.status().await;
+    assert!(status["states"]["holds"].as_object().unwrap().is_empty());
+    assert_eq!(status["phase"], "counting_down");
+
+    // The client never sent a heartbeat, e.g. it crashed right away
+    let missed = harness.history("heartbeat_missed").await;
+    assert_eq!(missed.len(), 1);
+    assert_eq!(missed[0]["owner"], "ci");
+    assert_eq!(missed[0]["last_heartbeat"], "2025-01-01T00:00:00Z");
+}
+
+#[tokio::test(start_paused = true)]
+async fn invalid_holds_are_rejected() {
+    let harness = Harness::start(10).await;
+
+    let (code, _) = harness.post_json("/holds", json!({"owner": " ", "reason": "nobody"})).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post_json("/holds", json!({"owner": "alice", "ttl": "soon"})).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post_json("/holds", json!({"owner": "alice", "ttl": "99999999999999999d"})).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.delete("/holds/unknown").await;
+    assert_eq!(code, StatusCode::NOT_FOUND);
+
+    assert!(harness.status().await["states"]["holds"].as_object().unwrap().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn heartbeats_keep_a_hold_alive() {
+    let harness = Harness::start(10).await;
+    let (_, body) = harness.post_json("/holds", json!({"owner": "ci", "reason": "build", "ttl": "2m"})).await;
+    let id = body["id"].as_str().unwrap().to_string();
+    assert_eq!(body["hold"]["ttl_seconds"], 120);
+
+    for _ in 0..5 {
+        harness.advance(Duration::from_secs(60)).await;
+        let (code, body) = harness.post(&format!("/holds/{}/heartbeat", id)).await;
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(body["hold"]["owner"], "ci");
+    }
+    let status = harness.status().await;
+    assert_eq!(status["states"]["holds"][&id]["until"], "2025-01-01T00:07:00Z");
+    assert_eq!(status["timer_active"], false);
+    assert!(harness.history("heartbeat_missed").await.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn missed_heartbeat_releases_the_hold_and_is_recorded() {
+    let harness = Harness::start(10).await;
+    let (_, body) = harness.post_json("/holds", json!({"owner": "ci", "reason": "build", "ttl": "2m"})).await;
+    let id = body["id"].as_str().unwrap().to_string();
+    harness.advance(Duration::from_secs(60)).await;
+    harness.post(&format!("/holds/{}/heartbeat", id)).await;
+
+    harness.advance(Duration::from_secs(120)).await;
+    let status = harness.status().await;
+    assert!(status["states"]["holds"].get(&id).is_none());
+    assert_eq!(status["phase"], "counting_down");
+
+    let missed = harness.history("heartbeat_missed").await;
+    assert_eq!(missed.len(), 1);
+    assert_eq!(missed[0]["id"], id);
+    assert_eq!(missed[0]["owner"], "ci");
+    assert_eq!(missed[0]["last_heartbeat"], "2025-01-01T00:01:00Z");
+
+    let (code, _) = harness.post(&format!("/holds/{}/heartbeat", id)).await;
+    assert_eq!(code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(start_paused = true)]
+async fn holds_without_ttl_cannot_be_renewed() {
+    let harness = Harness::start(10).await;
+    harness.post("/coffee?for=1h").await;
+    let (code, _) = harness.post("/holds/coffee/heartbeat").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+
+    // Leases and other holds with a fixed end aren't reported as missed heartbeats
+    harness.advance(Duration::from_secs(3600)).await;
+    assert_eq!(harness.status().await["states"]["coffee"], false);
+    assert!(harness.history("heartbeat_missed").await.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn server_holds_cannot_be_released_by_clients() {
+    let harness = Harness::start(10).await;
+    let hold = Hold {
+        owner: "wake-alarm".to_string(),
+        reason: "woke up for backup".to_string(),
+        created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+        until: Some(Utc.with_ymd_and_hms(2025, 1, 1, 1, 0, 0).unwrap()),
+        ttl_seconds: None,
+        last_heartbeat: None,
+    };
+    harness.state.set_hold("wake:backup", hold).unwrap();
+
+    let (code, _) = harness.delete("/holds/wake:backup").await;
+    assert_eq!(code, StatusCode::FORBIDDEN);
+    assert_eq!(harness.status().await["states"]["holds"]["wake:backup"]["owner"], "wake-alarm");
+}
+
+#[tokio::test(start_paused = true)]
+async fn holds_taken_in_the_same_tick_get_distinct_random_ids() {
+    let harness = Harness::start(10).await;
+
+    let mut ids = std::collections::HashSet::new();
+    for _ in 0..50 {
+        let (id, _) = harness.state.take_hold("client", "same tick", None).unwrap();
+        assert_eq!(id.len(), 32);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+        ids.insert(id);
+    }
+    assert_eq!(ids.len(), 50);
+    assert_eq!(harness.status().await["states"]["holds"].as_object().unwrap().len(), 50);
+}
@@ -0,0 +1,46 @@
+//! Pre-suspend and post-resume hooks
+
+mod common;
+
+use std::time::Duration;
+
+use common::Harness;
+use order_coffee::services::{HookPipeline, HooksConfig};
+
+fn stop_ollama_before_sleeping() -> HooksConfig {
+    toml::from_str(
+        r#"
+        [[pre_suspend]]
+        name = "stop-ollama"
+        stop_services = ["ollama"]
+        "#,
+    )
+    .unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn hook_runs_are_timestamped_with_the_state_clock() {
+    let harness = Harness::start_configured(1, |state| {
+        state.with_hooks(HookPipeline::new(stop_ollama_before_sleeping()))
+    })
+    .await;
+
+    harness.advance(Duration::from_secs(61)).await;
+    let run = harness.status().await["last_hook_run"].clone();
+    assert_eq!(run["stage"], "pre_suspend");
+    assert_eq!(run["finished_at"], "2025-01-01T00:01:00Z");
+    assert_eq!(run["results"][0]["message"], "ollama not running");
+}
+
+#[test]
+fn misspelled_hook_settings_are_rejected() {
+    let config = toml::from_str::<HooksConfig>(
+        r#"
+        [[pre_suspend]]
+        name = "backup"
+        command = "true"
+        timout_seconds = 5
+        "#,
+    );
+    assert!(config.unwrap_err().to_string().contains("timout_seconds"));
+}
@@ -0,0 +1,168 @@
+//! Staged idle policy: stages run during the countdown and are undone by activity
+
+mod common;
+
+use std::time::Duration;
+
+use common::{simulation, Harness};
+use order_coffee::services::{
+    write_cpu_governor, IdlePolicyConfig, IdleStageConfig, SimulatedServiceConfig, SimulationConfig,
+};
+
+fn policy() -> IdlePolicyConfig {
+    IdlePolicyConfig {
+        stages: vec![
+            IdleStageConfig {
+                name: "powersave".to_string(),
+                after_minutes: 4,
+                stop_services: Vec::new(),
+                cpu_governor: Some("powersave".to_string()),
+            },
+            IdleStageConfig {
+                name: "stop-heavy-services".to_string(),
+                after_minutes: 2,
+                stop_services: vec!["comfy-unsafe".to_string()],
+                cpu_governor: None,
+            },
+        ],
+    }
+    .sorted()
+}
+
+fn governor(harness: &Harness) -> String {
+    harness.host().cpu_governor()
+}
+
+#[tokio::test(start_paused = true)]
+async fn stages_are_listed_in_order_with_their_times() {
+    let harness = Harness::start_configured(10, |state| state.with_idle_policy(policy())).await;
+
+    let status = harness.status().await;
+    let stages = status["idle_stages"].as_array().expect("idle_stages array");
+    assert_eq!(stages.len(), 2);
+    assert_eq!(stages[0]["name"], "stop-heavy-services");
+    assert_eq!(stages[0]["at"], "2025-01-01T00:02:00Z");
+    assert_eq!(stages[0]["done"], false);
+    assert_eq!(stages[1]["name"], "powersave");
+    assert_eq!(stages[1]["at"], "2025-01-01T00:04:00Z");
+}
+
+#[tokio::test(start_paused = true)]
+async fn stages_run_when_due_and_suspend_still_waits_for_the_timer() {
+    let harness = Harness::start_configured(10, |state| state.with_idle_policy(policy())).await;
+    // Started outside of order-coffee, so not held
+    harness.state.backend.start_service("comfy-unsafe.service").await.unwrap();
+
+    harness.advance(Duration::from_secs(121)).await;
+    assert!(!harness.state.backend.is_service_active("comfy-unsafe.service").await.unwrap());
+    assert_eq!(governor(&harness), "schedutil");
+    let status = harness.status().await;
+    assert_eq!(status["idle_stages"][0]["done"], true);
+    assert_eq!(status["idle_stages"][1]["done"], false);
+
+    harness.advance(Duration::from_secs(120)).await;
+    assert_eq!(governor(&harness), "powersave");
+    assert_eq!(harness.status().await["idle_stages"][1]["done"], true);
+    assert!(harness.history("sleep").await.is_empty());
+
+    harness.advance(Duration::from_secs(360)).await;
+    assert_eq!(harness.status().await["phase"], "suspended");
+    // The governor is put back before sleeping
+    assert_eq!(governor(&harness), "schedutil");
+}
+
+#[tokio::test(start_paused = true)]
+async fn activity_cancels_pending_stages_and_restores_the_governor() {
+    let harness = Harness::start_configured(10, |state| state.with_idle_policy(policy())).await;
+    harness.advance(Duration::from_secs(241)).await;
+    assert_eq!(governor(&harness), "powersave");
+
+    harness.post("/coffee").await;
+    assert_eq!(governor(&harness), "schedutil");
+    let status = harness.status().await;
+    assert_eq!(status["idle_stages"].as_array().unwrap().len(), 0);
+
+    // A new idle period starts the stages over
+    harness.post("/chill").await;
+    let status = harness.status().await;
+    assert_eq!(status["idle_stages"][0]["at"], "2025-01-01T00:06:01Z");
+    assert_eq!(status["idle_stages"][0]["done"], false);
+}
+
+fn governor_stage(name: &str, after_minutes: u64, governor: &str) -> IdleStageConfig {
+    IdleStageConfig {
+        name: name.to_string(),
+        after_minutes,
+        stop_services: Vec::new(),
+        cpu_governor: Some(governor.to_string()),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn stacked_governor_stages_restore_the_original_governor() {
+    let stages = IdlePolicyConfig {
+        stages: vec![governor_stage("powersave", 2, "powersave"), governor_stage("conservative", 4, "conservative")],
+    };
+    let harness = Harness::start_configured(10, |state| state.with_idle_policy(stages)).await;
+
+    harness.advance(Duration::from_secs(241)).await;
+    assert_eq!(governor(&harness), "conservative");
+
+    harness.post("/coffee").await;
+    assert_eq!(governor(&harness), "schedutil");
+}
+
+#[tokio::test(start_paused = true)]
+async fn activity_cancels_a_stage_while_its_services_stop() {
+    let slow_stop = SimulationConfig {
+        services: [(
+            "comfy-unsafe".to_string(),
+            SimulatedServiceConfig { stop_delay_ms: Some(60_000), ..Default::default() },
+        )]
+        .into(),
+        ..simulation()
+    };
+    let stages = IdlePolicyConfig {
+        stages: vec![IdleStageConfig {
+            stop_services: vec!["comfy-unsafe".to_string()],
+            ..governor_stage("wind-down", 2, "powersave")
+        }],
+    };
+    let harness = Harness::start_full(10, slow_stop, |state| state.with_idle_policy(stages)).await;
+    harness.state.backend.start_service("comfy-unsafe.service").await.unwrap();
+
+    // The stage is stopping comfy-unsafe when activity resumes
+    harness.advance(Duration::from_secs(121)).await;
+    let (code, _) = harness.post("/coffee").await;
+    assert_eq!(code, axum::http::StatusCode::OK);
+    assert_eq!(harness.status().await["phase"], "held");
+
+    harness.advance(Duration::from_secs(120)).await;
+    assert!(harness.state.backend.is_service_active("comfy-unsafe.service").await.unwrap());
+    assert_eq!(governor(&harness), "schedutil");
+}
+
+#[test]
+fn stages_later_than_the_longest_countdown_are_rejected() {
+    assert!(policy().validate().is_ok());
+
+    let mut policy = policy();
+    policy.stages[0].after_minutes = u64::MAX;
+    assert!(policy.validate().unwrap_err().contains("stop-heavy-services"));
+}
+
+#[test]
+fn failed_governor_change_restores_the_switched_cpus() {
+    let root = std::env::temp_dir().join(format!("order-coffee-governor-{}", std::process::id()));
+    let cpus = root.join("devices/system/cpu");
+    std::fs::create_dir_all(cpus.join("cpu0/cpufreq")).unwrap();
+    std::fs::write(cpus.join("cpu0/cpufreq/scaling_governor"), "performance\n").unwrap();
+    // Unreadable governor file
+    std::fs::create_dir_all(cpus.join("cpu1/cpufreq/scaling_governor")).unwrap();
+
+    assert!(write_cpu_governor(&root, "powersave").is_err());
+    let governor = std::fs::read_to_string(cpus.join("cpu0/cpufreq/scaling_governor")).unwrap();
+    assert_eq!(governor.trim(), "performance");
+
+    let _ = std::fs::remove_dir_all(root);
+}
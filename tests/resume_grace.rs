@@ -0,0 +1,85 @@
+//! Post-resume grace period holding the timer after a wake-up
+
+mod common;
+
+use std::time::Duration;
+use axum::http::StatusCode;
+use clap::Parser;
+
+use common::Harness;
+use order_coffee::Config;
+
+/// Start with a one minute timer and let the machine sleep (T+60s) and wake up (T+90s)
+async fn resumed(grace_seconds: u64) -> Harness {
+    let harness = Harness::start_configured(1, |state| state.with_resume_grace(Duration::from_secs(grace_seconds))).await;
+    harness.advance(Duration::from_secs(61)).await;
+    harness.advance(Duration::from_secs(30)).await;
+    assert_eq!(harness.history("sleep").await.len(), 1);
+    harness
+}
+
+#[tokio::test(start_paused = true)]
+async fn timer_is_held_during_the_grace_period() {
+    let harness = resumed(120).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "grace");
+    assert_eq!(status["timer_active"], false);
+    assert_eq!(status["grace_remaining_seconds"], 119);
+    assert_eq!(status["grace_ends_at"], "2025-01-01T00:03:30Z");
+
+    // State changes during the grace period don't start a countdown
+    harness.post("/coffee").await;
+    harness.post("/chill").await;
+    assert_eq!(harness.status().await["phase"], "grace");
+
+    harness.advance(Duration::from_secs(120)).await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["grace_remaining_seconds"], serde_json::Value::Null);
+    assert_eq!(status["timer_remaining_seconds"], 59);
+}
+
+#[tokio::test(start_paused = true)]
+async fn grace_period_can_be_cancelled() {
+    let harness = resumed(120).await;
+
+    let (code, body) = harness.post("/timer/grace/cancel").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["message"], "Grace period ended");
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["timer_remaining_seconds"], 60);
+    assert_eq!(status["last_action"], "grace-cancel");
+
+    let (code, _) = harness.post("/timer/grace/cancel").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+}
+
+#[tokio::test(start_paused = true)]
+async fn active_states_hold_the_timer_once_the_grace_period_ends() {
+    let harness = resumed(120).await;
+
+    harness.post("/coffee").await;
+    harness.post("/timer/grace/cancel").await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "held");
+    assert_eq!(status["timer_active"], false);
+}
+
+#[tokio::test(start_paused = true)]
+async fn an_overlong_grace_period_is_skipped_instead_of_overflowing() {
+    let harness = resumed(u64::MAX).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["grace_ends_at"], serde_json::Value::Null);
+    assert!(harness.state.start_grace(Duration::from_secs(u64::MAX)).is_err());
+}
+
+#[test]
+fn resume_grace_longer_than_a_week_is_rejected() {
+    let config = Config::try_parse_from(["order-coffee", "--resume-grace-seconds", "604801"]).unwrap();
+    assert!(config.validate().unwrap_err().contains("--resume-grace-seconds"));
+}
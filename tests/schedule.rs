@@ -0,0 +1,143 @@
+//! Time-of-day schedules: work-hours holds, quiet-hours timer and timed services
+
+mod common;
+
+use std::time::Duration;
+
+use chrono::{Local, TimeZone, Utc};
+use common::Harness;
+use order_coffee::services::{
+    QuietHoursConfig, ScheduleConfig, ScheduledServiceAction, ScheduledServiceConfig, TimeOfDay,
+    WorkHoursConfig,
+};
+
+/// Local time of day `minutes` after the harness start (2025-01-01T00:00:00Z)
+fn at(minutes: i64) -> TimeOfDay {
+    let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap() + chrono::Duration::minutes(minutes);
+    TimeOfDay(start.with_timezone(&Local).time())
+}
+
+#[tokio::test(start_paused = true)]
+async fn work_hours_hold_the_machine_awake() {
+    let schedule = ScheduleConfig {
+        work_hours: vec![WorkHoursConfig {
+            name: "office".to_string(),
+            days: Vec::new(),
+            from: at(10),
+            to: at(40),
+        }],
+        ..ScheduleConfig::default()
+    };
+    let harness = Harness::start_configured(30, |state| state.with_schedule(schedule)).await;
+    assert_eq!(harness.status().await["phase"], "counting_down");
+
+    harness.advance(Duration::from_secs(11 * 60)).await;
+    let status = harness.status().await;
+    assert_eq!(status["states"]["holds"]["schedule:office"]["until"], "2025-01-01T00:40:00Z");
+    assert_eq!(status["timer_active"], false);
+
+    harness.advance(Duration::from_secs(30 * 60)).await;
+    let status = harness.status().await;
+    assert!(status["states"]["holds"].as_object().unwrap().is_empty());
+    assert_eq!(status["phase"], "counting_down");
+    // Started when the window closed a minute ago
+    assert_eq!(status["timer_remaining_seconds"], 29 * 60);
+}
+
+#[tokio::test(start_paused = true)]
+async fn quiet_hours_shorten_the_timer() {
+    let schedule = ScheduleConfig {
+        quiet_hours: vec![QuietHoursConfig {
+            days: Vec::new(),
+            from: at(-60),
+            to: at(20),
+            timer_minutes: 5,
+        }],
+        ..ScheduleConfig::default()
+    };
+    let harness = Harness::start_configured(30, |state| state.with_schedule(schedule)).await;
+    let status = harness.status().await;
+    assert_eq!(status["quiet_timer_seconds"], 300);
+    assert_eq!(status["timer_remaining_seconds"], 300);
+
+    // Back to the configured timer once the quiet hours are over
+    harness.post("/coffee").await;
+    harness.advance(Duration::from_secs(21 * 60)).await;
+    harness.post("/chill").await;
+    let status = harness.status().await;
+    assert!(status["quiet_timer_seconds"].is_null());
+    assert_eq!(status["timer_remaining_seconds"], 30 * 60);
+}
+
+#[tokio::test(start_paused = true)]
+async fn changing_the_timer_during_quiet_hours_keeps_the_countdown() {
+    let schedule = ScheduleConfig {
+        quiet_hours: vec![QuietHoursConfig {
+            days: Vec::new(),
+            from: at(-60),
+            to: at(60),
+            timer_minutes: 5,
+        }],
+        ..ScheduleConfig::default()
+    };
+    let harness = Harness::start_configured(30, |state| state.with_schedule(schedule)).await;
+    harness.post("/timer/extend?minutes=10").await;
+    harness.advance(Duration::from_secs(120)).await;
+
+    // The quiet hours timer stays in effect, so does the extension
+    let (_, body) = harness.put_json("/config/timer", serde_json::json!({ "seconds": 3600 })).await;
+    assert_eq!(body["timer_remaining_seconds"], 13 * 60);
+}
+
+#[tokio::test(start_paused = true)]
+async fn services_start_and_stop_on_schedule() {
+    let entry = |action, minutes| ScheduledServiceConfig {
+        service: "ollama".to_string(),
+        action,
+        at: at(minutes),
+        days: Vec::new(),
+    };
+    let schedule = ScheduleConfig {
+        services: vec![entry(ScheduledServiceAction::Start, 5), entry(ScheduledServiceAction::Stop, 20)],
+        ..ScheduleConfig::default()
+    };
+    let harness = Harness::start_configured(60, |state| state.with_schedule(schedule)).await;
+
+    harness.advance(Duration::from_secs(6 * 60)).await;
+    assert_eq!(harness.status().await["states"]["services"]["ollama"], true);
+    assert!(harness.state.backend.is_service_active("ollama.service").await.unwrap());
+    assert_eq!(harness.status().await["timer_active"], false);
+
+    harness.advance(Duration::from_secs(15 * 60)).await;
+    assert_eq!(harness.status().await["states"]["services"]["ollama"], false);
+    assert!(!harness.state.backend.is_service_active("ollama.service").await.unwrap());
+    assert_eq!(harness.status().await["phase"], "counting_down");
+}
+
+#[test]
+fn schedule_tables_parse_days_and_times() {
+    let schedule: ScheduleConfig = toml::from_str(r#"
+        [[work_hours]]
+        name = "office"
+        days = ["mon", "tue", "wed", "thu", "fri"]
+        from = "09:00"
+        to = "18:00"
+
+        [[quiet_hours]]
+        from = "23:00"
+        to = "07:00"
+        timer_minutes = 5
+    "#).unwrap();
+    assert_eq!(schedule.work_hours[0].days.len(), 5);
+    assert_eq!(schedule.quiet_hours[0].to, TimeOfDay(chrono::NaiveTime::from_hms_opt(7, 0, 0).unwrap()));
+    assert!(schedule.validate().is_ok());
+
+    for timer_minutes in [0, u64::MAX] {
+        let mut schedule = schedule.clone();
+        schedule.quiet_hours[0].timer_minutes = timer_minutes;
+        assert!(schedule.validate().is_err());
+    }
+
+    let invalid = toml::from_str::<ScheduleConfig>("[[quiet_hours]]\nfrom = \"25:00\"\nto = \"07:00\"\ntimer_minutes = 5\n");
+    assert!(invalid.is_err());
+}
@@ -0,0 +1,74 @@
+//! Reading and following the log of a managed service
+
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Response, StatusCode},
+};
+use futures::StreamExt;
+use common::Harness;
+use order_coffee::services::MAX_LOG_LINES;
+
+const UNIT: &str = "ollama.service";
+
+async fn text(response: Response<Body>) -> String {
+    let body = to_bytes(response.into_body(), usize::MAX).await.expect("readable body");
+    String::from_utf8(body.to_vec()).expect("UTF-8 body")
+}
+
+#[tokio::test(start_paused = true)]
+async fn logs_are_returned_as_plain_text() {
+    let harness = Harness::start(10).await;
+    harness.host().start_service(UNIT).await.unwrap();
+    harness.host().stop_service(UNIT).await.unwrap();
+
+    let response = harness.get_response("/service/ollama/logs?lines=2", "*/*").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+    let lines: Vec<String> = text(response).await.lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("Started") && lines[1].ends_with("Stopped"), "{:?}", lines);
+}
+
+#[tokio::test(start_paused = true)]
+async fn followed_logs_are_streamed_as_events() {
+    let harness = Harness::start(10).await;
+    harness.host().start_service(UNIT).await.unwrap();
+
+    let response = harness.get_response("/service/ollama/logs?lines=1&follow=true", "text/event-stream").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+    let mut events = response.into_body().into_data_stream();
+    let mut next_event = async || String::from_utf8(events.next().await.unwrap().unwrap().to_vec()).unwrap();
+
+    let history = next_event().await;
+    assert!(history.starts_with("data: ") && history.trim_end().ends_with("Started"), "{}", history);
+
+    // Lines written later are sent as they come
+    harness.host().stop_service(UNIT).await.unwrap();
+    let live = next_event().await;
+    assert!(live.starts_with("data: ") && live.trim_end().ends_with("Stopped"), "{}", live);
+}
+
+#[tokio::test(start_paused = true)]
+async fn history_is_capped_at_the_maximum() {
+    let harness = Harness::start(10).await;
+    // Three lines per round trip, more than a request may ask for
+    for _ in 0..=MAX_LOG_LINES / 3 {
+        harness.host().start_service(UNIT).await.unwrap();
+        harness.host().stop_service(UNIT).await.unwrap();
+    }
+
+    let response = harness.get_response("/service/ollama/logs?lines=1000000", "*/*").await;
+    let body = text(response).await;
+    assert_eq!(body.lines().count(), MAX_LOG_LINES);
+    assert!(body.trim_end().ends_with("Stopped"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn logs_of_unknown_units_are_refused() {
+    let harness = Harness::start(10).await;
+    let response = harness.get_response("/service/sshd/logs", "*/*").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
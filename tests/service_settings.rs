@@ -0,0 +1,86 @@
+//! Memory requirement and priority of managed services from `[services.<name>]`
+
+mod common;
+
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use common::{simulation, Harness};
+use order_coffee::services::{parse_mem_available, AdmissionPolicy, ServiceSettings, SimulationConfig};
+
+fn machine_with_mb(memory_total_mb: u64) -> SimulationConfig {
+    SimulationConfig { memory_total_mb, ..simulation() }
+}
+
+async fn start_with_settings(memory_total_mb: u64, settings: &[(&str, ServiceSettings)]) -> Harness {
+    let settings: HashMap<String, ServiceSettings> = settings
+        .iter()
+        .map(|(name, settings)| (name.to_string(), settings.clone()))
+        .collect();
+    Harness::start_full(10, machine_with_mb(memory_total_mb), |state| {
+        state
+            .with_admission_policy(AdmissionPolicy::Preempt)
+            .with_service_settings(settings)
+    })
+    .await
+}
+
+fn requiring_mb(mb: u64) -> ServiceSettings {
+    ServiceSettings { min_available_memory_mb: Some(mb), priority: None }
+}
+
+#[tokio::test(start_paused = true)]
+async fn services_start_on_low_memory_without_a_configured_requirement() {
+    // Default admission policy (reject) and no [services] tables
+    let harness = Harness::start_with(10, machine_with_mb(512)).await;
+
+    for name in ["ollama", "comfy-unsafe"] {
+        let (code, _) = harness.post(&format!("/service/{}/start", name)).await;
+        assert_eq!(code, StatusCode::OK, "{}", name);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn configured_memory_requirement_is_checked() {
+    let harness = start_with_settings(6144, &[("ollama", requiring_mb(8192))]).await;
+
+    let (code, body) = harness.post("/service/ollama/start").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+    assert!(body["message"].as_str().unwrap().contains("8.0 GiB required"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn configured_priority_protects_a_service_from_preemption() {
+    let harness = start_with_settings(10240, &[
+        ("ollama", ServiceSettings { min_available_memory_mb: None, priority: Some(30) }),
+        ("comfy-unsafe", requiring_mb(8192)),
+    ]).await;
+    let (code, _) = harness.post("/service/ollama/start").await;
+    assert_eq!(code, StatusCode::OK);
+
+    // comfy-unsafe (priority 20) may not stop ollama for its 8 GiB any more
+    let (code, _) = harness.post("/service/comfy-unsafe/start").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+    assert_eq!(harness.status().await["states"]["services"]["ollama"], true);
+}
+
+#[tokio::test(start_paused = true)]
+async fn oversized_simulated_memory_is_rejected_and_does_not_overflow() {
+    assert!(machine_with_mb(u64::MAX).validate().unwrap_err().contains("memory_total_mb"));
+    assert!(machine_with_mb(32768).validate().is_ok());
+
+    let harness = start_with_settings(u64::MAX, &[("ollama", requiring_mb(8192))]).await;
+    let (code, _) = harness.post("/service/ollama/start").await;
+    assert_eq!(code, StatusCode::OK);
+}
+
+#[test]
+fn available_memory_is_read_from_meminfo() {
+    let meminfo = |available: &str| format!("MemTotal: 32768000 kB\nMemAvailable: {} kB\n", available);
+    assert_eq!(parse_mem_available(&meminfo("2048")), Ok(2048 * 1024));
+    assert!(parse_mem_available(&meminfo("many")).is_err());
+    // Would not fit in bytes
+    let error = parse_mem_available(&meminfo(&u64::MAX.to_string())).unwrap_err();
+    assert!(error.contains("Invalid MemAvailable"), "{}", error);
+    assert!(parse_mem_available("MemTotal: 32768000 kB\n").is_err());
+}
@@ -0,0 +1,73 @@
+//! Restarting services that die while marked active
+
+mod common;
+
+use std::{collections::HashMap, time::Duration};
+
+use axum::http::StatusCode;
+use common::{simulation, Harness, WATCHDOG_INTERVAL, WATCHDOG_MAX_RESTARTS};
+use order_coffee::services::{SimulatedServiceConfig, SimulationConfig};
+
+/// A simulation in which ollama never manages to start
+fn ollama_failing() -> SimulationConfig {
+    let ollama = SimulatedServiceConfig { failure_rate: Some(1.0), ..SimulatedServiceConfig::default() };
+    SimulationConfig { services: HashMap::from([("ollama".to_string(), ollama)]), ..simulation() }
+}
+
+/// Wait for the next watchdog check and the recovery it runs
+async fn next_check(harness: &Harness) {
+    harness.advance(WATCHDOG_INTERVAL).await;
+    harness.advance(Duration::from_secs(5)).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn crashed_service_is_restarted() {
+    let harness = Harness::start(10).await;
+    let (code, _) = harness.post("/service/ollama/start").await;
+    assert_eq!(code, StatusCode::OK);
+
+    harness.host().force_kill("ollama").await.unwrap();
+    assert!(!harness.host().is_service_active("ollama.service").await.unwrap());
+
+    next_check(&harness).await;
+    assert!(harness.host().is_service_active("ollama.service").await.unwrap());
+    let status = harness.status().await;
+    assert_eq!(status["states"]["services"]["ollama"], true);
+    assert!(status["states"]["errors"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn restarts_stop_at_the_limit() {
+    let harness = Harness::start_with(10, ollama_failing()).await;
+    // Marked active, but the unit is not running
+    harness.state.update_state("test", |state| state.set_service("ollama", true)).unwrap();
+
+    for _ in 0..WATCHDOG_MAX_RESTARTS {
+        next_check(&harness).await;
+        let status = harness.status().await;
+        assert_eq!(status["states"]["services"]["ollama"], true);
+        assert!(status["states"]["errors"].as_array().unwrap().is_empty());
+        assert!(status["timer_remaining_seconds"].is_null());
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn service_is_marked_failed_after_the_last_restart() {
+    let harness = Harness::start_with(10, ollama_failing()).await;
+    harness.state.update_state("test", |state| state.set_service("ollama", true)).unwrap();
+
+    for _ in 0..=WATCHDOG_MAX_RESTARTS {
+        next_check(&harness).await;
+    }
+    let status = harness.status().await;
+    assert_eq!(status["states"]["services"]["ollama"], false);
+    let errors = status["states"]["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].as_str().unwrap().contains("3 restart attempts failed"), "{}", errors[0]);
+    // Nothing keeps the machine awake any more
+    assert!(status["timer_remaining_seconds"].as_u64().is_some());
+
+    // The watchdog leaves the failed service alone
+    next_check(&harness).await;
+    assert_eq!(harness.status().await["states"]["errors"].as_array().unwrap().len(), 1);
+}
@@ -0,0 +1,58 @@
+//! Idle actions the machine can't perform are refused
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{simulation, Harness};
+use order_coffee::services::{IdleAction, SimulationConfig, SleepCapabilities};
+
+/// A machine with suspend but without a swap partition to hibernate to
+fn without_hibernation() -> SimulationConfig {
+    SimulationConfig {
+        power_states: vec!["freeze".to_string(), "mem".to_string()],
+        disk_modes: Vec::new(),
+        ..simulation()
+    }
+}
+
+#[test]
+fn hibernation_is_rejected_at_startup_when_disabled() {
+    let root = std::env::temp_dir().join(format!("order-coffee-power-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("power")).unwrap();
+    std::fs::write(root.join("power/state"), "freeze mem disk\n").unwrap();
+    std::fs::write(root.join("power/disk"), "[disabled]\n").unwrap();
+
+    let capabilities = SleepCapabilities::read(&root);
+    assert!(capabilities.check(IdleAction::Suspend, None).is_ok());
+    for action in [IdleAction::Hibernate, IdleAction::HybridSleep, IdleAction::SuspendThenHibernate] {
+        let error = capabilities.check(action, None).unwrap_err();
+        assert!(error.contains("not supported") && error.contains("power/disk: [disabled]"), "{}", error);
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn hybrid_sleep_needs_the_suspend_disk_mode() {
+    let capabilities = SleepCapabilities {
+        states: vec!["mem".to_string(), "disk".to_string()],
+        disk_modes: vec!["platform".to_string(), "shutdown".to_string()],
+    };
+    assert!(capabilities.check(IdleAction::Hibernate, None).is_ok());
+    assert!(capabilities.check(IdleAction::HybridSleep, None).is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn unsupported_action_override_is_rejected_by_suspend() {
+    let harness = Harness::start_with(10, without_hibernation()).await;
+
+    for action in ["hibernate", "hybrid-sleep"] {
+        let (code, body) = harness.post(&format!("/suspend?action={}", action)).await;
+        assert_eq!(code, StatusCode::BAD_REQUEST, "{}", action);
+        assert!(body["message"].as_str().unwrap().contains("not supported"), "{}", body);
+    }
+    assert!(harness.status().await["timer_remaining_seconds"].as_u64().unwrap() > 500);
+
+    let (code, _) = harness.post("/suspend?action=suspend").await;
+    assert_eq!(code, StatusCode::OK);
+}
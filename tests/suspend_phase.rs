@@ -0,0 +1,72 @@
+//! Suspension state machine transitions
+
+mod common;
+
+use std::time::Duration;
+use order_coffee::{services::IdleAction, state::SuspendPhase};
+
+use common::Harness;
+
+#[tokio::test(start_paused = true)]
+async fn status_reports_phase_and_entry_time() {
+    let harness = Harness::start(1).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["phase_entered_at"], "2025-01-01T00:00:00Z");
+
+    harness.advance(Duration::from_secs(10)).await;
+    harness.post("/coffee").await;
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "held");
+    assert_eq!(status["phase_entered_at"], "2025-01-01T00:00:10Z");
+}
+
+#[tokio::test(start_paused = true)]
+async fn every_transition_of_a_sleep_cycle_is_broadcast() {
+    let harness = Harness::start(1).await;
+    let mut phase_rx = harness.state.phase_tx.subscribe();
+
+    harness.advance(Duration::from_secs(91)).await;
+
+    let mut transitions = Vec::new();
+    while let Ok(event) = phase_rx.try_recv() {
+        transitions.push((event.from, event.to));
+    }
+    assert_eq!(transitions, vec![
+        (SuspendPhase::CountingDown, SuspendPhase::Suspending),
+        (SuspendPhase::Suspending, SuspendPhase::Suspended),
+        (SuspendPhase::Suspended, SuspendPhase::Resuming),
+        (SuspendPhase::Resuming, SuspendPhase::CountingDown),
+    ]);
+}
+
+#[test]
+fn invalid_transitions_are_rejected() {
+    assert!(!SuspendPhase::CountingDown.can_transition_to(SuspendPhase::Suspended));
+    assert!(!SuspendPhase::CountingDown.can_transition_to(SuspendPhase::Grace));
+    assert!(!SuspendPhase::Resuming.can_transition_to(SuspendPhase::Resuming));
+    assert!(SuspendPhase::CountingDown.can_transition_to(SuspendPhase::Suspending));
+}
+
+#[tokio::test(start_paused = true)]
+async fn custom_idle_action_starts_the_next_countdown() {
+    let harness = Harness::start_configured(1, |state| {
+        state.with_idle_action(IdleAction::Custom, Some("true".to_string()))
+    })
+    .await;
+    let mut phase_rx = harness.state.phase_tx.subscribe();
+
+    harness.advance(Duration::from_secs(61)).await;
+
+    let mut transitions = Vec::new();
+    while let Ok(event) = phase_rx.try_recv() {
+        transitions.push((event.from, event.to));
+    }
+    assert_eq!(transitions, vec![
+        (SuspendPhase::CountingDown, SuspendPhase::Suspending),
+        (SuspendPhase::Suspending, SuspendPhase::CountingDown),
+    ]);
+    assert_eq!(harness.status().await["timer_active"], true);
+}
@@ -0,0 +1,77 @@
+//! Verification that the machine really slept, with retries on failure
+
+mod common;
+
+use std::time::Duration;
+
+use common::{simulation, Harness};
+use order_coffee::services::SimulationConfig;
+
+/// Every idle action "succeeds" but the simulated machine stays awake
+fn insomnia() -> SimulationConfig {
+    SimulationConfig {
+        suspend_failure_rate: 1.0,
+        ..simulation()
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_sleep_is_recorded_and_retried_with_backoff() {
+    let harness = Harness::start_with(2, insomnia()).await;
+
+    // Idle action at T+120s, still awake at T+180s
+    harness.advance(Duration::from_secs(121)).await;
+    assert_eq!(harness.status().await["phase"], "suspended");
+    harness.advance(Duration::from_secs(60)).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["timer_remaining_seconds"], 29);
+    let errors = status["states"]["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].as_str().unwrap().starts_with("System suspension failed (attempt 1)"));
+
+    let failures = harness.history("suspend_failed").await;
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0]["action"], "suspend");
+    assert_eq!(failures[0]["attempt"], 1);
+    assert_eq!(failures[0]["retry_in_seconds"], 30);
+
+    // Second attempt at T+210s, noticed at T+270s, the backoff doubles
+    harness.advance(Duration::from_secs(90)).await;
+    let failures = harness.history("suspend_failed").await;
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[1]["attempt"], 2);
+    assert_eq!(failures[1]["retry_in_seconds"], 60);
+    let status = harness.status().await;
+    assert_eq!(status["timer_remaining_seconds"], 59);
+    assert_eq!(status["states"]["errors"].as_array().unwrap().len(), 1);
+
+    // Capped at the timer duration
+    harness.advance(Duration::from_secs(120)).await;
+    assert_eq!(harness.history("suspend_failed").await[2]["retry_in_seconds"], 120);
+}
+
+#[tokio::test(start_paused = true)]
+async fn active_states_hold_the_retry() {
+    let harness = Harness::start_with(1, insomnia()).await;
+    harness.advance(Duration::from_secs(61)).await;
+
+    harness.post("/coffee").await;
+    harness.advance(Duration::from_secs(60)).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "held");
+    assert_eq!(status["timer_active"], false);
+    assert_eq!(harness.history("suspend_failed").await.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn successful_sleep_is_not_reported_as_failed() {
+    let harness = Harness::start(1).await;
+    harness.advance(Duration::from_secs(200)).await;
+
+    assert!(!harness.history("sleep").await.is_empty());
+    assert!(harness.history("suspend_failed").await.is_empty());
+    assert!(harness.status().await["states"]["errors"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,155 @@
+//! Suspension timer behaviour, driven through the HTTP API with paused time
+
+mod common;
+
+use std::time::Duration;
+use axum::http::StatusCode;
+
+use common::Harness;
+
+#[tokio::test(start_paused = true)]
+async fn timer_starts_when_everything_is_idle() {
+    let harness = Harness::start(1).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], true);
+    assert_eq!(status["timer_remaining_seconds"], 60);
+    assert_eq!(status["inhibitor_active"], false);
+}
+
+#[tokio::test(start_paused = true)]
+async fn timer_counts_down_with_virtual_time() {
+    let harness = Harness::start(1).await;
+
+    harness.advance(Duration::from_secs(20)).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], true);
+    assert_eq!(status["timer_remaining_seconds"], 40);
+}
+
+#[tokio::test(start_paused = true)]
+async fn timer_cancelled_when_coffee_turns_on_at_t_minus_5s() {
+    let harness = Harness::start(1).await;
+
+    harness.advance(Duration::from_secs(55)).await;
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 5);
+
+    let (code, body) = harness.post("/coffee").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["states"]["coffee"], true);
+
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], false);
+    assert_eq!(status["timer_remaining_seconds"], serde_json::Value::Null);
+    assert_eq!(status["inhibitor_active"], true);
+
+    // Well past the original deadline, nothing was suspended
+    harness.advance(Duration::from_secs(120)).await;
+    assert_eq!(harness.status().await["timer_active"], false);
+    assert!(!harness.state.is_suspended().unwrap());
+    assert!(harness.history("sleep").await.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn chill_restarts_the_full_countdown() {
+    let harness = Harness::start(1).await;
+
+    harness.advance(Duration::from_secs(30)).await;
+    harness.post("/coffee").await;
+    harness.advance(Duration::from_secs(10)).await;
+
+    let (code, _) = harness.post("/chill").await;
+    assert_eq!(code, StatusCode::OK);
+
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], true);
+    assert_eq!(status["timer_remaining_seconds"], 60);
+    assert_eq!(status["last_action"], "chill");
+}
+
+#[tokio::test(start_paused = true)]
+async fn running_service_holds_off_the_timer() {
+    let harness = Harness::start(1).await;
+
+    let (code, body) = harness.post("/service/ollama/start").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["states"]["services"]["ollama"], true);
+
+    harness.advance(Duration::from_secs(120)).await;
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], false);
+    assert!(harness.history("sleep").await.is_empty());
+
+    harness.post("/service/ollama/stop").await;
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 60);
+}
+
+#[tokio::test(start_paused = true)]
+async fn expiry_suspends_and_resume_restarts_the_countdown() {
+    let harness = Harness::start(1).await;
+
+    // Timer fires at T+60s, the simulated sleep lasts 30s
+    harness.advance(Duration::from_secs(61)).await;
+    assert!(harness.state.is_suspended().unwrap());
+    assert_eq!(harness.status().await["timer_active"], false);
+
+    harness.advance(Duration::from_secs(30)).await;
+    assert!(!harness.state.is_suspended().unwrap());
+
+    let sleeps = harness.history("sleep").await;
+    assert_eq!(sleeps.len(), 1);
+    assert_eq!(sleeps[0]["initiated_by_us"], true);
+    assert_eq!(sleeps[0]["duration_seconds"], 30);
+    assert_eq!(sleeps[0]["resumed_at"], "2025-01-01T00:01:30Z");
+
+    // Resumed at T+90s, the fresh countdown has been running for a second
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], true);
+    assert_eq!(status["timer_remaining_seconds"], 59);
+}
+
+#[tokio::test(start_paused = true)]
+async fn uptime_follows_the_injected_clock() {
+    let harness = Harness::start(10).await;
+
+    harness.advance(Duration::from_secs(125)).await;
+
+    assert_eq!(harness.status().await["uptime"], "2m 5s");
+}
+
+#[tokio::test(start_paused = true)]
+async fn status_reports_absolute_suspend_time() {
+    let harness = Harness::start(10).await;
+
+    harness.advance(Duration::from_secs(90)).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["suspend_at"], "2025-01-01T00:10:00Z");
+    assert_eq!(status["timer_remaining_seconds"], 510);
+
+    harness.post("/coffee").await;
+    assert_eq!(harness.status().await["suspend_at"], serde_json::Value::Null);
+}
+
+#[tokio::test(start_paused = true)]
+async fn running_countdown_does_not_rewrite_timer_state() {
+    let harness = Harness::start(10).await;
+    let timer_rx = harness.state.timer_update_tx.subscribe();
+
+    harness.advance(Duration::from_secs(300)).await;
+
+    assert!(!timer_rx.has_changed().unwrap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn state_updates_keep_errors_of_other_components() {
+    let harness = Harness::start(1).await;
+    harness.state.add_error("Failed to list foreign inhibitors: no logind".to_string()).unwrap();
+
+    harness.post("/coffee").await;
+    harness.post("/chill").await;
+
+    let errors = harness.status().await["states"]["errors"].clone();
+    assert_eq!(errors, serde_json::json!(["Failed to list foreign inhibitors: no logind"]));
+}
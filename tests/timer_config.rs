@@ -0,0 +1,119 @@
+//! Changing the idle timer duration at runtime
+
+mod common;
+
+use std::time::Duration;
+use axum::http::StatusCode;
+use clap::Parser;
+use serde_json::json;
+
+use common::{temp_state_file, Harness};
+use order_coffee::Config;
+
+#[tokio::test(start_paused = true)]
+async fn get_returns_the_configured_duration() {
+    let harness = Harness::start(10).await;
+
+    let (code, body) = harness.get("/config/timer").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["seconds"], 600);
+    assert_eq!(body["timer_remaining_seconds"], 600);
+}
+
+#[tokio::test(start_paused = true)]
+async fn running_countdown_is_recomputed_against_the_new_duration() {
+    let harness = Harness::start(10).await;
+    harness.advance(Duration::from_secs(120)).await;
+
+    // 2 of 30 minutes have passed
+    let (code, body) = harness.put_json("/config/timer", json!({ "seconds": 1800 })).await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["seconds"], 1800);
+    assert_eq!(body["timer_remaining_seconds"], 1680);
+
+    // Shrinking below the time already counted down suspends right away
+    harness.put_json("/config/timer", json!({ "seconds": 60 })).await;
+    harness.advance(Duration::from_secs(1)).await;
+    assert!(harness.state.is_suspended().unwrap());
+    assert_eq!(harness.status().await["last_action"], "timer-config");
+}
+
+#[tokio::test(start_paused = true)]
+async fn new_duration_applies_to_the_next_countdown() {
+    let harness = Harness::start(10).await;
+    harness.post("/coffee").await;
+
+    harness.put_json("/config/timer", json!({ "seconds": 90 })).await;
+    assert_eq!(harness.status().await["timer_active"], false);
+
+    harness.post("/chill").await;
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 90);
+}
+
+#[tokio::test(start_paused = true)]
+async fn out_of_range_durations_are_rejected() {
+    let harness = Harness::start(10).await;
+
+    let (code, _) = harness.put_json("/config/timer", json!({ "seconds": 5 })).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    assert_eq!(harness.get("/config/timer").await.1["seconds"], 600);
+}
+
+#[tokio::test(start_paused = true)]
+async fn duration_survives_a_restart() {
+    let state_file = temp_state_file("timer-config");
+
+    let harness = Harness::start_configured(10, |state| state.with_state_file(Some(state_file.clone()))).await;
+    harness.put_json("/config/timer", json!({ "seconds": 1500 })).await;
+    drop(harness);
+
+    // --timer is overridden by the saved value
+    let harness = Harness::start_configured(10, |state| state.with_state_file(Some(state_file.clone()))).await;
+    assert_eq!(harness.get("/config/timer").await.1["seconds"], 1500);
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 1500);
+
+    let _ = std::fs::remove_file(state_file);
+}
+
+#[tokio::test(start_paused = true)]
+async fn out_of_range_saved_duration_is_ignored() {
+    let state_file = temp_state_file("timer-config-range");
+    std::fs::write(&state_file, r#"{ "timer_seconds": 0 }"#).unwrap();
+
+    let harness = Harness::start_configured(10, |state| state.with_state_file(Some(state_file.clone()))).await;
+    assert_eq!(harness.get("/config/timer").await.1["seconds"], 600);
+    assert_eq!(harness.status().await["phase"], "counting_down");
+
+    let _ = std::fs::remove_file(state_file);
+}
+
+#[tokio::test(start_paused = true)]
+async fn extension_is_kept_when_the_duration_changes() {
+    let harness = Harness::start(10).await;
+    harness.advance(Duration::from_secs(120)).await;
+    harness.post("/timer/extend?minutes=5").await;
+
+    // 2 of 10 + 5 minutes have passed, the 10 become 20
+    let (_, body) = harness.put_json("/config/timer", json!({ "seconds": 1200 })).await;
+    assert_eq!(body["timer_remaining_seconds"], 1380);
+}
+
+#[tokio::test(start_paused = true)]
+async fn snooze_is_kept_when_the_duration_changes() {
+    let harness = Harness::start(10).await;
+    harness.advance(Duration::from_secs(120)).await;
+    harness.post("/timer/snooze?minutes=3").await;
+
+    let (_, body) = harness.put_json("/config/timer", json!({ "seconds": 1200 })).await;
+    assert_eq!(body["timer_remaining_seconds"], 180);
+}
+
+#[test]
+fn timer_arguments_outside_the_put_bounds_are_rejected() {
+    for minutes in ["0", "10081", "18446744073709551615"] {
+        let config = Config::try_parse_from(["order-coffee", "--timer", minutes]).unwrap();
+        assert!(config.validate().unwrap_err().contains("--timer"), "{}", minutes);
+    }
+    let config = Config::try_parse_from(["order-coffee", "--timer", "10080"]).unwrap();
+    assert!(config.validate().is_ok());
+}
@@ -0,0 +1,151 @@
+//! Timer control endpoints: extend, snooze, reset, cancel and suspend-now
+
+mod common;
+
+use std::time::Duration;
+use axum::http::StatusCode;
+
+use common::Harness;
+
+#[tokio::test(start_paused = true)]
+async fn extend_pushes_the_deadline_back() {
+    let harness = Harness::start(10).await;
+    harness.advance(Duration::from_secs(60)).await;
+
+    let (code, body) = harness.post("/timer/extend?minutes=5").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["message"], "Timer extended by 5 minutes, suspending in 840s");
+
+    let status = harness.status().await;
+    assert_eq!(status["timer_remaining_seconds"], 840);
+    assert_eq!(status["suspend_at"], "2025-01-01T00:15:00Z");
+    assert_eq!(status["last_action"], "timer-extend");
+}
+
+#[tokio::test(start_paused = true)]
+async fn snooze_and_reset_restart_from_now() {
+    let harness = Harness::start(10).await;
+    harness.advance(Duration::from_secs(500)).await;
+
+    harness.post("/timer/snooze?minutes=3").await;
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 180);
+
+    harness.advance(Duration::from_secs(60)).await;
+    let (code, _) = harness.post("/timer/reset").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 600);
+}
+
+#[tokio::test(start_paused = true)]
+async fn cancel_holds_until_the_next_state_change() {
+    let harness = Harness::start(1).await;
+
+    let (code, _) = harness.post("/timer/cancel").await;
+    assert_eq!(code, StatusCode::OK);
+
+    harness.advance(Duration::from_secs(120)).await;
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], false);
+    assert_eq!(status["phase"], "idle");
+    assert!(harness.history("sleep").await.is_empty());
+
+    // The next state change starts a fresh countdown
+    harness.post("/coffee").await;
+    harness.post("/chill").await;
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 60);
+}
+
+#[tokio::test(start_paused = true)]
+async fn commands_without_a_countdown_are_refused() {
+    let harness = Harness::start(1).await;
+    harness.post("/coffee").await;
+
+    for uri in ["/timer/extend", "/timer/snooze", "/timer/cancel", "/timer/reset"] {
+        let (code, body) = harness.post(uri).await;
+        assert_eq!(code, StatusCode::CONFLICT, "{}", uri);
+        assert_eq!(body["status"], "error");
+    }
+    assert_eq!(harness.status().await["last_action"], "coffee");
+}
+
+#[tokio::test(start_paused = true)]
+async fn suspend_now_runs_after_the_requested_delay() {
+    let harness = Harness::start(10).await;
+
+    let (code, body) = harness.post("/suspend?delay=30").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["message"], "Running suspend in 30s");
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 30);
+
+    harness.advance(Duration::from_secs(31)).await;
+    assert!(harness.state.is_suspended().unwrap());
+    assert_eq!(harness.status().await["last_action"], "suspend-suspend");
+}
+
+#[tokio::test(start_paused = true)]
+async fn suspend_while_held_needs_a_confirmation_token() {
+    let harness = Harness::start(10).await;
+    harness.post("/coffee").await;
+
+    let (code, body) = harness.post("/suspend").await;
+    assert_eq!(code, StatusCode::ACCEPTED);
+    assert_eq!(body["status"], "confirm");
+    let first_token = body["token"].as_str().unwrap().to_string();
+
+    // A wrong token just hands out a new one, replacing the first
+    let (code, _) = harness.post("/suspend?confirm=wrong").await;
+    assert_eq!(code, StatusCode::ACCEPTED);
+    let (code, body) = harness.post(&format!("/suspend?confirm={}", first_token)).await;
+    assert_eq!(code, StatusCode::ACCEPTED);
+    let token = body["token"].as_str().unwrap().to_string();
+
+    harness.advance(Duration::from_secs(5)).await;
+    assert!(!harness.state.is_suspended().unwrap());
+
+    let (code, _) = harness.post(&format!("/suspend?confirm={}", token)).await;
+    assert_eq!(code, StatusCode::OK);
+
+    // Confirmed requests are not cancelled by the active state
+    harness.advance(Duration::from_secs(2)).await;
+    assert!(harness.state.is_suspended().unwrap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn token_only_confirms_the_request_it_was_issued_for() {
+    let harness = Harness::start(10).await;
+    harness.post("/coffee").await;
+
+    let (_, body) = harness.post("/suspend?action=suspend").await;
+    let token = body["token"].as_str().unwrap().to_string();
+    let (code, _) = harness.post(&format!("/suspend?action=poweroff&confirm={}", token)).await;
+    assert_eq!(code, StatusCode::ACCEPTED);
+
+    let (_, body) = harness.post("/suspend?delay=30").await;
+    let token = body["token"].as_str().unwrap().to_string();
+    let (code, _) = harness.post(&format!("/suspend?delay=5&confirm={}", token)).await;
+    assert_eq!(code, StatusCode::ACCEPTED);
+}
+
+#[tokio::test(start_paused = true)]
+async fn oversized_durations_are_rejected() {
+    let harness = Harness::start(10).await;
+
+    let (code, _) = harness.post("/timer/extend?minutes=18446744073709551615").await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post("/timer/snooze?minutes=100000000").await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post("/suspend?delay=18446744073709551615").await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+
+    // Extensions can't add up past the longest timer either
+    for _ in 0..6 {
+        let (code, _) = harness.post("/timer/extend?minutes=1440").await;
+        assert_eq!(code, StatusCode::OK);
+    }
+    let (code, _) = harness.post("/timer/extend?minutes=1440").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+
+    // The timer task survived all of it
+    harness.advance(Duration::from_secs(60)).await;
+    assert_eq!(harness.status().await["timer_active"], true);
+}
@@ -0,0 +1,106 @@
+//! Debounce and blip handling of flapping states
+
+mod common;
+
+use std::time::Duration;
+use axum::http::StatusCode;
+use clap::Parser;
+
+use common::Harness;
+use order_coffee::{state::{BlipPolicy, TimerHysteresis}, Config};
+
+fn debounced() -> TimerHysteresis {
+    TimerHysteresis {
+        debounce: Duration::from_secs(30),
+        ..TimerHysteresis::default()
+    }
+}
+
+fn pausing() -> TimerHysteresis {
+    TimerHysteresis {
+        blip_policy: BlipPolicy::Pause,
+        blip_window: Duration::from_secs(60),
+        ..TimerHysteresis::default()
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn countdown_starts_after_the_debounce_time() {
+    let harness = Harness::start_configured(10, |state| state.with_hysteresis(debounced())).await;
+
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "idle");
+    assert_eq!(status["timer_active"], false);
+
+    harness.advance(Duration::from_secs(31)).await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["timer_remaining_seconds"], 599);
+}
+
+#[tokio::test(start_paused = true)]
+async fn flapping_within_the_debounce_time_never_starts_a_countdown() {
+    let harness = Harness::start_configured(10, |state| state.with_hysteresis(debounced())).await;
+
+    for _ in 0..5 {
+        harness.advance(Duration::from_secs(20)).await;
+        harness.post("/coffee").await;
+        harness.post("/chill").await;
+    }
+    assert_eq!(harness.status().await["timer_active"], false);
+
+    // 30 seconds after the last toggle
+    harness.advance(Duration::from_secs(30)).await;
+    let status = harness.status().await;
+    assert_eq!(status["timer_active"], true);
+    assert_eq!(status["timer_remaining_seconds"], 600);
+}
+
+#[tokio::test(start_paused = true)]
+async fn cancel_stops_a_pending_debounce() {
+    let harness = Harness::start_configured(10, |state| state.with_hysteresis(debounced())).await;
+
+    let (code, _) = harness.post("/timer/cancel").await;
+    assert_eq!(code, StatusCode::OK);
+    harness.advance(Duration::from_secs(60)).await;
+    assert_eq!(harness.status().await["timer_active"], false);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_blip_pauses_the_countdown() {
+    let harness = Harness::start_configured(10, |state| state.with_hysteresis(pausing())).await;
+    harness.advance(Duration::from_secs(300)).await;
+
+    harness.post("/coffee").await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "held");
+    assert_eq!(status["timer_active"], false);
+
+    harness.advance(Duration::from_secs(20)).await;
+    harness.post("/chill").await;
+    let status = harness.status().await;
+    assert_eq!(status["phase"], "counting_down");
+    assert_eq!(status["timer_remaining_seconds"], 300);
+    assert_eq!(status["suspend_at"], "2025-01-01T00:10:20Z");
+}
+
+#[tokio::test(start_paused = true)]
+async fn longer_activity_resets_the_countdown() {
+    let harness = Harness::start_configured(10, |state| state.with_hysteresis(pausing())).await;
+    harness.advance(Duration::from_secs(300)).await;
+
+    harness.post("/coffee").await;
+    harness.advance(Duration::from_secs(120)).await;
+    harness.post("/chill").await;
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 600);
+}
+
+#[test]
+fn hysteresis_arguments_longer_than_a_week_are_rejected() {
+    for arg in ["--debounce-seconds", "--blip-seconds"] {
+        let config = Config::try_parse_from(["order-coffee", arg, "18446744073709551615"]).unwrap();
+        assert!(config.validate().unwrap_err().contains(arg));
+    }
+    let config = Config::try_parse_from(["order-coffee", "--debounce-seconds", "604800"]).unwrap();
+    assert!(config.validate().is_ok());
+}
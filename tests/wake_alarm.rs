@@ -0,0 +1,150 @@
+//! RTC wake alarms: programmed before sleeping, holding the machine awake afterwards
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use chrono::{TimeZone, Utc};
+use common::{simulation, Harness};
+use order_coffee::services::{
+    write_rtc_wake_alarm, SimulationConfig, TimeOfDay, WakeAlarmConfig, WakeScheduleConfig,
+};
+use serde_json::json;
+
+/// The machine sleeps for an hour unless the wake alarm fires first
+fn long_sleep() -> SimulationConfig {
+    SimulationConfig {
+        sleep_seconds: 3600,
+        ..simulation()
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn wake_alarm_wakes_the_machine_and_holds_it_awake() {
+    let harness = Harness::start_with(1, long_sleep()).await;
+    let (code, body) = harness.post_json("/wake-alarm", json!({
+        "in_minutes": 5,
+        "name": "nightly-backup",
+        "start_services": ["ollama"],
+        "hold_minutes": 30,
+    })).await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["requested"]["at"], "2025-01-01T00:05:00Z");
+    assert_eq!(harness.status().await["next_wake_alarm"]["name"], "nightly-backup");
+
+    harness.advance(Duration::from_secs(61)).await;
+    assert_eq!(harness.status().await["phase"], "suspended");
+
+    // Woken up by the alarm rather than after the full hour
+    harness.advance(Duration::from_secs(240)).await;
+    let wakes = harness.history("scheduled_wake").await;
+    assert_eq!(wakes.len(), 1);
+    assert_eq!(wakes[0]["name"], "nightly-backup");
+    assert_eq!(wakes[0]["hold_until"], "2025-01-01T00:35:00Z");
+    assert!(harness.state.backend.is_service_active("ollama.service").await.unwrap());
+
+    let status = harness.status().await;
+    assert_eq!(status["states"]["holds"]["wake:nightly-backup"]["until"], "2025-01-01T00:35:00Z");
+    assert_eq!(status["states"]["services"]["ollama"], true);
+    assert_eq!(status["timer_active"], false);
+    assert!(status["next_wake_alarm"].is_null());
+
+    // The started service keeps the machine awake past the hold, like one started through the API
+    harness.advance(Duration::from_secs(30 * 60)).await;
+    let status = harness.status().await;
+    assert!(status["states"]["holds"].as_object().unwrap().is_empty());
+    assert_eq!(status["timer_active"], false);
+
+    harness.post("/service/ollama/stop").await;
+    assert_eq!(harness.status().await["phase"], "counting_down");
+}
+
+#[tokio::test(start_paused = true)]
+async fn early_wake_up_keeps_the_alarm_for_the_next_sleep() {
+    let harness = Harness::start(1).await;
+    harness.post_json("/wake-alarm", json!({ "in_minutes": 60, "hold_minutes": 10 })).await;
+
+    harness.advance(Duration::from_secs(61)).await;
+    harness.advance(Duration::from_secs(30)).await;
+    assert_eq!(harness.history("sleep").await.len(), 1);
+    assert!(harness.history("scheduled_wake").await.is_empty());
+    assert_eq!(harness.status().await["next_wake_alarm"]["name"], "wake-alarm");
+}
+
+#[tokio::test(start_paused = true)]
+async fn invalid_requests_are_rejected() {
+    let harness = Harness::start(10).await;
+
+    let (code, body) = harness.post_json("/wake-alarm", json!({ "at": "2024-12-31T23:00:00Z" })).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("in the past"));
+
+    // Absolute times get the same week as in_minutes
+    let (code, body) = harness.post_json("/wake-alarm", json!({ "at": "2025-01-08T00:01:00Z" })).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("more than 10080 minutes ahead"));
+    let (code, _) = harness.post_json("/wake-alarm", json!({ "at": "9999-12-31T23:59:59Z" })).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+    let (code, _) = harness.post_json("/wake-alarm", json!({ "at": "2025-01-08T00:00:00Z" })).await;
+    assert_eq!(code, StatusCode::OK);
+    harness.delete("/wake-alarm").await;
+
+    let (code, _) = harness.post_json("/wake-alarm", json!({ "in_minutes": 5, "start_services": ["nope"] })).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+
+    let (code, _) = harness.post_json("/wake-alarm", json!({})).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+
+    let (code, _) = harness.post_json("/wake-alarm", json!({ "in_minutes": u64::MAX })).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+
+    let (code, _) = harness.post_json("/wake-alarm", json!({ "in_minutes": 5, "hold_minutes": u64::MAX })).await;
+    assert_eq!(code, StatusCode::BAD_REQUEST);
+
+    let (code, _) = harness.delete("/wake-alarm").await;
+    assert_eq!(code, StatusCode::CONFLICT);
+
+    harness.post_json("/wake-alarm", json!({ "in_minutes": 5 })).await;
+    let (code, body) = harness.delete("/wake-alarm").await;
+    assert_eq!(code, StatusCode::OK);
+    assert!(body["next_wake_alarm"].is_null());
+}
+
+#[test]
+fn rtc_wake_alarm_is_written_below_the_sysfs_root() {
+    let root = std::env::temp_dir().join(format!("order-coffee-sysfs-{}", std::process::id()));
+    let rtc = root.join("class/rtc/rtc0");
+    std::fs::create_dir_all(&rtc).unwrap();
+
+    let at = Utc.with_ymd_and_hms(2025, 1, 1, 3, 0, 0).unwrap();
+    write_rtc_wake_alarm(&root, at).unwrap();
+    assert_eq!(std::fs::read_to_string(rtc.join("wakealarm")).unwrap(), at.timestamp().to_string());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn configured_holds_are_limited_to_a_week() {
+    let alarm = |hold_minutes| WakeAlarmConfig {
+        name: "backup".to_string(),
+        at: TimeOfDay::try_from("03:00".to_string()).unwrap(),
+        start_services: Vec::new(),
+        hold_minutes,
+    };
+    assert!(WakeScheduleConfig { alarms: vec![alarm(7 * 24 * 60)] }.validate().is_ok());
+    assert!(WakeScheduleConfig { alarms: vec![alarm(u64::MAX)] }.validate().is_err());
+}
+
+#[test]
+fn configured_alarms_need_a_valid_time_and_managed_services() {
+    let parse = |toml: &str| toml::from_str::<WakeScheduleConfig>(toml);
+
+    assert!(parse("[[alarms]]\nname = \"backup\"\nat = \"3 am\"\n").is_err());
+
+    let schedule = parse("[[alarms]]\nname = \"backup\"\nat = \"03:00\"\nstart_services = [\"nope\"]\n").unwrap();
+    assert!(schedule.validate().unwrap_err().contains("nope"));
+
+    let schedule = parse("[[alarms]]\nname = \"backup\"\nat = \"03:00\"\nstart_services = [\"ollama\"]\n").unwrap();
+    assert!(schedule.validate().is_ok());
+}
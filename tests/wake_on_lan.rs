@@ -0,0 +1,99 @@
+//! Wake-on-LAN packets for peers and the devices allowed to wake this machine
+
+mod common;
+
+use std::fs;
+
+use axum::http::StatusCode;
+use common::Harness;
+use order_coffee::services::{
+    magic_packet, parse_mac, read_wake_sources, write_wake_source, WakeOnLanConfig, WolPeerConfig,
+};
+use serde_json::json;
+
+fn peers() -> WakeOnLanConfig {
+    WakeOnLanConfig {
+        peers: vec![WolPeerConfig {
+            name: "nas".to_string(),
+            mac: "AA-BB-CC-00-11-22".to_string(),
+            broadcast: "192.168.1.255:9".to_string(),
+        }],
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn magic_packet_is_sent_to_configured_peers_only() {
+    let harness = Harness::start_configured(10, |state| state.with_wake_on_lan(peers())).await;
+
+    let (code, body) = harness.post("/wol/nas").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["broadcast"], "192.168.1.255:9");
+    assert_eq!(
+        harness.host().magic_packets(),
+        vec![("aa:bb:cc:00:11:22".to_string(), "192.168.1.255:9".to_string())]
+    );
+
+    let (code, _) = harness.post("/wol/printer").await;
+    assert_eq!(code, StatusCode::NOT_FOUND);
+    assert_eq!(harness.host().magic_packets().len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn wake_sources_can_be_listed_and_toggled() {
+    let harness = Harness::start(10).await;
+
+    let (code, body) = harness.get("/wake-sources").await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["wake_on_lan"], false);
+    assert_eq!(body["sources"][1]["id"], "net:eth0");
+
+    let (code, body) = harness.put_json("/wake-sources/net:eth0", json!({ "enabled": true })).await;
+    assert_eq!(code, StatusCode::OK);
+    assert_eq!(body["wake_on_lan"], true);
+    assert_eq!(body["sources"][1]["enabled"], true);
+
+    let (code, _) = harness.put_json("/wake-sources/net:wlan9", json!({ "enabled": true })).await;
+    assert_eq!(code, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn magic_packet_repeats_the_mac_after_a_sync_stream() {
+    let mac = parse_mac("01:23:45:67:89:ab").unwrap();
+    let packet = magic_packet(mac);
+    assert_eq!(packet.len(), 102);
+    assert_eq!(&packet[..6], &[0xff; 6]);
+    assert!(packet[6..].chunks(6).all(|chunk| chunk == mac));
+
+    assert!(parse_mac("01:23:45:67:89").is_err());
+    assert!(parse_mac("01:23:45:67:89:zz").is_err());
+}
+
+#[test]
+fn wake_sources_are_read_and_written_below_the_roots() {
+    let root = std::env::temp_dir().join(format!("order-coffee-wake-sources-{}", std::process::id()));
+    let sysfs = root.join("sys");
+    let procfs = root.join("proc");
+    let eth0 = sysfs.join("class/net/eth0/device/power");
+    fs::create_dir_all(&eth0).unwrap();
+    fs::create_dir_all(sysfs.join("class/net/lo")).unwrap();
+    fs::create_dir_all(procfs.join("acpi")).unwrap();
+    fs::write(eth0.join("wakeup"), "disabled\n").unwrap();
+    let acpi_wakeup = procfs.join("acpi/wakeup");
+    fs::write(&acpi_wakeup, "Device\tS-state\t  Status   Sysfs node\nGLAN\t  S4\t*enabled   pci:0000:00:1f.6\nXHC\t  S3\t*disabled  pci:0000:00:14.0\n").unwrap();
+
+    let sources = read_wake_sources(&sysfs, &procfs).unwrap();
+    let ids: Vec<_> = sources.iter().map(|source| (source.id.as_str(), source.enabled)).collect();
+    assert_eq!(ids, vec![("acpi:GLAN", true), ("acpi:XHC", false), ("net:eth0", false)]);
+
+    write_wake_source(&sysfs, &procfs, &sources[2], true).unwrap();
+    assert_eq!(fs::read_to_string(eth0.join("wakeup")).unwrap(), "enabled");
+
+    // ACPI devices are toggled by writing their name, and only when they have to change
+    fs::write(&acpi_wakeup, "").unwrap();
+    write_wake_source(&sysfs, &procfs, &sources[0], true).unwrap();
+    assert_eq!(fs::read_to_string(&acpi_wakeup).unwrap(), "");
+    write_wake_source(&sysfs, &procfs, &sources[1], true).unwrap();
+    assert_eq!(fs::read_to_string(&acpi_wakeup).unwrap(), "XHC");
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,76 @@
+//! Wake-ups announced by the simulated host
+
+mod common;
+
+use std::time::Duration;
+
+use common::{simulation, Harness};
+use order_coffee::services::{HookPipeline, HooksConfig, IdleAction, SimulationConfig};
+use serde_json::json;
+
+fn sleeping_for(sleep_seconds: u64) -> SimulationConfig {
+    SimulationConfig { sleep_seconds, ..simulation() }
+}
+
+#[tokio::test(start_paused = true)]
+async fn outside_sleep_is_recorded_and_restarts_the_countdown() {
+    let harness = Harness::start(10).await;
+    harness.advance(Duration::from_secs(300)).await;
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 300);
+
+    // E.g. the lid was closed; the suspension timer did not ask for this one
+    let mut resumed = harness.host().subscribe_resume();
+    harness.host().run_idle_action(IdleAction::Suspend).await.unwrap();
+    harness.advance(Duration::from_secs(30)).await;
+    assert_eq!(resumed.try_recv().unwrap(), Duration::from_secs(30));
+
+    let sleeps = harness.history("sleep").await;
+    assert_eq!(sleeps.len(), 1);
+    assert_eq!(sleeps[0]["suspended_at"], "2025-01-01T00:05:00Z");
+    assert_eq!(sleeps[0]["resumed_at"], "2025-01-01T00:05:30Z");
+    assert_eq!(sleeps[0]["duration_seconds"], 30);
+    assert_eq!(sleeps[0]["initiated_by_us"], false);
+    assert_eq!(harness.status().await["timer_remaining_seconds"], 600);
+}
+
+#[tokio::test(start_paused = true)]
+async fn wake_up_shortly_before_the_alarm_counts_as_the_scheduled_wake() {
+    // Suspended at 00:01, woken up 30 seconds before the 00:05 alarm
+    let harness = Harness::start_with(1, sleeping_for(210)).await;
+    harness.post_json("/wake-alarm", json!({ "in_minutes": 5, "name": "backup", "hold_minutes": 10 })).await;
+
+    harness.advance(Duration::from_secs(60)).await;
+    harness.advance(Duration::from_secs(210)).await;
+    let wakes = harness.history("scheduled_wake").await;
+    assert_eq!(wakes.len(), 1);
+    assert_eq!(wakes[0]["alarm_at"], "2025-01-01T00:05:00Z");
+    assert_eq!(wakes[0]["hold_until"], "2025-01-01T00:14:30Z");
+    assert_eq!(harness.status().await["states"]["holds"]["wake:backup"]["owner"], "wake-alarm");
+}
+
+#[tokio::test(start_paused = true)]
+async fn post_resume_hooks_run_after_a_wake_up() {
+    let hooks: HooksConfig = toml::from_str(
+        r#"
+        [[pre_suspend]]
+        name = "stop-ollama"
+        stop_services = ["ollama"]
+
+        [[post_resume]]
+        name = "restore"
+        restore_services = true
+        "#,
+    )
+    .unwrap();
+    let harness = Harness::start_configured(1, |state| state.with_hooks(HookPipeline::new(hooks))).await;
+    harness.host().start_service("ollama.service").await.unwrap();
+
+    harness.advance(Duration::from_secs(60)).await;
+    assert!(!harness.host().is_service_active("ollama.service").await.unwrap());
+
+    harness.advance(Duration::from_secs(30)).await;
+    let run = harness.status().await["last_hook_run"].clone();
+    assert_eq!(run["stage"], "post_resume");
+    assert_eq!(run["finished_at"], "2025-01-01T00:01:30Z");
+    assert!(harness.host().is_service_active("ollama.service").await.unwrap());
+}